@@ -0,0 +1,46 @@
+#ifndef IMGCMP_H
+#define IMGCMP_H
+
+/*
+ * C interface of imgcmp (build the shared library with `cargo build --release --features ffi`).
+ */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The comparison options. Pass NULL instead of a pointer to this struct to use the defaults. */
+typedef struct {
+    float value_threshold;        /* A threshold [0-1] on the maximum allowed per-channel error (same as -t). */
+    const char *error_threshold;  /* The number (or percentage) of pixels allowed to be different (same as -e). NULL for "0". */
+    uint32_t shift_tolerance;     /* Same as --shift-tolerance. */
+    uint32_t ignore_border;       /* Same as --ignore-border. */
+} imgcmp_options_t;
+
+/* The comparison result. */
+typedef struct {
+    bool matched;
+    uint32_t wrong_pixels;
+    uint32_t total_pixels;
+    char *error_message;          /* NULL unless the comparison could not be done. Owned by the result. */
+} imgcmp_result_t;
+
+/*
+ * Compare the images at the two given paths.
+ * If the comparison could not be done (e.g. invalid options, unreadable images or different sizes),
+ * `matched` is false and `error_message` describes the error.
+ * The result must be released with imgcmp_result_free.
+ */
+imgcmp_result_t imgcmp_compare(const char *path1, const char *path2, const imgcmp_options_t *options);
+
+/* Release the memory owned by a result. It is safe to call it more than once. */
+void imgcmp_result_free(imgcmp_result_t *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* IMGCMP_H */
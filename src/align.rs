@@ -0,0 +1,91 @@
+use image::imageops;
+
+use crate::{ImagePair, luma};
+
+// Return the luminance of every pixel of the two images in row-major order.
+fn luminance(images: &ImagePair) -> [Vec<f32>; 2] {
+    match images {
+        ImagePair::Integer(img1, img2) => [img1, img2].map(|image| {
+            image.pixels().map(|pixel| luma(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32)).collect()
+        }),
+        ImagePair::Float(img1, img2) => [img1, img2].map(|image| {
+            image.pixels().map(|pixel| luma(pixel[0], pixel[1], pixel[2])).collect()
+        }),
+    }
+}
+
+// Compute the normalized cross-correlation between the first image and the second image shifted by the given offset,
+// over the region where they overlap. The result is in [-1, 1] (or 0 if either region has no variance).
+fn normalized_cross_correlation(luma: &[Vec<f32>; 2], size: (u32, u32), offset: (i32, i32)) -> f64 {
+    let (width, height) = (size.0 as i64, size.1 as i64);
+    let (dx, dy) = (offset.0 as i64, offset.1 as i64);
+    let (mut sum1, mut sum2, mut sum11, mut sum22, mut sum12) = (0f64, 0f64, 0f64, 0f64, 0f64);
+    let mut count = 0f64;
+    for y in 0.max(-dy)..height.min(height - dy) {
+        for x in 0.max(-dx)..width.min(width - dx) {
+            let v1 = luma[0][(y * width + x) as usize] as f64;
+            let v2 = luma[1][((y + dy) * width + x + dx) as usize] as f64;
+            sum1 += v1;
+            sum2 += v2;
+            sum11 += v1 * v1;
+            sum22 += v2 * v2;
+            sum12 += v1 * v2;
+            count += 1f64;
+        }
+    }
+    let variance = (count * sum11 - sum1 * sum1) * (count * sum22 - sum2 * sum2);
+    if variance <= 0f64 { return 0f64; }
+    (count * sum12 - sum1 * sum2) / variance.sqrt()
+}
+
+// Estimate the integer translation (dx, dy) such that the pixel (x, y) of the first image corresponds to the pixel (x + dx, y + dy)
+// of the second image, by maximizing the normalized cross-correlation over a search window of the given radius.
+// Both images must have the same size. Ties are broken in favor of the offsets closest to (0, 0).
+pub fn estimate_offset(images: &ImagePair, radius: u32) -> (i32, i32) {
+    let size = images.dimensions()[0];
+    let luma = luminance(images);
+    let radius_x = radius.min(size.0.saturating_sub(1)) as i32;
+    let radius_y = radius.min(size.1.saturating_sub(1)) as i32;
+
+    let mut candidates: Vec<(i32, i32)> = (-radius_y..=radius_y)
+        .flat_map(|dy| (-radius_x..=radius_x).map(move |dx| (dx, dy)))
+        .collect();
+    candidates.sort_by_key(|(dx, dy)| dx.abs().max(dy.abs()));
+
+    let mut best = ((0, 0), f64::NEG_INFINITY);
+    for offset in candidates {
+        let score = normalized_cross_correlation(&luma, size, offset);
+        if score > best.1 { best = (offset, score); }
+    }
+    best.0
+}
+
+// Return the origins (in the first and second images) and the size of the region where two images of the given size overlap
+// after shifting the second image by the given offset.
+fn overlap(size: (u32, u32), offset: (i32, i32)) -> [(u32, u32); 3] {
+    let (dx, dy) = offset;
+    let origin1 = (0.max(-dx) as u32, 0.max(-dy) as u32);
+    let origin2 = (0.max(dx) as u32, 0.max(dy) as u32);
+    [origin1, origin2, (size.0 - dx.unsigned_abs(), size.1 - dy.unsigned_abs())]
+}
+
+// Crop an image aligned with the first image (e.g. a tolerance map) to the region where the images overlap.
+pub fn crop_first_to_overlap(image: &image::GrayImage, offset: (i32, i32)) -> image::GrayImage {
+    let [origin1, _, size] = overlap(image.dimensions(), offset);
+    imageops::crop_imm(image, origin1.0, origin1.1, size.0, size.1).to_image()
+}
+
+// Crop both images to the region where they overlap after shifting the second image by the given offset.
+pub fn crop_to_overlap(images: &ImagePair, offset: (i32, i32)) -> ImagePair {
+    let [origin1, origin2, size] = overlap(images.dimensions()[0], offset);
+    match images {
+        ImagePair::Integer(img1, img2) => ImagePair::Integer(
+            imageops::crop_imm(img1, origin1.0, origin1.1, size.0, size.1).to_image(),
+            imageops::crop_imm(img2, origin2.0, origin2.1, size.0, size.1).to_image(),
+        ),
+        ImagePair::Float(img1, img2) => ImagePair::Float(
+            imageops::crop_imm(img1, origin1.0, origin1.1, size.0, size.1).to_image(),
+            imageops::crop_imm(img2, origin2.0, origin2.1, size.0, size.1).to_image(),
+        ),
+    }
+}
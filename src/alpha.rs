@@ -0,0 +1,38 @@
+// Conversion of images with premultiplied alpha (where the color channels are already multiplied by the alpha)
+// back to straight alpha, so their colors can be compared in the transparent regions too.
+
+use image::{DynamicImage, Pixel};
+
+// Divide the color channels of each pixel by its alpha, clamping them to the range of the channels.
+// The fully transparent pixels (A = 0) have no color, so they are set to transparent black: two of them always match,
+// which excludes them from the comparison, while such a pixel against a visible one is compared as black.
+// Images without alpha are returned as they are.
+pub fn unpremultiply(image: DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLumaA8(_) | DynamicImage::ImageRgba8(_) => {
+            let mut image = image.into_rgba8();
+            for pixel in image.pixels_mut() {
+                let alpha = pixel[3] as u32;
+                pixel.apply_without_alpha(|value| (value as u32 * 255 + alpha / 2).checked_div(alpha).map_or(0, |value| value.min(255) as u8));
+            }
+            DynamicImage::ImageRgba8(image)
+        },
+        DynamicImage::ImageLumaA16(_) | DynamicImage::ImageRgba16(_) => {
+            let mut image = image.into_rgba16();
+            for pixel in image.pixels_mut() {
+                let alpha = pixel[3] as u64;
+                pixel.apply_without_alpha(|value| (value as u64 * 65535 + alpha / 2).checked_div(alpha).map_or(0, |value| value.min(65535) as u16));
+            }
+            DynamicImage::ImageRgba16(image)
+        },
+        DynamicImage::ImageRgba32F(mut image) => {
+            // Float images aren't clamped, since their colors can exceed 1 (e.g. HDR).
+            for pixel in image.pixels_mut() {
+                let alpha = pixel[3];
+                pixel.apply_without_alpha(|value| if alpha <= 0f32 { 0f32 } else { value / alpha });
+            }
+            DynamicImage::ImageRgba32F(image)
+        },
+        image => image,
+    }
+}
@@ -0,0 +1,65 @@
+use std::{ffi::OsString, fs, path::Path};
+
+use anyhow::Context;
+use clap::{ArgMatches, Command, arg};
+use image::io::Reader;
+
+use crate::metadata;
+
+// The `baseline` subcommand, which manages baseline images without an external baseline store:
+// `baseline save <IMAGE> <BASELINE_PATH>` stores the image as a baseline, and
+// `baseline compare <IMAGE> <BASELINE_PATH> [OPTIONS]...` compares the image against the baseline with the usual options.
+pub fn command() -> Command {
+    Command::new("baseline")
+        .about("Saves an image as a baseline, or compares an image against its baseline")
+        .subcommand_required(true)
+        .subcommand(Command::new("save")
+            .about("Stores the image as a PNG baseline (whatever its format), with its source path embedded as metadata. Parent directories are created as needed.")
+            .arg(arg!(<image> "The path to the image to store"))
+            .arg(arg!(<baseline_path> "The path to the baseline")))
+        .subcommand(Command::new("compare")
+            .about("Compares the image against the baseline (as the first image), using the options that follow (e.g. -t 0.1 -o diff.png) or the configuration")
+            .arg(arg!(<image> "The path to the image to compare"))
+            .arg(arg!(<baseline_path> "The path to the baseline"))
+            .arg(arg!([options] ... "The comparison options").trailing_var_arg(true).allow_hyphen_values(true)))
+}
+
+// What the baseline subcommand asks the command to do next.
+pub enum Action {
+    Saved(String),              // The image was stored at the given baseline path.
+    Compare(Vec<OsString>),     // Compare the images using the given command line (without the program name).
+}
+
+// Store the image as a PNG baseline, embedding the path of the image it was saved from.
+fn save(image_path: &str, baseline_path: &str) -> anyhow::Result<()> {
+    let reader = Reader::open(image_path).context(format!("Failed to read {}", image_path))?
+        .with_guessed_format().context(format!("Failed to read {}", image_path))?;
+    let img = reader.decode().context(format!("Failed to decode {}", image_path))?;
+    if let Some(parent) = Path::new(baseline_path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+    }
+    let entries = [
+        ("source_image", image_path.to_string()),
+        ("Software", format!("{} {}", clap::crate_name!(), clap::crate_version!())),
+    ];
+    metadata::save_png(baseline_path, &img, &entries, png::Compression::Default)
+}
+
+// Run the baseline subcommand with the given matches. Saving is done right away,
+// while a comparison is delegated to the command as the baseline and image paths followed by the options.
+pub fn run(args: &ArgMatches) -> anyhow::Result<Action> {
+    let paths = |args: &ArgMatches| ["image", "baseline_path"].map(|name| args.get_one::<String>(name).expect("The paths are required").clone());
+    match args.subcommand() {
+        Some(("save", args)) => {
+            let [image_path, baseline_path] = paths(args);
+            save(&image_path, &baseline_path)?;
+            Ok(Action::Saved(baseline_path))
+        },
+        Some(("compare", args)) => {
+            let [image_path, baseline_path] = paths(args);
+            let options = args.get_many::<String>("options").into_iter().flatten().cloned();
+            Ok(Action::Compare([baseline_path, image_path].into_iter().chain(options).map(OsString::from).collect()))
+        },
+        _ => Err(anyhow::Error::msg("Expected the save or compare subcommand")),
+    }
+}
@@ -0,0 +1,105 @@
+use std::{borrow::Cow, collections::BTreeMap, path::{Path, PathBuf}};
+
+use anyhow::Context;
+
+use imgcmp::{ComparisonOptions, ComparisonOutcome, Threshold};
+
+// A pair of images to compare in a run, with optional overrides of the options given on the command line (e.g. from a manifest).
+pub struct Pair {
+    pub image_paths: [String; 2],   // The paths to the first (expected) and second (actual) images.
+    pub threshold: Option<f32>,     // Overrides the value threshold [0-1].
+    pub error: Option<Threshold>,   // Overrides the error threshold.
+    pub output: Option<String>,     // Overrides the path to the error image.
+}
+
+impl Pair {
+    // Create a pair that uses the options given on the command line.
+    pub fn new(image_paths: [String; 2]) -> Self {
+        Pair { image_paths, threshold: None, error: None, output: None }
+    }
+
+    // Return the given comparison options with the overrides of the pair applied.
+    pub fn comparison_options<'a>(&self, options: &'a ComparisonOptions) -> Cow<'a, ComparisonOptions> {
+        if self.threshold.is_none() && self.error.is_none() {
+            return Cow::Borrowed(options);
+        }
+        let mut options = options.clone();
+        if let Some(threshold) = self.threshold {
+            options.value_threshold = (threshold * 255f32) as u8;
+            options.float_value_threshold = threshold;
+        }
+        if let Some(error) = &self.error {
+            options.error_threshold = error.clone();
+        }
+        Cow::Owned(options)
+    }
+}
+
+// The comparison of a pair of images in a run (which may contain one or more pairs).
+pub struct Comparison {
+    pub image_paths: [String; 2],   // The paths to the first and second images.
+    pub output: Option<String>,     // The path to the error image of the pair (if requested).
+    pub result: anyhow::Result<ComparisonOutcome>, // The outcome of the comparison (or the error that prevented it).
+    pub updated: bool,              // True if the first image (the baseline) was replaced by the second one.
+}
+
+// Return true if the path contains any glob wildcards.
+pub fn is_glob_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+// Return the static prefix of a glob pattern, which is made of its leading path components that don't contain any wildcards.
+fn static_prefix(pattern: &str) -> PathBuf {
+    Path::new(pattern).components().take_while(|component| !is_glob_pattern(&component.as_os_str().to_string_lossy())).collect()
+}
+
+// Return the files matching the given glob pattern, keyed by their path relative to the static prefix of the pattern
+// without the extension (so "golden/**/*.png" and "out/**/*.exr" can be paired), along with the static prefix.
+fn glob_files(pattern: &str) -> anyhow::Result<(PathBuf, BTreeMap<PathBuf, PathBuf>)> {
+    let prefix = static_prefix(pattern);
+    let mut files = BTreeMap::new();
+    for path in glob::glob(pattern).context(format!("Invalid glob pattern \"{}\"", pattern))? {
+        let path = path.map_err(|err| anyhow::Error::new(std::io::Error::from(err)).context(format!("Failed to expand {}", pattern)))?;
+        if !path.is_file() {
+            continue;
+        }
+        let key = path.strip_prefix(&prefix).unwrap_or(&path).with_extension("");
+        if let Some(other) = files.insert(key, path.clone()) {
+            return Err(anyhow::Error::msg(format!("The glob pattern {} matched both {} and {}, which can't be told apart (only their extensions differ)",
+                pattern, other.display(), path.display())));
+        }
+    }
+    Ok((prefix, files))
+}
+
+// Expand the two glob patterns and pair the matched files by their paths relative to the static prefixes of the patterns
+// (ignoring the extensions), sorted by these relative paths. The patterns must match at least one file.
+// Return the matched pairs and the unmatched files, which are paired with the path their counterpart would have (which doesn't exist).
+pub fn glob_pairs(patterns: [&str; 2]) -> anyhow::Result<(Vec<Pair>, Vec<Pair>)> {
+    let (prefix1, files1) = glob_files(patterns[0])?;
+    let (prefix2, mut files2) = glob_files(patterns[1])?;
+    if files1.is_empty() && files2.is_empty() {
+        return Err(anyhow::Error::msg(format!("The glob patterns {} and {} didn't match any files", patterns[0], patterns[1])));
+    }
+
+    let to_string = |path: &Path| path.to_string_lossy().into_owned();
+    // The counterpart of an unmatched file has the same path relative to the other prefix.
+    let counterpart = |path: &Path, prefix: &Path, other_prefix: &Path| to_string(&other_prefix.join(path.strip_prefix(prefix).unwrap_or(path)));
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    for (key, file1) in files1 {
+        match files2.remove(&key) {
+            Some(file2) => pairs.push(Pair::new([to_string(&file1), to_string(&file2)])),
+            None => unmatched.push(Pair::new([to_string(&file1), counterpart(&file1, &prefix1, &prefix2)])),
+        }
+    }
+    unmatched.extend(files2.into_values().map(|file2| Pair::new([counterpart(&file2, &prefix2, &prefix1), to_string(&file2)])));
+    Ok((pairs, unmatched))
+}
+
+// Return the path of the error image of a pair in a batch run: a PNG image inside the output directory,
+// named after the file stem of the second image.
+pub fn output_path(output_dir: &str, image_paths: &[String; 2]) -> String {
+    let name = Path::new(&image_paths[1]).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    Path::new(output_dir).join(format!("{}.png", name)).to_string_lossy().into_owned()
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+// The wall-clock timings of repeated runs of the comparison.
+pub struct Timings {
+    durations: Vec<Duration>, // The duration of every run, sorted in ascending order.
+    pixels: u64,              // The number of pixels compared in each run.
+}
+
+impl Timings {
+    // Run the given function the given number of times (at least once), and record how long each run takes.
+    // The function returns the number of compared pixels. The first error stops the measurement.
+    pub fn measure(iterations: u32, mut run: impl FnMut() -> anyhow::Result<u64>) -> anyhow::Result<Timings> {
+        let mut durations = Vec::with_capacity(iterations as usize);
+        let mut pixels = 0;
+        for _ in 0..iterations.max(1) {
+            let start = Instant::now();
+            pixels = run()?;
+            durations.push(start.elapsed());
+        }
+        durations.sort();
+        Ok(Timings { durations, pixels })
+    }
+
+    fn mean(&self) -> Duration {
+        self.durations.iter().sum::<Duration>() / self.durations.len() as u32
+    }
+
+    fn median(&self) -> Duration {
+        let middle = self.durations.len() / 2;
+        if self.durations.len().is_multiple_of(2) {
+            (self.durations[middle - 1] + self.durations[middle]) / 2
+        } else {
+            self.durations[middle]
+        }
+    }
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000f64;
+        let mean = self.mean();
+        let megapixels = self.pixels as f64 / 1e6;
+        writeln!(f, "Benchmark: {} runs of {:.3} megapixels", self.durations.len(), megapixels)?;
+        writeln!(f, "    min: {:.3} ms, max: {:.3} ms, mean: {:.3} ms, median: {:.3} ms",
+            milliseconds(self.durations[0]), milliseconds(self.durations[self.durations.len() - 1]),
+            milliseconds(mean), milliseconds(self.median()))?;
+        write!(f, "    throughput: {:.3} megapixels/s", megapixels / mean.as_secs_f64().max(f64::EPSILON))
+    }
+}
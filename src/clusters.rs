@@ -0,0 +1,99 @@
+use crate::BoundingBox;
+
+// The neighborhood used to decide whether two pixels belong to the same cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Four,   // Only horizontal and vertical neighbors are connected.
+    Eight,  // Diagonal neighbors are connected too.
+}
+
+impl Connectivity {
+    // Return the offsets of the neighbors of a pixel.
+    fn offsets(&self) -> &'static [(i64, i64)] {
+        match self {
+            Connectivity::Four => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+            Connectivity::Eight => &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)],
+        }
+    }
+}
+
+impl TryFrom<&str> for Connectivity {
+    type Error = anyhow::Error;
+    // Try to parse a string into a connectivity. The accepted values are "4" and "8".
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "4" => Ok(Connectivity::Four),
+            "8" => Ok(Connectivity::Eight),
+            _ => Err(anyhow::Error::msg(format!("Invalid connectivity \"{}\" (expected 4 or 8)", value))),
+        }
+    }
+}
+
+// A connected region of different pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Cluster {
+    pub bbox: BoundingBox,  // The box enclosing the cluster.
+    pub pixel_count: u32,   // The number of different pixels in the cluster.
+    pub max_error: f32,     // The largest per-channel error among the pixels of the cluster (in [0-1] for 8-bit images).
+}
+
+// Visit the connected components of the mismatch mask in row-major order of discovery, passing the row-major indices of the pixels of each one.
+// The mask is stored in row-major order, and it is true for the pixels that are different.
+fn visit_components(mask: &[bool], size: (u32, u32), connectivity: Connectivity, mut visit: impl FnMut(&[usize])) {
+    let (width, height) = (size.0 as i64, size.1 as i64);
+    let mut visited = vec![false; mask.len()];
+    let mut stack = Vec::new();
+    let mut component = Vec::new();
+
+    for start in 0..mask.len() {
+        if !mask[start] || visited[start] { continue; }
+
+        // Flood fill the component starting from this pixel.
+        component.clear();
+        visited[start] = true;
+        stack.push(start);
+        while let Some(index) = stack.pop() {
+            component.push(index);
+            let (x, y) = (index as i64 % width, index as i64 / width);
+            for (dx, dy) in connectivity.offsets() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width || ny >= height { continue; }
+                let neighbor = (ny * width + nx) as usize;
+                if mask[neighbor] && !visited[neighbor] {
+                    visited[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        visit(&component);
+    }
+}
+
+// Label the connected components of the mismatch mask and return them in row-major order of discovery.
+// The mask and the per-pixel errors are stored in row-major order, and the mask is true for the pixels that are different.
+pub fn find_clusters(mask: &[bool], errors: &[f32], size: (u32, u32), connectivity: Connectivity) -> Vec<Cluster> {
+    let width = size.0 as usize;
+    let mut clusters = Vec::new();
+    visit_components(mask, size, connectivity, |component| {
+        let (x, y) = ((component[0] % width) as u32, (component[0] / width) as u32);
+        let mut cluster = Cluster { bbox: BoundingBox::new(x, y), pixel_count: 0, max_error: 0f32 };
+        for &index in component {
+            cluster.bbox.include((index % width) as u32, (index / width) as u32);
+            cluster.pixel_count += 1;
+            cluster.max_error = cluster.max_error.max(errors[index]);
+        }
+        clusters.push(cluster);
+    });
+    clusters
+}
+
+// Clear the connected components of the mismatch mask that have fewer than the given number of pixels,
+// and return the row-major indices of the cleared pixels.
+pub fn discard_small_clusters(mask: &mut [bool], size: (u32, u32), connectivity: Connectivity, min_size: u32) -> Vec<usize> {
+    let mut discarded = Vec::new();
+    visit_components(mask, size, connectivity, |component| {
+        if (component.len() as u64) < min_size as u64 { discarded.extend_from_slice(component); }
+    });
+    for &index in &discarded { mask[index] = false; }
+    discarded
+}
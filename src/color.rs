@@ -0,0 +1,54 @@
+use std::io::IsTerminal;
+
+// When the results printed on stdout are colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,   // Only if stdout is a terminal and NO_COLOR isn't set (see https://no-color.org).
+    Always,
+    Never,
+}
+
+impl TryFrom<&str> for ColorMode {
+    type Error = anyhow::Error;
+    // Try to parse a string into a color mode.
+    fn try_from(value: &str) -> Result<Self, anyhow::Error> {
+        match value {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(anyhow::Error::msg(format!("Invalid color mode \"{}\" (expected auto, always or never)", value))),
+        }
+    }
+}
+
+impl ColorMode {
+    // Return true if the output should be colored in this mode.
+    pub fn is_enabled(self) -> bool {
+        match self {
+            ColorMode::Auto => std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && std::io::stdout().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+// The colors of the printed results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Red,
+    Yellow,
+}
+
+// Return the text wrapped in the ANSI codes of the color if coloring is enabled, or the text as-is otherwise.
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+    if !enabled {
+        return text.to_string();
+    }
+    let code = match color {
+        Color::Green => 32,
+        Color::Red => 31,
+        Color::Yellow => 33,
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
@@ -0,0 +1,76 @@
+use std::{env, ffi::OsString, fs, path::PathBuf};
+
+use anyhow::Context;
+use clap::{ArgAction, ArgMatches, Command, parser::ValueSource};
+use serde::Deserialize;
+
+// The name of the implicit configuration file in the home directory.
+const DEFAULT_CONFIG_NAME: &str = ".imgcmp.toml";
+// The name of the implicit configuration file of a project, which is looked up in the current directory.
+const PROJECT_CONFIG_NAME: &str = "imgcmp.toml";
+
+// The default options read from a TOML configuration file.
+// The keys are the long flag names (e.g. `threshold = 0.02` or `error = "1%"`), flags are booleans,
+// and options that can be repeated (e.g. `channel`) can be given as arrays.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct Config {
+    values: toml::Table,
+}
+
+impl Config {
+    // Read the configuration file at the given path.
+    pub fn load(path: &str) -> anyhow::Result<Config> {
+        let text = fs::read_to_string(path).context(format!("Failed to read {}", path))?;
+        toml::from_str(&text).context(format!("Failed to parse {}", path))
+    }
+
+    // Return the path to the implicit configuration file if it exists: imgcmp.toml in the current directory,
+    // or ~/.imgcmp.toml otherwise (only the first one found is read).
+    pub fn default_path() -> Option<PathBuf> {
+        let project = PathBuf::from(PROJECT_CONFIG_NAME);
+        if project.is_file() {
+            return Some(project);
+        }
+        let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+        let path = PathBuf::from(home).join(DEFAULT_CONFIG_NAME);
+        path.is_file().then_some(path)
+    }
+
+    // Convert the configuration into command-line arguments of the given command.
+    // The options that were given on the command line (in the given matches) are skipped, since the command line overrides the configuration.
+    pub fn to_args(&self, command: &Command, args: &ArgMatches) -> anyhow::Result<Vec<OsString>> {
+        let mut config_args = Vec::new();
+        for (key, value) in &self.values {
+            let arg = command.get_arguments()
+                .find(|arg| arg.get_long() == Some(key.as_str()) && key != "config")
+                .ok_or(anyhow::Error::msg(format!("Unknown option \"{}\" in the configuration", key)))?;
+            if args.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+                continue;
+            }
+
+            let invalid = || anyhow::Error::msg(format!("Invalid value for \"{}\" in the configuration: {}", key, value));
+            if !arg.get_action().takes_values() {
+                if value.as_bool().ok_or_else(invalid)? {
+                    config_args.push(OsString::from(format!("--{}", key)));
+                }
+                continue;
+            }
+
+            let values = match value {
+                toml::Value::Array(values) if matches!(arg.get_action(), ArgAction::Append) => values.iter().collect(),
+                value => vec![value],
+            };
+            for value in values {
+                let value = match value {
+                    toml::Value::String(value) => value.clone(),
+                    toml::Value::Integer(value) => value.to_string(),
+                    toml::Value::Float(value) => value.to_string(),
+                    _ => return Err(invalid()),
+                };
+                config_args.push(OsString::from(format!("--{}={}", key, value)));
+            }
+        }
+        Ok(config_args)
+    }
+}
@@ -0,0 +1,41 @@
+use std::{fs::OpenOptions, io::Write, path::Path};
+
+use anyhow::Context;
+
+use crate::{Status, batch::Comparison, output_manifest::csv_field};
+
+// The header of the CSV report.
+const HEADER: &str = "expected,actual,width,height,wrong_pixels,total_pixels,percentage,status,output\n";
+
+// Return the row of a comparison in the CSV report. The size is that of the first image, and the pixel counts are only known
+// if the images could be compared and have the same size (the unknown fields are empty).
+fn row(comparison: &Comparison) -> String {
+    let outcome = comparison.result.as_ref().ok().filter(|_| !comparison.updated);
+    let size = outcome.map(|outcome| outcome.sizes[0]);
+    let counts = outcome.filter(|outcome| !outcome.is_size_mismatch());
+    let fields = [
+        csv_field(&comparison.image_paths[0]),
+        csv_field(&comparison.image_paths[1]),
+        size.map(|size| size.0.to_string()).unwrap_or_default(),
+        size.map(|size| size.1.to_string()).unwrap_or_default(),
+        counts.map(|outcome| outcome.wrong_pixels.to_string()).unwrap_or_default(),
+        counts.map(|outcome| outcome.total_pixels.to_string()).unwrap_or_default(),
+        counts.map(|outcome| outcome.wrong_percentage().to_string()).unwrap_or_default(),
+        Status::from(comparison).name().to_string(),
+        comparison.output.as_deref().map(csv_field).unwrap_or_default(),
+    ];
+    fields.join(",") + "\n"
+}
+
+// Write the CSV report of a batch run, with one row per compared pair (including those that couldn't be compared).
+// If appending, the rows are added to the end of an existing file without a header (which is only written to a new file).
+pub fn write_report(path: &str, comparisons: &[Comparison], append: bool) -> anyhow::Result<()> {
+    let header = !append || !Path::new(path).exists();
+    let mut text = if header { HEADER.to_string() } else { String::new() };
+    for comparison in comparisons {
+        text += &row(comparison);
+    }
+    let mut file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)
+        .context(format!("Failed to open {}", path))?;
+    file.write_all(text.as_bytes()).context(format!("Failed to write {}", path))
+}
@@ -0,0 +1,83 @@
+// Simulation of color vision deficiencies, so two images can be compared as a color-blind viewer would see them.
+// The transforms are the matrices of Machado, Oliveira and Fernandes in "A Physiologically-based Model for Simulation
+// of Color Vision Deficiency" (2009) at full severity, which apply to linear RGB colors (with the sRGB primaries).
+
+use image::DynamicImage;
+
+use crate::flip::{multiply, srgb_to_linear};
+
+// A color vision deficiency (dichromacy), where one type of cone is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deficiency {
+    Protanopia,     // The long-wavelength (red) cones are missing.
+    Deuteranopia,   // The medium-wavelength (green) cones are missing.
+    Tritanopia,     // The short-wavelength (blue) cones are missing.
+}
+
+impl TryFrom<&str> for Deficiency {
+    type Error = anyhow::Error;
+    // Try to parse a string into a deficiency.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "protanopia" => Ok(Deficiency::Protanopia),
+            "deuteranopia" => Ok(Deficiency::Deuteranopia),
+            "tritanopia" => Ok(Deficiency::Tritanopia),
+            _ => Err(anyhow::Error::msg(format!("Invalid deficiency \"{}\" (expected protanopia, deuteranopia or tritanopia)", value))),
+        }
+    }
+}
+
+impl Deficiency {
+    // Return the matrix that transforms a linear RGB color into the color perceived with the deficiency.
+    fn matrix(&self) -> [[f32; 3]; 3] {
+        match self {
+            Deficiency::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Deficiency::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Deficiency::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+// Return the image as seen with the given deficiency. The colors of 8-bit (and 16-bit) images are decoded from sRGB,
+// transformed, clamped to [0-1] and encoded again, while float images are transformed as they are since they are already linear.
+// The alpha channel is kept.
+pub fn simulate(image: DynamicImage, deficiency: Deficiency) -> DynamicImage {
+    let matrix = deficiency.matrix();
+    match image {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            let mut image = image.into_rgba32f();
+            for pixel in image.pixels_mut() {
+                let [r, g, b] = multiply(&matrix, [pixel[0], pixel[1], pixel[2]]);
+                (pixel[0], pixel[1], pixel[2]) = (r, g, b);
+            }
+            DynamicImage::ImageRgba32F(image)
+        },
+        image => {
+            let mut image = image.into_rgba8();
+            let linear: Vec<f32> = (0..=255).map(|value| srgb_to_linear(value as f32 / 255.0)).collect();
+            for pixel in image.pixels_mut() {
+                let rgb = multiply(&matrix, [0, 1, 2].map(|c| linear[pixel[c] as usize]));
+                for (c, value) in rgb.into_iter().enumerate() {
+                    pixel[c] = (linear_to_srgb(value.clamp(0.0, 1.0)) * 255.0).round() as u8;
+                }
+            }
+            DynamicImage::ImageRgba8(image)
+        },
+    }
+}
@@ -0,0 +1,61 @@
+use std::{fs, io::{BufWriter, Write}};
+
+use anyhow::Context;
+
+// The format of the file listing the different pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,    // One x,y,dr,dg,db line per pixel.
+    Json,   // A compact array of [x, y, dr, dg, db] arrays.
+}
+
+impl TryFrom<&str> for Format {
+    type Error = anyhow::Error;
+    // Try to parse a string into a dump format.
+    fn try_from(value: &str) -> Result<Self, anyhow::Error> {
+        match value {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow::Error::msg(format!("Invalid dump format \"{}\" (expected csv or json)", value))),
+        }
+    }
+}
+
+// The number of different pixels in a dump, and in total (more if the dump was truncated).
+#[derive(Debug, Clone, Copy)]
+pub struct Written {
+    pub pixels: u64,
+    pub total: u64,
+}
+
+// Write the different pixels given as their position and the absolute difference of their RGB channels, as they come,
+// so they are never held in memory. If there are more than the limit (if any), the first ones are written followed by a marker:
+// a "# truncated" comment line in CSV, or a final {"truncated": total} object in JSON (where total is the number of different pixels).
+pub fn write(path: &str, format: Format, limit: Option<u64>, pixels: impl Iterator<Item = (u32, u32, [f32; 3])>) -> anyhow::Result<Written> {
+    let context = || format!("Failed to write {}", path);
+    let mut out = BufWriter::new(fs::File::create(path).context(context())?);
+    let mut written = Written { pixels: 0, total: 0 };
+    if format == Format::Json {
+        write!(out, "[").context(context())?;
+    }
+    for (x, y, [r, g, b]) in pixels {
+        written.total += 1;
+        if limit.is_some_and(|limit| written.pixels >= limit) {
+            continue;
+        }
+        match format {
+            Format::Csv => writeln!(out, "{},{},{},{},{}", x, y, r, g, b),
+            Format::Json => write!(out, "{}[{},{},{},{},{}]", if written.pixels == 0 { "" } else { "," }, x, y, r, g, b),
+        }.context(context())?;
+        written.pixels += 1;
+    }
+    let truncated = written.pixels < written.total;
+    match format {
+        Format::Csv if truncated => writeln!(out, "# truncated after {} of {} different pixels", written.pixels, written.total),
+        Format::Csv => Ok(()),
+        Format::Json if truncated => writeln!(out, "{}{{\"truncated\":{}}}]", if written.pixels == 0 { "" } else { "," }, written.total),
+        Format::Json => writeln!(out, "]"),
+    }.context(context())?;
+    out.flush().context(context())?;
+    Ok(written)
+}
@@ -0,0 +1,144 @@
+// An edge-based comparison for line drawings and diagrams, where a stroke that moved or got thicker by a pixel shouldn't fail.
+// The edges of both images are found with a Sobel filter, then each edge pixel is matched to the closest edge pixel of the other image
+// (using a Euclidean distance transform). The largest of these distances (truncated) is the Hausdorff distance between the edge maps.
+// The gradient metric compares the Sobel gradient magnitudes of the images instead, which finds the edges that got blurrier or sharper.
+
+use crate::{ImagePair, luma};
+
+// The Sobel gradient magnitude (normalized so that a step from black to white is 1) above which a pixel is an edge.
+pub const EDGE_THRESHOLD: f32 = 0.2;
+
+// The distance (in pixels) at which the distances to the closest edge are truncated, so that an edge missing from the other image
+// (or an image without edges at all) counts as a large but bounded error.
+pub const MAX_DISTANCE: f32 = 64.0;
+
+// The distances used in the distance transform for the pixels that aren't edges (before any edge is found).
+const INFINITY: f32 = 1e20;
+
+// The statistics of an edge comparison.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeStats {
+    pub hausdorff: f32,         // The truncated Hausdorff distance (in pixels) between the edge maps of the two images.
+    pub edge_pixels: [u32; 2],  // The number of edge pixels in each image.
+}
+
+// The edges of two images of the same size and the distance from each edge pixel to the closest edge of the other image.
+pub struct EdgeComparison {
+    pub edges: [Vec<bool>; 2],      // The row-major edge maps of the two images.
+    pub distances: [Vec<f32>; 2],   // The truncated distance from each edge pixel of an image to the other image (0 for other pixels).
+}
+
+impl EdgeComparison {
+    // Return the error of the given (row-major) pixel: its largest distance to the other image in either direction.
+    pub fn error(&self, index: usize) -> f32 {
+        self.distances[0][index].max(self.distances[1][index])
+    }
+
+    // Return the statistics of the comparison.
+    pub fn stats(&self) -> EdgeStats {
+        let largest = |distances: &[f32]| distances.iter().copied().fold(0f32, f32::max);
+        EdgeStats {
+            hausdorff: largest(&self.distances[0]).max(largest(&self.distances[1])),
+            edge_pixels: self.edges.each_ref().map(|edges| edges.iter().filter(|&&edge| edge).count() as u32),
+        }
+    }
+}
+
+// Return the luminance [0-1] of the pixels of the two images (float images are clamped to [0-1]).
+fn luminance(images: &ImagePair) -> [Vec<f32>; 2] {
+    match images {
+        ImagePair::Integer(img1, img2) => [img1, img2].map(|image| {
+            image.pixels().map(|pixel| luma(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32) / 255f32).collect()
+        }),
+        ImagePair::Float(img1, img2) => [img1, img2].map(|image| {
+            image.pixels().map(|pixel| luma(pixel[0], pixel[1], pixel[2]).clamp(0f32, 1f32)).collect()
+        }),
+    }
+}
+
+// Return the Sobel gradient magnitude of each pixel of a row-major plane, normalized so that a step from black to white is 1.
+// The pixels outside the plane are clamped to its edges.
+pub fn sobel_magnitudes(plane: &[f32], size: (u32, u32)) -> Vec<f32> {
+    let (width, height) = (size.0 as i64, size.1 as i64);
+    let at = |x: i64, y: i64| plane[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+    (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| {
+        let gx = (at(x + 1, y - 1) + 2f32 * at(x + 1, y) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2f32 * at(x - 1, y) + at(x - 1, y + 1));
+        let gy = (at(x - 1, y + 1) + 2f32 * at(x, y + 1) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2f32 * at(x, y - 1) + at(x + 1, y - 1));
+        // The kernels weigh the step by 4, so the magnitude is divided by 4.
+        (gx * gx + gy * gy).sqrt() / 4f32
+    }).collect()
+}
+
+// Find the edges of a row-major plane: the pixels whose Sobel gradient magnitude exceeds the edge threshold.
+pub fn sobel_edges(plane: &[f32], size: (u32, u32)) -> Vec<bool> {
+    sobel_magnitudes(plane, size).into_iter().map(|magnitude| magnitude > EDGE_THRESHOLD).collect()
+}
+
+// Return the row-major error of each pixel of two images of the same size with the gradient metric:
+// the absolute difference between the Sobel gradient magnitudes of their luminance, clamped to [0-1].
+pub fn gradient_errors(images: &ImagePair) -> Vec<f32> {
+    let size = images.dimensions()[0];
+    let [magnitudes1, magnitudes2] = luminance(images).map(|plane| sobel_magnitudes(&plane, size));
+    magnitudes1.into_iter().zip(magnitudes2).map(|(magnitude1, magnitude2)| (magnitude1 - magnitude2).abs().min(1f32)).collect()
+}
+
+// Compute the squared distance transform of a 1D function in place, as the lower envelope of parabolas rooted at each sample
+// (Felzenszwalb and Huttenlocher, "Distance Transforms of Sampled Functions", 2012).
+fn distance_transform_1d(values: &mut [f32]) {
+    let count = values.len();
+    let input = values.to_vec();
+    let mut roots = vec![0usize; count];        // The samples whose parabolas form the lower envelope.
+    let mut bounds = vec![0f32; count + 1];     // The ranges in which each of the parabolas is the lowest.
+    let mut parabolas = 0;
+    bounds[0] = -INFINITY;
+    bounds[1] = INFINITY;
+    // The position where the parabolas rooted at the samples q and p intersect.
+    let intersect = |q: usize, p: usize| ((input[q] + (q * q) as f32) - (input[p] + (p * p) as f32)) / (2f32 * (q as f32 - p as f32));
+    for q in 1..count {
+        let mut s = intersect(q, roots[parabolas]);
+        while s <= bounds[parabolas] {
+            parabolas -= 1;
+            s = intersect(q, roots[parabolas]);
+        }
+        parabolas += 1;
+        roots[parabolas] = q;
+        bounds[parabolas] = s;
+        bounds[parabolas + 1] = INFINITY;
+    }
+    let mut parabola = 0;
+    for (q, value) in values.iter_mut().enumerate() {
+        while bounds[parabola + 1] < q as f32 {
+            parabola += 1;
+        }
+        let offset = q as f32 - roots[parabola] as f32;
+        *value = offset * offset + input[roots[parabola]];
+    }
+}
+
+// Return the Euclidean distance from every pixel of a row-major edge map to the closest edge, truncated at the maximum distance.
+pub fn distance_transform(edges: &[bool], size: (u32, u32)) -> Vec<f32> {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    let mut squared: Vec<f32> = edges.iter().map(|&edge| if edge { 0f32 } else { INFINITY }).collect();
+    // The transform is separable: the columns are transformed first, then the rows.
+    let mut column = vec![0f32; height];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() { *value = squared[y * width + x]; }
+        distance_transform_1d(&mut column);
+        for (y, value) in column.iter().enumerate() { squared[y * width + x] = *value; }
+    }
+    for row in squared.chunks_mut(width) {
+        distance_transform_1d(row);
+    }
+    squared.into_iter().map(|value| value.sqrt().min(MAX_DISTANCE)).collect()
+}
+
+// Find the edges of two images of the same size, and the distance from each edge pixel to the closest edge of the other image.
+pub fn compare_edges(images: &ImagePair) -> EdgeComparison {
+    let size = images.dimensions()[0];
+    let edges = luminance(images).map(|plane| sobel_edges(&plane, size));
+    let transforms = edges.each_ref().map(|edges| distance_transform(edges, size));
+    let distances = [(0, 1), (1, 0)].map(|(from, to)| {
+        edges[from].iter().zip(&transforms[to]).map(|(&edge, &distance)| if edge { distance } else { 0f32 }).collect()
+    });
+    EdgeComparison { edges, distances }
+}
@@ -0,0 +1,95 @@
+// The pixels with the largest differences of a comparison, which explain what changed between the images.
+// Only the N largest ones seen so far are kept (in a heap), so the memory doesn't grow with the image.
+
+use std::{cmp::{Ordering, Reverse}, collections::BinaryHeap};
+
+// A pixel with one of the largest differences, and its RGB channels in both images
+// (in [0-255] for 8-bit images, and in linear float space for float images).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorstPixel {
+    pub x: u32,
+    pub y: u32,
+    pub frame: Option<usize>,   // The index of the frame if animated images were compared frame by frame.
+    pub error: f32,             // The largest difference among the compared channels [0-1] (unbounded for float images).
+    pub values: [[f32; 3]; 2],  // The RGB channels of the pixel in the first and second images.
+}
+
+impl WorstPixel {
+    // Return the absolute difference of the RGB channels.
+    pub fn channel_errors(&self) -> [f32; 3] {
+        [0, 1, 2].map(|channel| (self.values[0][channel] - self.values[1][channel]).abs())
+    }
+}
+
+// A pixel ordered by its error, then by its position in row-major order (frame by frame), where the earlier pixel ranks higher on a tie.
+struct Ranked(WorstPixel);
+
+impl Ranked {
+    fn position(&self) -> (Option<usize>, u32, u32) {
+        (self.0.frame, self.0.y, self.0.x)
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.error.total_cmp(&other.0.error).then_with(|| other.position().cmp(&self.position()))
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+// Collects the (at most) N pixels with the largest errors as the pixels are compared, in any order.
+pub(crate) struct WorstPixels {
+    count: usize,
+    heap: BinaryHeap<Reverse<Ranked>>, // The lowest ranked of the kept pixels is on top, so it is the one replaced.
+}
+
+impl WorstPixels {
+    pub(crate) fn new(count: usize) -> Self {
+        WorstPixels { count, heap: BinaryHeap::with_capacity(count + 1) }
+    }
+
+    // Add a compared pixel (the pixels that don't differ at all are ignored), whose channels are only read if it is kept.
+    pub(crate) fn add(&mut self, x: u32, y: u32, frame: Option<usize>, error: f32, values: impl FnOnce() -> [[f32; 3]; 2]) {
+        if error <= 0f32 || self.count == 0 {
+            return;
+        }
+        let pixel = Ranked(WorstPixel { x, y, frame, error, values: [[0f32; 3]; 2] });
+        if self.heap.len() == self.count && self.heap.peek().is_some_and(|Reverse(lowest)| pixel <= *lowest) {
+            return;
+        }
+        self.heap.push(Reverse(Ranked(WorstPixel { values: values(), ..pixel.0 })));
+        if self.heap.len() > self.count {
+            self.heap.pop();
+        }
+    }
+
+    // Return the kept pixels from the largest error to the smallest one.
+    pub(crate) fn finish(self) -> Vec<WorstPixel> {
+        self.heap.into_sorted_vec().into_iter().map(|Reverse(Ranked(pixel))| pixel).collect()
+    }
+}
+
+// Return the (at most) N pixels with the largest errors among the worst pixels of the frames, which are labeled with their frame index,
+// or None if they weren't collected.
+pub(crate) fn worst_frame_pixels<'a>(frames: impl Iterator<Item = Option<&'a [WorstPixel]>>, count: usize) -> Option<Vec<WorstPixel>> {
+    let mut worst = WorstPixels::new(count);
+    for (index, pixels) in frames.enumerate() {
+        for pixel in pixels? {
+            worst.add(pixel.x, pixel.y, Some(index), pixel.error, || pixel.values);
+        }
+    }
+    Some(worst.finish())
+}
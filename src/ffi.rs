@@ -0,0 +1,104 @@
+use std::ffi::{CStr, CString, c_char, c_float};
+use std::panic;
+use std::ptr;
+
+use anyhow::Context;
+
+use crate::{ComparisonOptions, ImagePair, Threshold};
+
+// The comparison options as received from C (see `imgcmp_options_t` in imgcmp.h).
+#[repr(C)]
+pub struct ImgcmpOptions {
+    pub value_threshold: c_float,           // A threshold [0-1] on the maximum allowed per-channel error.
+    pub error_threshold: *const c_char,     // The number (or percentage) of pixels allowed to be different (NULL for "0").
+    pub shift_tolerance: u32,
+    pub ignore_border: u32,
+}
+
+// The comparison result as returned to C (see `imgcmp_result_t` in imgcmp.h).
+#[repr(C)]
+pub struct ImgcmpResult {
+    pub matched: bool,
+    pub wrong_pixels: u32,
+    pub total_pixels: u32,
+    pub error_message: *mut c_char, // NULL unless the comparison could not be done. Owned by the result.
+}
+
+impl ImgcmpResult {
+    fn error(message: String) -> Self {
+        // The message can't contain interior NUL bytes unless a path did, in which case they are dropped.
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        ImgcmpResult { matched: false, wrong_pixels: 0, total_pixels: 0, error_message: message.into_raw() }
+    }
+}
+
+// Read a NUL-terminated UTF-8 string passed from C.
+fn read_str<'a>(string: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    if string.is_null() {
+        return Err(anyhow::Error::msg(format!("{} must not be NULL", name)));
+    }
+    // SAFETY: The caller guarantees that non-NULL strings are valid and NUL-terminated.
+    unsafe { CStr::from_ptr(string) }.to_str().context(format!("{} is not valid UTF-8", name))
+}
+
+// Convert the C options (NULL for the defaults) into comparison options.
+fn read_options(options: *const ImgcmpOptions) -> anyhow::Result<ComparisonOptions> {
+    // SAFETY: The caller guarantees that a non-NULL options pointer points to a valid struct.
+    let Some(options) = (unsafe { options.as_ref() }) else { return Ok(ComparisonOptions::default()); };
+    let error_threshold = if options.error_threshold.is_null() {
+        Threshold::Absolute(0)
+    } else {
+        Threshold::try_from(read_str(options.error_threshold, "error_threshold")?)?
+    };
+    Ok(ComparisonOptions {
+        value_threshold: (options.value_threshold * 255f32) as u8,
+        float_value_threshold: options.value_threshold,
+        error_threshold,
+        shift_tolerance: options.shift_tolerance,
+        ignore_border: options.ignore_border,
+        ..ComparisonOptions::default()
+    })
+}
+
+fn compare_files(path1: *const c_char, path2: *const c_char, options: *const ImgcmpOptions) -> anyhow::Result<ImgcmpResult> {
+    let options = read_options(options)?;
+    let [img1, img2] = [read_str(path1, "path1")?, read_str(path2, "path2")?].map(|path| -> anyhow::Result<image::DynamicImage> {
+        let reader = image::io::Reader::open(path).context(format!("Failed to read {}", path))?;
+        reader.decode().context(format!("Failed to decode {}", path))
+    });
+    let (outcome, _) = crate::compare(&ImagePair::new(img1?, img2?), &options)?;
+    if outcome.is_size_mismatch() {
+        let [size1, size2] = outcome.sizes;
+        return Err(anyhow::Error::msg(format!("Images have different sizes (Got ({}x{}) and ({}x{}))", size1.0, size1.1, size2.0, size2.1)));
+    }
+    Ok(ImgcmpResult {
+        matched: outcome.matched,
+        wrong_pixels: outcome.wrong_pixels,
+        total_pixels: outcome.total_pixels,
+        error_message: ptr::null_mut(),
+    })
+}
+
+// Compare the images at the two given paths using the given options (NULL for the defaults).
+// If the comparison could not be done (e.g. invalid options, unreadable images or different sizes),
+// `matched` is false and `error_message` describes the error. The result must be released with `imgcmp_result_free`.
+#[no_mangle]
+pub extern "C" fn imgcmp_compare(path1: *const c_char, path2: *const c_char, options: *const ImgcmpOptions) -> ImgcmpResult {
+    match panic::catch_unwind(|| compare_files(path1, path2, options)) {
+        Ok(Ok(result)) => result,
+        Ok(Err(err)) => ImgcmpResult::error(format!("{err:#}")),
+        Err(_) => ImgcmpResult::error("The comparison panicked".to_string()),
+    }
+}
+
+// Release the memory owned by a result returned from `imgcmp_compare`. It is safe to call it more than once.
+#[no_mangle]
+pub extern "C" fn imgcmp_result_free(result: *mut ImgcmpResult) {
+    // SAFETY: The caller guarantees that a non-NULL result pointer points to a result returned from `imgcmp_compare`.
+    let Some(result) = (unsafe { result.as_mut() }) else { return; };
+    if !result.error_message.is_null() {
+        // SAFETY: The message was allocated by `CString::into_raw` and is only released once since it is reset below.
+        drop(unsafe { CString::from_raw(result.error_message) });
+        result.error_message = ptr::null_mut();
+    }
+}
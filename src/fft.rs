@@ -0,0 +1,107 @@
+// A comparison of the magnitude spectra of the images, for systematic artifacts (like ringing, blocking or banding)
+// that are spread over the whole image, so they are faint in every pixel but concentrated in a few frequencies.
+// Each channel is transformed with a 2D FFT, and the magnitude of each frequency bin is compressed as ln(1 + |F|) and divided by ln(1 + N),
+// which is the largest compressed magnitude of a channel in [0-1] over N pixels (at the zero frequency), so the errors of the bins are in [0-1].
+
+use rustfft::{FftPlanner, num_complex::Complex};
+
+use crate::{ComparisonOptions, ComparisonOutcome, DiffStyle, ImagePair, RGB_CHANNELS, colormap};
+
+// The statistics of the differences between the spectra.
+#[derive(Debug, Clone, Copy)]
+pub struct FftStats {
+    pub mean: f32,              // The mean error of the frequency bins.
+    pub max: f32,               // The largest error of the frequency bins.
+}
+
+// Return the row-major channel of both images, in [0-1] for 8-bit images.
+fn planes(images: &ImagePair, channel: usize) -> [Vec<f32>; 2] {
+    match images {
+        ImagePair::Integer(img1, img2) => [img1, img2].map(|image| image.pixels().map(|pixel| pixel[channel] as f32 / 255f32).collect()),
+        ImagePair::Float(img1, img2) => [img1, img2].map(|image| image.pixels().map(|pixel| pixel[channel]).collect()),
+    }
+}
+
+// Return the normalized magnitudes (see above) of the 2D FFT of a row-major plane of the given size,
+// in row-major order with the zero frequency shifted to the center.
+fn spectrum(plane: Vec<f32>, size: (u32, u32), planner: &mut FftPlanner<f32>) -> Vec<f32> {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    // The rows are transformed in place, then they are transposed so the columns can be transformed in place too.
+    let mut rows: Vec<Complex<f32>> = plane.into_iter().map(|value| Complex::new(value, 0f32)).collect();
+    planner.plan_fft_forward(width).process(&mut rows);
+    let mut columns = vec![Complex::default(); rows.len()];
+    for y in 0..height {
+        for x in 0..width {
+            columns[x * height + y] = rows[y * width + x];
+        }
+    }
+    planner.plan_fft_forward(height).process(&mut columns);
+
+    let scale = ((width * height) as f32).ln_1p();
+    let mut magnitudes = vec![0f32; columns.len()];
+    for x in 0..width {
+        for y in 0..height {
+            let shifted = ((y + height / 2) % height) * width + (x + width / 2) % width;
+            magnitudes[shifted] = (columns[x * height + y].norm().ln_1p() / scale).min(1f32);
+        }
+    }
+    magnitudes
+}
+
+// Return the error of each frequency bin of the images (in the order of `spectrum`): the largest difference between
+// the normalized magnitudes of the two images among the given channels.
+pub fn spectrum_errors(images: &ImagePair, channels: &[usize]) -> Vec<f32> {
+    let size = images.dimensions()[0];
+    let mut planner = FftPlanner::new();
+    let mut errors = vec![0f32; (size.0 * size.1) as usize];
+    for &channel in channels {
+        let [spectrum1, spectrum2] = planes(images, channel).map(|plane| spectrum(plane, size, &mut planner));
+        for ((error, magnitude1), magnitude2) in errors.iter_mut().zip(spectrum1).zip(spectrum2) {
+            *error = error.max((magnitude1 - magnitude2).abs());
+        }
+    }
+    errors
+}
+
+// Return the statistics of the errors of the frequency bins.
+pub fn stats(errors: &[f32]) -> FftStats {
+    let sum: f64 = errors.iter().map(|&error| error as f64).sum();
+    FftStats {
+        mean: if errors.is_empty() { 0f32 } else { (sum / errors.len() as f64) as f32 },
+        max: errors.iter().copied().fold(0f32, f32::max),
+    }
+}
+
+// Compare the spectra of the images, where each frequency bin is compared like a pixel against the float value threshold,
+// and the error threshold limits the number of different bins. The error image is the map of the bin errors (with the zero frequency
+// at its center), where the different bins are drawn like different pixels and the rest are black.
+pub fn compare_spectra(images: &ImagePair, options: &ComparisonOptions) -> anyhow::Result<(ComparisonOutcome, image::DynamicImage)> {
+    // The frequency bins have no position in the images, so the options that select or analyze the pixels can't apply to them.
+    if options.tolerance_map.is_some() || options.shift_tolerance > 0 || options.luma_weight > 0f32 || options.ignore_border > 0
+        || options.alpha_threshold.is_some() || options.tile.is_some() || options.tile_size.is_some() || options.explain.is_some()
+        || options.analyze_clusters() || options.percentile.is_some() || options.sample.is_some() {
+        return Err(anyhow::Error::msg("The fft metric can't be combined with a tolerance map, a shift tolerance, a luma weight, an ignored border, \
+            an alpha threshold, tiles, the worst pixels, clusters, a percentile or sampling"));
+    }
+    let size = images.dimensions()[0];
+    let errors = spectrum_errors(images, options.channels.as_deref().unwrap_or(&RGB_CHANNELS));
+    let mut error_img = image::RgbImage::new(size.0, size.1);
+    let mut wrong_bins = 0u32;
+    for (pixel, &error) in error_img.pixels_mut().zip(&errors) {
+        if error > options.float_value_threshold {
+            wrong_bins += 1;
+            let remapped = 128 | ((error * 255f32) as u8 >> 1);
+            pixel.0 = if options.diff_style == DiffStyle::ColorblindSafe { colormap::viridis(remapped) } else { [remapped; 3] };
+        }
+    }
+    let allowed_wrong_pixels = options.error_threshold.get_actual_range(size);
+    let outcome = ComparisonOutcome {
+        matched: allowed_wrong_pixels.0 <= wrong_bins && wrong_bins <= allowed_wrong_pixels.1,
+        wrong_pixels: wrong_bins,
+        total_pixels: errors.len() as u32,
+        allowed_wrong_pixels,
+        fft: Some(stats(&errors)),
+        ..ComparisonOutcome::size_mismatch([size, size])
+    };
+    Ok((outcome, image::DynamicImage::ImageRgb8(error_img)))
+}
@@ -0,0 +1,212 @@
+// The LDR-FLIP perceptual error metric, as described by Andersson et al. in
+// "FLIP: A Difference Evaluator for Alternating Images" (2020) and its reference implementation.
+// The error of each pixel is in [0-1], combining a color difference (after filtering both images by the contrast sensitivity
+// of the human eye) with a difference between their edges and points (which makes errors near features more noticeable).
+
+use std::f32::consts::PI;
+
+use crate::ImagePair;
+
+// The number of pixels per degree of visual angle for a 0.7m wide 4K monitor seen from 0.7m (the default of the reference implementation).
+pub const DEFAULT_PIXELS_PER_DEGREE: f32 = 0.7 * 3840.0 / 0.7 * PI / 180.0;
+
+const COLOR_EXPONENT: f32 = 0.7;    // The exponent (qc) applied to the HyAB color difference.
+const FEATURE_EXPONENT: f32 = 0.5;  // The exponent (qf) applied to the feature difference.
+const COLOR_CUTOFF: f32 = 0.4;      // The fraction (pc) of the maximum color difference below which the differences are compressed.
+const COLOR_CUTOFF_ERROR: f32 = 0.95; // The error (pt) that a color difference at the cutoff is mapped to.
+const FEATURE_WIDTH: f32 = 0.082;   // The width (in degrees) of the human edge detection filter.
+
+// The (a1, b1, a2, b2) parameters of the contrast sensitivity functions of the achromatic, red-green and blue-yellow channels.
+const CSF_PARAMETERS: [[f32; 4]; 3] = [[1.0, 0.0047, 0.0, 1e-5], [1.0, 0.0053, 0.0, 1e-5], [34.1, 0.04, 13.5, 0.025]];
+
+// The conversion matrices between linear sRGB and CIE XYZ.
+const RGB_TO_XYZ: [[f32; 3]; 3] = [
+    [10135552.0 / 24577794.0, 8788810.0 / 24577794.0, 4435075.0 / 24577794.0],
+    [2613072.0 / 12288897.0, 8788810.0 / 12288897.0, 887015.0 / 12288897.0],
+    [1425312.0 / 73733382.0, 8788810.0 / 73733382.0, 70074185.0 / 73733382.0],
+];
+const XYZ_TO_RGB: [[f32; 3]; 3] = [
+    [3.241003, -1.537399, -0.4986159],
+    [-0.9692243, 1.87593, 0.04155422],
+    [0.05563942, -0.2040112, 1.057149],
+];
+
+// The mean and maximum FLIP error over the compared pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct FlipStats {
+    pub mean: f32,
+    pub max: f32,
+}
+
+// An image stored as a row-major plane per channel.
+type Planes = [Vec<f32>; 3];
+
+pub(crate) fn multiply(matrix: &[[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+// Return the CIE XYZ coordinates of the reference white (the D65 white point of sRGB).
+fn white_point() -> [f32; 3] {
+    multiply(&RGB_TO_XYZ, [1.0; 3])
+}
+
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+// Convert a linear RGB color to the YCxCz opponent color space.
+fn linear_rgb_to_ycxcz(rgb: [f32; 3]) -> [f32; 3] {
+    let white = white_point();
+    let xyz = multiply(&RGB_TO_XYZ, rgb);
+    let [x, y, z] = [0, 1, 2].map(|c| xyz[c] / white[c]);
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+// Convert a color in the YCxCz opponent color space back to linear RGB.
+fn ycxcz_to_linear_rgb(ycxcz: [f32; 3]) -> [f32; 3] {
+    let white = white_point();
+    let y = (ycxcz[0] + 16.0) / 116.0;
+    let (x, z) = (ycxcz[1] / 500.0 + y, y - ycxcz[2] / 200.0);
+    multiply(&XYZ_TO_RGB, [x * white[0], y * white[1], z * white[2]])
+}
+
+// Convert a linear RGB color to CIELAB, then apply the Hunt adjustment (which scales the chroma by the lightness).
+fn linear_rgb_to_hunt_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let white = white_point();
+    let xyz = multiply(&RGB_TO_XYZ, rgb);
+    let delta = 6.0 / 29.0;
+    let [fx, fy, fz] = [0, 1, 2].map(|c| {
+        let t = xyz[c] / white[c];
+        if t > delta * delta * delta { t.cbrt() } else { t / (3.0 * delta * delta) + 4.0 / 29.0 }
+    });
+    let lightness = 116.0 * fy - 16.0;
+    [lightness, 0.01 * lightness * 500.0 * (fx - fy), 0.01 * lightness * 200.0 * (fy - fz)]
+}
+
+// Return the HyAB distance between two colors (the lightness difference plus the Euclidean chroma difference).
+fn hyab(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    (lab1[0] - lab2[0]).abs() + (lab1[1] - lab2[1]).hypot(lab1[2] - lab2[2])
+}
+
+// Convolve a row-major plane with a separable kernel (given as its horizontal and vertical parts, each of odd length),
+// extending the plane beyond its edges by repeating the edge pixels.
+fn convolve(plane: &[f32], size: (u32, u32), kernel_x: &[f32], kernel_y: &[f32]) -> Vec<f32> {
+    let (width, height) = (size.0 as i64, size.1 as i64);
+    let (radius_x, radius_y) = ((kernel_x.len() / 2) as i64, (kernel_y.len() / 2) as i64);
+    let mut horizontal = vec![0f32; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[(y * width + x) as usize] = kernel_x.iter().enumerate().map(|(i, weight)| {
+                let nx = (x + i as i64 - radius_x).clamp(0, width - 1);
+                weight * plane[(y * width + nx) as usize]
+            }).sum();
+        }
+    }
+    let mut result = vec![0f32; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            result[(y * width + x) as usize] = kernel_y.iter().enumerate().map(|(i, weight)| {
+                let ny = (y + i as i64 - radius_y).clamp(0, height - 1);
+                weight * horizontal[(ny * width + x) as usize]
+            }).sum();
+        }
+    }
+    result
+}
+
+// Filter a channel of an image in the YCxCz color space by its contrast sensitivity function,
+// which is a (normalized) sum of two Gaussians that are each separable.
+fn contrast_sensitivity_filter(plane: &[f32], size: (u32, u32), parameters: [f32; 4], pixels_per_degree: f32) -> Vec<f32> {
+    let [a1, b1, a2, b2] = parameters;
+    let max_scale = CSF_PARAMETERS.iter().flat_map(|parameters| [parameters[1], parameters[3]]).fold(0f32, f32::max);
+    let radius = (3.0 * (max_scale / (2.0 * PI * PI)).sqrt() * pixels_per_degree).ceil() as i32;
+    let gaussians: Vec<(f32, Vec<f32>)> = [(a1, b1), (a2, b2)].into_iter().filter(|&(a, _)| a > 0.0).map(|(a, b)| {
+        let kernel = (-radius..=radius).map(|i| {
+            let x = i as f32 / pixels_per_degree;
+            (-PI * PI * x * x / b).exp()
+        }).collect::<Vec<f32>>();
+        (a * (PI / b).sqrt(), kernel)
+    }).collect();
+    let total: f32 = gaussians.iter().map(|(weight, kernel)| weight * kernel.iter().sum::<f32>().powi(2)).sum();
+    let mut result = vec![0f32; plane.len()];
+    for (weight, kernel) in &gaussians {
+        for (value, filtered) in result.iter_mut().zip(convolve(plane, size, kernel, kernel)) {
+            *value += weight / total * filtered;
+        }
+    }
+    result
+}
+
+// Return the magnitudes of the edges and points detected in the normalized achromatic channel of an image,
+// using the first and second derivatives of a Gaussian (whose positive and negative weights are normalized to 1 and -1).
+fn detect_features(plane: &[f32], size: (u32, u32), pixels_per_degree: f32) -> [Vec<f32>; 2] {
+    let deviation = 0.5 * FEATURE_WIDTH * pixels_per_degree;
+    let radius = (3.0 * deviation).ceil() as i32;
+    let gaussian: Vec<f32> = (-radius..=radius).map(|i| (-((i * i) as f32) / (2.0 * deviation * deviation)).exp()).collect();
+    let sum: f32 = gaussian.iter().sum();
+    let smoothing: Vec<f32> = gaussian.iter().map(|weight| weight / sum).collect();
+    let normalize = |kernel: Vec<f32>| -> Vec<f32> {
+        let positive: f32 = kernel.iter().filter(|&&weight| weight > 0.0).sum();
+        let negative: f32 = -kernel.iter().filter(|&&weight| weight < 0.0).sum::<f32>();
+        kernel.into_iter().map(|weight| if weight < 0.0 { weight / negative } else { weight / positive }).collect()
+    };
+    let edge = normalize((-radius..=radius).zip(&gaussian).map(|(i, weight)| -i as f32 * weight).collect());
+    let point = normalize((-radius..=radius).zip(&gaussian).map(|(i, weight)| ((i * i) as f32 / (deviation * deviation) - 1.0) * weight).collect());
+    [edge, point].map(|derivative| {
+        let along_x = convolve(plane, size, &derivative, &smoothing);
+        let along_y = convolve(plane, size, &smoothing, &derivative);
+        along_x.iter().zip(&along_y).map(|(dx, dy)| dx.hypot(*dy)).collect()
+    })
+}
+
+// Return the pixels of the two images in linear RGB (8-bit images are decoded from sRGB, and float images are clamped to [0-1]).
+fn linear_pixels(images: &ImagePair) -> [Vec<[f32; 3]>; 2] {
+    match images {
+        ImagePair::Integer(img1, img2) => [img1, img2].map(|image| {
+            image.pixels().map(|pixel| [0, 1, 2].map(|c| srgb_to_linear(pixel[c] as f32 / 255.0))).collect()
+        }),
+        ImagePair::Float(img1, img2) => [img1, img2].map(|image| {
+            image.pixels().map(|pixel| [0, 1, 2].map(|c| pixel[c].clamp(0.0, 1.0))).collect()
+        }),
+    }
+}
+
+// Compute the FLIP error [0-1] of every pixel of two images of the same size (in row-major order),
+// for an observer who sees the given number of pixels per degree of visual angle.
+pub fn flip_errors(images: &ImagePair, pixels_per_degree: f32) -> Vec<f32> {
+    let size = images.dimensions()[0];
+    let opponent: [Planes; 2] = linear_pixels(images).map(|pixels| {
+        let ycxcz: Vec<[f32; 3]> = pixels.into_iter().map(linear_rgb_to_ycxcz).collect();
+        [0, 1, 2].map(|c| ycxcz.iter().map(|pixel| pixel[c]).collect())
+    });
+
+    // The color pipeline: filter the images by the contrast sensitivity functions, then compare them in the Hunt-adjusted CIELAB space.
+    let [lab1, lab2] = opponent.each_ref().map(|planes| {
+        let [y, cx, cz] = [0, 1, 2].map(|c| contrast_sensitivity_filter(&planes[c], size, CSF_PARAMETERS[c], pixels_per_degree));
+        (0..y.len()).map(|i| {
+            let rgb = ycxcz_to_linear_rgb([y[i], cx[i], cz[i]]).map(|value| value.clamp(0.0, 1.0));
+            linear_rgb_to_hunt_lab(rgb)
+        }).collect::<Vec<[f32; 3]>>()
+    });
+    let max_color_difference = hyab(linear_rgb_to_hunt_lab([0.0, 1.0, 0.0]), linear_rgb_to_hunt_lab([0.0, 0.0, 1.0])).powf(COLOR_EXPONENT);
+    let cutoff = COLOR_CUTOFF * max_color_difference;
+
+    // The feature pipeline: detect the edges and points in the achromatic channels (normalized to [0-1]).
+    let [features1, features2] = opponent.each_ref().map(|planes| {
+        let achromatic: Vec<f32> = planes[0].iter().map(|y| (y + 16.0) / 116.0).collect();
+        detect_features(&achromatic, size, pixels_per_degree)
+    });
+
+    (0..lab1.len()).map(|i| {
+        // Compress the color differences below the cutoff, and spread the rest over the remaining range.
+        let difference = hyab(lab1[i], lab2[i]).powf(COLOR_EXPONENT);
+        let color_error = if difference < cutoff {
+            COLOR_CUTOFF_ERROR / cutoff * difference
+        } else {
+            COLOR_CUTOFF_ERROR + (difference - cutoff) / (max_color_difference - cutoff) * (1.0 - COLOR_CUTOFF_ERROR)
+        };
+        let feature_difference = (features1[0][i] - features2[0][i]).abs().max((features1[1][i] - features2[1][i]).abs());
+        let feature_error = (feature_difference / 2f32.sqrt()).powf(FEATURE_EXPONENT).clamp(0.0, 1.0);
+        color_error.powf(1.0 - feature_error)
+    }).collect()
+}
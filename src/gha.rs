@@ -0,0 +1,81 @@
+use std::{env, fs::OpenOptions, io::Write};
+
+use anyhow::Context;
+
+use imgcmp::ComparisonOutcome;
+
+use crate::batch::Comparison;
+
+// Return true if the tool is running inside a GitHub Actions job.
+pub fn is_github_actions() -> bool {
+    env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true")
+}
+
+// Escape a workflow command message (https://github.com/actions/toolkit/blob/main/packages/core/src/command.ts).
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+// Escape a workflow command property value.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+// Describe the range of wrong pixels allowed by the error threshold as a percentage of the compared pixels.
+fn describe_threshold(outcome: &ComparisonOutcome) -> String {
+    let percentage = |count: u32| (100 * count as u64) as f32 / outcome.total_pixels.max(1) as f32;
+    match outcome.allowed_wrong_pixels {
+        (0, max) => format!("threshold {}%", percentage(max)),
+        (min, u32::MAX) => format!("threshold at least {}%", percentage(min)),
+        (min, max) => format!("threshold {}%..{}%", percentage(min), percentage(max)),
+    }
+}
+
+// Return the annotation message for a failed comparison, or None if the images match.
+fn failure_message(result: &anyhow::Result<ComparisonOutcome>) -> Option<String> {
+    match result {
+        Ok(outcome) if outcome.is_size_mismatch() => {
+            let [size1, size2] = outcome.sizes;
+            Some(format!("SIZE MISMATCH: ({}x{}) vs ({}x{})", size1.0, size1.1, size2.0, size2.1))
+        },
+        Ok(outcome) if !outcome.matched => {
+            Some(format!("MISMATCH: {}% of pixels differ ({})", outcome.wrong_percentage(), describe_threshold(outcome)))
+        },
+        Ok(_) => None,
+        Err(err) => Some(format!("ERROR: {err:#}")),
+    }
+}
+
+// Print an error annotation for a failed comparison.
+pub fn print_annotation(comparison: &Comparison) {
+    if let Some(message) = failure_message(&comparison.result) {
+        println!("::error file={}::{}", escape_property(&comparison.image_paths[1]), escape_data(&message));
+    }
+}
+
+// Append a Markdown summary of the comparison to the file named by GITHUB_STEP_SUMMARY (if set).
+pub fn write_step_summary(comparison: &Comparison) -> anyhow::Result<()> {
+    let Ok(path) = env::var("GITHUB_STEP_SUMMARY") else { return Ok(()) };
+
+    let mut summary = format!("### imgcmp: `{}` vs `{}`\n\n", comparison.image_paths[0], comparison.image_paths[1]);
+    let result = &comparison.result;
+    match result {
+        Ok(outcome) => {
+            let status = match failure_message(result) {
+                Some(message) => format!(":x: {}", message),
+                None => ":white_check_mark: MATCH".to_string(),
+            };
+            summary.push_str("| Result | Different Pixels | Total Pixels | Percentage |\n");
+            summary.push_str("| --- | ---: | ---: | ---: |\n");
+            summary.push_str(&format!("| {} | {} | {} | {}% |\n", status, outcome.wrong_pixels, outcome.total_pixels, outcome.wrong_percentage()));
+            if let Some(output) = &comparison.output {
+                summary.push_str(&format!("\nError image: `{}`\n", output));
+            }
+        },
+        Err(err) => summary.push_str(&format!(":x: ERROR: {err:#}\n")),
+    }
+    summary.push('\n');
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path).context(format!("Failed to open {}", path))?;
+    file.write_all(summary.as_bytes()).context(format!("Failed to write {}", path))
+}
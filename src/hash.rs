@@ -0,0 +1,107 @@
+// Perceptual hashes of images, which fingerprint their structure in 64 bits: similar images have hashes that differ in few bits,
+// so they can be compared approximately without comparing their pixels.
+
+use crate::{ImagePair, luma};
+
+// The algorithm that computes the perceptual hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    DHash,  // The difference hash: whether each pixel of a 9x8 thumbnail is brighter than its right neighbor.
+    PHash,  // The DCT hash: whether each of the 8x8 lowest frequencies of a 32x32 thumbnail is above their median.
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::DHash => "dhash",
+            HashAlgorithm::PHash => "phash",
+        }
+    }
+}
+
+impl TryFrom<&str> for HashAlgorithm {
+    type Error = anyhow::Error;
+    // Try to parse a string into a hash algorithm.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "dhash" => Ok(HashAlgorithm::DHash),
+            "phash" => Ok(HashAlgorithm::PHash),
+            _ => Err(anyhow::Error::msg(format!("Invalid hash algorithm \"{}\" (expected dhash or phash)", value))),
+        }
+    }
+}
+
+// The perceptual hashes of the two images and the number of bits in which they differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashComparison {
+    pub algorithm: HashAlgorithm,
+    pub hashes: [u64; 2],
+    pub distance: u32,          // The Hamming distance between the hashes (0-64).
+    pub threshold: u32,         // The largest distance under which the images match.
+}
+
+impl HashComparison {
+    pub fn matched(&self) -> bool {
+        self.distance <= self.threshold
+    }
+}
+
+// Return the thumbnails (in row-major order) of the luminance of the two images, where each thumbnail pixel is the mean of the pixels it covers.
+// A thumbnail larger than the image repeats its pixels instead.
+fn thumbnails(images: &ImagePair, size: (u32, u32)) -> [Vec<f64>; 2] {
+    let span = |cell: u32, cells: u32, length: u32| {
+        let start = (cell as u64 * length as u64 / cells as u64) as u32;
+        start..((cell as u64 + 1) * length as u64 / cells as u64).max(start as u64 + 1) as u32
+    };
+    let thumbnail = |(width, height): (u32, u32), luminance: &dyn Fn(u32, u32) -> f32| {
+        let mut thumbnail = Vec::with_capacity((size.0 * size.1) as usize);
+        for cy in 0..size.1 {
+            for cx in 0..size.0 {
+                let (xs, ys) = (span(cx, size.0, width), span(cy, size.1, height));
+                let count = (xs.len() * ys.len()) as f64;
+                let sum: f64 = ys.flat_map(|y| xs.clone().map(move |x| (x, y))).map(|(x, y)| luminance(x, y) as f64).sum();
+                thumbnail.push(sum / count);
+            }
+        }
+        thumbnail
+    };
+    match images {
+        ImagePair::Integer(img1, img2) => [img1, img2].map(|image| thumbnail(image.dimensions(), &|x, y| { let pixel = image.get_pixel(x, y); luma(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32) })),
+        ImagePair::Float(img1, img2) => [img1, img2].map(|image| thumbnail(image.dimensions(), &|x, y| { let pixel = image.get_pixel(x, y); luma(pixel[0], pixel[1], pixel[2]) })),
+    }
+}
+
+// Return the difference hash of a 9x8 thumbnail, where each row gives 8 bits (from the most significant one).
+fn dhash(thumbnail: &[f64]) -> u64 {
+    (0..8).flat_map(|y| (0..8).map(move |x| (x, y)))
+        .fold(0u64, |hash, (x, y)| (hash << 1) | (thumbnail[y * 9 + x] > thumbnail[y * 9 + x + 1]) as u64)
+}
+
+// Return the DCT hash of a 32x32 thumbnail: the 8x8 lowest frequencies of its 2D DCT-II (in row-major order, from the most significant bit)
+// compared to their median, which leaves the DC out since it only holds the mean brightness. A coefficient must exceed the median by more than
+// the rounding errors of the transform, so the frequencies that are absent from both images (e.g. in flat regions) give the same bits.
+fn phash(thumbnail: &[f64]) -> u64 {
+    const SIZE: usize = 32;
+    let cosines: Vec<f64> = (0..8).flat_map(|frequency| (0..SIZE).map(move |n|
+        (std::f64::consts::PI / SIZE as f64 * (n as f64 + 0.5) * frequency as f64).cos())).collect();
+    let mut coefficients = [0f64; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            coefficients[v * 8 + u] = (0..SIZE).map(|y| (0..SIZE).map(|x| thumbnail[y * SIZE + x] * cosines[u * SIZE + x]).sum::<f64>() * cosines[v * SIZE + y]).sum();
+        }
+    }
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    let tolerance = coefficients.iter().map(|coefficient| coefficient.abs()).fold(0f64, f64::max) * 1e-9;
+    coefficients.iter().fold(0u64, |hash, &coefficient| (hash << 1) | (coefficient > median + tolerance) as u64)
+}
+
+// Return the perceptual hashes of the two images (which can have different sizes) and their distance, given the largest distance allowed.
+pub fn compare_hashes(images: &ImagePair, algorithm: HashAlgorithm, threshold: u32) -> HashComparison {
+    let hashes = match algorithm {
+        HashAlgorithm::DHash => thumbnails(images, (9, 8)).map(|thumbnail| dhash(&thumbnail)),
+        HashAlgorithm::PHash => thumbnails(images, (32, 32)).map(|thumbnail| phash(&thumbnail)),
+    };
+    HashComparison { algorithm, hashes, distance: (hashes[0] ^ hashes[1]).count_ones(), threshold }
+}
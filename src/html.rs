@@ -0,0 +1,188 @@
+use std::{fs, io::Cursor, path::Path};
+
+use anyhow::Context;
+use image::{DynamicImage, ImageOutputFormat, imageops::FilterType};
+
+use imgcmp::{ComparisonOptions, ComparisonOutcome, render};
+
+use crate::{Status, batch::Comparison};
+
+// The largest dimension of the thumbnails in the gallery of a batch report.
+const THUMBNAIL_DIM: u32 = 160;
+
+// The style of the report. The toggle between the expected and actual images is a checkbox, so the report needs no scripts.
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+section { margin-top: 2em; border-top: 2px solid #ccc; }
+.images { display: flex; gap: 1em; align-items: flex-start; margin-top: 1em; }
+.images figure { margin: 0; }
+.images img { max-width: 100%; image-rendering: pixelated; border: 1px solid #ccc; }
+.toggle ~ .images .actual, .toggle:checked ~ .images .expected { display: none; }
+.toggle:checked ~ .images .actual { display: block; }
+.gallery { display: flex; flex-wrap: wrap; gap: 1em; }
+.gallery figure { margin: 0; max-width: 12em; }
+.gallery figcaption { font-size: small; overflow-wrap: anywhere; }
+.match { color: green; } .mismatch, .io_error, .invalid_arguments { color: red; } .size_mismatch, .missing_baseline { color: #b80; }";
+
+// Escape the characters that are not allowed to appear as-is in HTML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Encode the bytes in base64 (with padding).
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0u32, |triple, (index, &byte)| triple | (byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - 6 * index) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Return the image as a PNG data URI, downscaled (keeping its aspect ratio) so neither of its dimensions exceeds the given maximum.
+fn data_uri(image: &DynamicImage, max_dim: Option<u32>) -> anyhow::Result<String> {
+    let image = match max_dim {
+        Some(max_dim) if image.width() > max_dim || image.height() > max_dim => std::borrow::Cow::Owned(image.resize(max_dim, max_dim, FilterType::Triangle)),
+        _ => std::borrow::Cow::Borrowed(image),
+    };
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png).context("Failed to encode an image of the HTML report")?;
+    Ok(format!("data:image/png;base64,{}", base64(&bytes)))
+}
+
+// Return the figure embedding the image at the given path with the given caption and class,
+// or a note if the path isn't a readable image file (e.g. an URL or the standard input).
+fn figure(path: &str, caption: &str, class: &str, max_dim: Option<u32>) -> anyhow::Result<String> {
+    let image = Path::new(path).is_file().then(|| image::open(path).ok()).flatten();
+    figure_of(image.as_ref(), caption, class, max_dim)
+}
+
+// Return the figure embedding the given image with the given caption and class, or a note if there is no image.
+fn figure_of(image: Option<&DynamicImage>, caption: &str, class: &str, max_dim: Option<u32>) -> anyhow::Result<String> {
+    let content = match image {
+        Some(image) => format!("<img src=\"{}\" alt=\"{}\">", data_uri(image, max_dim)?, escape(caption)),
+        None => "<p>(not available)</p>".to_string(),
+    };
+    Ok(format!("<figure class=\"{class}\">{content}<figcaption>{}</figcaption></figure>", escape(caption)))
+}
+
+// Return the difference image of a comparison: the error image if it was written,
+// or the different pixels drawn over the first image from the mask of the outcome otherwise.
+fn difference_image(comparison: &Comparison, outcome: &ComparisonOutcome, options: &ComparisonOptions) -> Option<DynamicImage> {
+    if let Some(image) = comparison.output.as_deref().filter(|path| Path::new(path).is_file()).and_then(|path| image::open(path).ok()) {
+        return Some(image);
+    }
+    let mask = outcome.diff_mask.as_deref()?;
+    let first = image::open(&comparison.image_paths[0]).ok()?.into_rgba8();
+    (first.len() == 4 * mask.len()).then(|| DynamicImage::ImageRgb8(render::render_overlay(&first, mask, options.highlight_color)))
+}
+
+// Return the rows of the statistics table of an outcome.
+fn statistics(outcome: &ComparisonOutcome) -> String {
+    let [size1, size2] = outcome.sizes;
+    let mut rows = vec![("Sizes", format!("{}x{} and {}x{}", size1.0, size1.1, size2.0, size2.1))];
+    if !outcome.is_size_mismatch() {
+        rows.push(("Different pixels", format!("{} of {} ({}%)", outcome.wrong_pixels, outcome.total_pixels, outcome.wrong_percentage())));
+        rows.push(("MAE", format!("{:.2}", outcome.mae)));
+        rows.push(("RMSE", format!("{:.2}", outcome.rmse)));
+        rows.push(("Difference bounds", outcome.bbox.map_or("none".to_string(), |bbox| bbox.to_string())));
+    }
+    rows.iter().map(|(name, value)| format!("<tr><th>{}</th><td>{}</td></tr>\n", name, escape(value))).collect()
+}
+
+// Return the section of a comparison, with its statistics, the expected and actual images (toggled by a checkbox) and its difference image.
+fn section(index: usize, comparison: &Comparison, difference: Option<&DynamicImage>, max_dim: Option<u32>) -> anyhow::Result<String> {
+    let [expected, actual] = &comparison.image_paths;
+    let status = Status::from(comparison);
+    let mut html = format!("<section id=\"pair-{index}\">\n<h2>{} vs {}</h2>\n<p class=\"{}\">{}</p>\n", escape(expected), escape(actual), status.name(), status.label());
+    match &comparison.result {
+        Ok(outcome) => {
+            html.push_str(&format!("<table>\n{}</table>\n", statistics(outcome)));
+            // The checkbox must be a sibling of the images for the toggle selectors to match them.
+            html.push_str(&format!("<input type=\"checkbox\" class=\"toggle\" id=\"toggle-{index}\"><label for=\"toggle-{index}\">Show the actual image</label>\n"));
+            html.push_str(&format!("<div class=\"images\">\n{}\n{}\n{}\n</div>\n",
+                figure(expected, &format!("Expected: {}", expected), "expected", max_dim)?,
+                figure(actual, &format!("Actual: {}", actual), "actual", max_dim)?,
+                figure_of(difference, "Difference", "difference", max_dim)?));
+        },
+        Err(err) => html.push_str(&format!("<pre>{}</pre>\n", escape(&format!("{err:#}")))),
+    }
+    html.push_str("</section>\n");
+    Ok(html)
+}
+
+// Write a self-contained HTML report of the given comparisons: the options, a summary table of all the pairs,
+// a gallery of the difference images of the failed pairs (for a batch), and a section per failed pair
+// (or for the only pair of a single comparison) with the embedded images, which are downscaled so their dimensions don't exceed the given maximum.
+pub fn write_report(path: &str, comparisons: &[Comparison], options: &ComparisonOptions, max_dim: Option<u32>) -> anyhow::Result<()> {
+    let command_line = std::env::args().map(|arg| if arg.contains(' ') { format!("\"{}\"", arg) } else { arg }).collect::<Vec<_>>().join(" ");
+    let mut summary = String::new();
+    let mut gallery = String::new();
+    let mut sections = String::new();
+    for (index, comparison) in comparisons.iter().enumerate() {
+        let status = Status::from(comparison);
+        let shown = comparisons.len() == 1 || status != Status::Match;
+        let different = match &comparison.result {
+            Ok(outcome) if !outcome.is_size_mismatch() => format!("{}%", outcome.wrong_percentage()),
+            _ => String::new(),
+        };
+        let label = if shown { format!("<a href=\"#pair-{index}\">{}</a>", status.label()) } else { status.label().to_string() };
+        summary.push_str(&format!("<tr><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            status.name(), label, different, escape(&comparison.image_paths[0]), escape(&comparison.image_paths[1])));
+        if shown {
+            let difference = comparison.result.as_ref().ok().and_then(|outcome| difference_image(comparison, outcome, options));
+            if comparisons.len() > 1 {
+                let caption = Path::new(&comparison.image_paths[1]).file_name().map_or(comparison.image_paths[1].clone(), |name| name.to_string_lossy().into_owned());
+                gallery.push_str(&format!("<a href=\"#pair-{index}\">{}</a>\n", figure_of(difference.as_ref(), &caption, status.name(), Some(THUMBNAIL_DIM))?));
+            }
+            sections.push_str(&section(index, comparison, difference.as_ref(), max_dim)?);
+        }
+    }
+
+    let report = format!(
+"<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>imgcmp report</title>
+<style>
+{STYLE}
+</style>
+</head>
+<body>
+<h1>imgcmp report</h1>
+<table>
+<tr><th>Command</th><td><code>{}</code></td></tr>
+<tr><th>Value threshold</th><td>{}</td></tr>
+<tr><th>Error threshold</th><td>{}</td></tr>
+</table>
+<h2>Summary</h2>
+<table>
+<tr><th>Status</th><th>Different pixels</th><th>Expected</th><th>Actual</th></tr>
+{summary}</table>
+{}{sections}</body>
+</html>
+", escape(&command_line), options.float_value_threshold, escape(&options.error_threshold.to_string()),
+    if gallery.is_empty() { String::new() } else { format!("<h2>Gallery</h2>\n<div class=\"gallery\">\n{gallery}</div>\n") });
+
+    fs::write(path, report).context(format!("Failed to write {}", path))
+}
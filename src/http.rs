@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+// Return true if the image path is an HTTP(S) URL.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// Fetch the bytes at the given URL (following redirects), failing if the server doesn't respond within the timeout
+// or responds with an unsuccessful status. The failures are I/O errors, so they are reported like unreadable files.
+#[cfg(feature = "http")]
+pub fn fetch(url: &str, timeout: Duration) -> anyhow::Result<Vec<u8>> {
+    use anyhow::Context;
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .build()
+        .context("Failed to create the HTTP client")?;
+    let fetch_error = || format!("Failed to fetch {}", url);
+    let response = client.get(url).send().map_err(std::io::Error::other).context(fetch_error())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow::Error::new(std::io::Error::other(format!("HTTP status {}", status))).context(fetch_error()));
+    }
+    Ok(response.bytes().map_err(std::io::Error::other).context(fetch_error())?.to_vec())
+}
+
+// Without the http feature, URLs can't be read.
+#[cfg(not(feature = "http"))]
+pub fn fetch(url: &str, _timeout: Duration) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow::Error::msg(format!("Reading {} requires the http feature (build with --features http)", url)))
+}
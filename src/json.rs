@@ -0,0 +1,389 @@
+use std::fs;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use imgcmp::{BoundingBox, stats::{self, ChannelStats, Suggestions}};
+
+use crate::{HISTOGRAM_BINS, Status, batch::Comparison};
+
+// A box written as [min_x, min_y, max_x, max_y] (both corners are inclusive).
+type JsonBox = [u32; 4];
+
+fn to_json_box(bbox: &BoundingBox) -> JsonBox {
+    [bbox.min.0, bbox.min.1, bbox.max.0, bbox.max.1]
+}
+
+// A cluster (region) of different pixels.
+#[derive(Serialize)]
+struct JsonCluster {
+    bbox: JsonBox,
+    pixel_count: u32,
+    max_error: f32,
+}
+
+// A tile that failed, with its column and row in the grid.
+#[derive(Serialize)]
+struct JsonTile {
+    column: u32,
+    row: u32,
+    bbox: JsonBox,
+    wrong_pixels: u32,
+}
+
+// The pixel with the largest error and the absolute difference of its RGB channels.
+#[derive(Serialize)]
+struct JsonPixelError {
+    x: u32,
+    y: u32,
+    r_diff: f32,
+    g_diff: f32,
+    b_diff: f32,
+}
+
+// A pixel with one of the largest differences and its RGB channels in both images.
+#[derive(Serialize)]
+struct JsonWorstPixel {
+    x: u32,
+    y: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frame: Option<usize>,
+    expected: [f32; 3],
+    actual: [f32; 3],
+    difference: [f32; 3],
+}
+
+// The perceptual hashes of the images (as hexadecimal strings) and the number of bits in which they differ.
+#[derive(Serialize)]
+struct JsonHash {
+    algorithm: &'static str,
+    hashes: [String; 2],
+    distance: u32,
+    threshold: u32,
+}
+
+// The linear transform applied to the channels of the second image by the normalization.
+#[derive(Serialize)]
+struct JsonNormalization {
+    gain: f32,
+    offset: f32,
+}
+
+// The statistics of the per-pixel errors and the value threshold derived from them.
+#[derive(Serialize)]
+struct JsonZScore {
+    z: f32,
+    mean: f32,
+    deviation: f32,
+    threshold: f32,
+}
+
+// A compared level of the pyramid.
+#[derive(Serialize)]
+struct JsonPyramidLevel {
+    level: u32,
+    width: u32,
+    height: u32,
+    wrong_pixels: u32,
+    total_pixels: u32,
+    matched: bool,
+}
+
+// The compared levels of the pyramid, from the coarsest one, and the level that detected the mismatch.
+#[derive(Serialize)]
+struct JsonPyramid {
+    levels: Vec<JsonPyramidLevel>,
+    mismatch_level: Option<u32>,
+}
+
+// The mean and maximum FLIP error.
+#[derive(Serialize)]
+struct JsonFlipStats {
+    mean: f32,
+    max: f32,
+}
+
+// The mean and maximum error of the frequency bins with the fft metric.
+#[derive(Serialize)]
+struct JsonFftStats {
+    mean: f32,
+    max: f32,
+}
+
+// The statistics of the differences of a channel.
+#[derive(Serialize)]
+struct JsonChannelStats {
+    mean: f32,
+    max: f32,
+    differing_pixels: u32,
+}
+
+fn to_json_channel_stats(stats: &ChannelStats) -> JsonChannelStats {
+    JsonChannelStats { mean: stats.mean, max: stats.max, differing_pixels: stats.differing }
+}
+
+// The statistics of the differences of the R, G and B channels.
+#[derive(Serialize)]
+struct JsonChannels {
+    r: JsonChannelStats,
+    g: JsonChannelStats,
+    b: JsonChannelStats,
+}
+
+// The thresholds under which the images would match.
+#[derive(Serialize)]
+struct JsonSuggestions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threshold: Option<f32>,
+    error: u32,
+    error_percentage: f32,
+}
+
+fn to_json_suggestions(suggestions: &Suggestions) -> JsonSuggestions {
+    JsonSuggestions { threshold: suggestions.value_threshold, error: suggestions.error_pixels, error_percentage: suggestions.error_percentage }
+}
+
+// The outcome under a value threshold of the sweep.
+#[derive(Serialize)]
+struct JsonSweepPoint {
+    threshold: f32,
+    wrong_pixels: u32,
+    wrong_percentage: f32,
+    matched: bool,
+}
+
+// The Hausdorff distance between the edges of the images and their numbers of edge pixels.
+#[derive(Serialize)]
+struct JsonEdgeStats {
+    hausdorff: f32,
+    edge_pixels: [u32; 2],
+}
+
+// The report of a single comparison. The outcome fields are only written if the images could be compared.
+#[derive(Serialize)]
+struct JsonComparison<'a> {
+    expected: &'a str,
+    actual: &'a str,
+    status: &'static str,
+    missing_baseline: bool,
+    updated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    outcome: Option<JsonOutcome>,
+}
+
+// The outcome of a comparison of two images.
+#[derive(Serialize)]
+struct JsonOutcome {
+    matched: bool,
+    sizes: [(u32, u32); 2],
+    wrong_pixels: u32,
+    total_pixels: u32,
+    wrong_percentage: f32,
+    mae: f64,
+    rmse: f64,
+    bbox: Option<JsonBox>,
+    clusters: Option<Vec<JsonCluster>>,
+    failed_tiles: Vec<JsonTile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tile_wrong_pixels: Option<Vec<Vec<u32>>>,
+    max_error_pixel: Option<JsonPixelError>,
+    alignment_offset: Option<(i32, i32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalization: Option<JsonNormalization>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zscore: Option<JsonZScore>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pyramid: Option<JsonPyramid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    perceptual_hash: Option<JsonHash>,
+    byte_identical: bool,
+    sampled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flip: Option<JsonFlipStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fft: Option<JsonFftStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edges: Option<JsonEdgeStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percentile_error: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channels: Option<JsonChannels>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_histogram: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestions: Option<JsonSuggestions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sweep: Option<Vec<JsonSweepPoint>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    worst_pixels: Option<Vec<JsonWorstPixel>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frame_counts: Option<[usize; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frames: Option<Vec<JsonFrame>>,
+}
+
+// The outcome of a pair of frames of animated images.
+#[derive(Serialize)]
+struct JsonFrame {
+    matched: bool,
+    wrong_pixels: u32,
+    total_pixels: u32,
+    bbox: Option<JsonBox>,
+}
+
+// The number of comparisons by status.
+#[derive(Serialize)]
+struct JsonSummary {
+    total: usize,
+    matched: usize,
+    mismatched: usize,
+    failed: usize,
+}
+
+// The report of a run, which contains the comparisons in order and their summary.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    summary: JsonSummary,
+    comparisons: Vec<JsonComparison<'a>>,
+}
+
+// A response of the server mode: the report of a comparison, or the error of a request that couldn't be read,
+// along with the id given in the request (if any).
+#[derive(Serialize)]
+struct JsonResponse<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a serde_json::Value>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    comparison: Option<JsonComparison<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn json_comparison(comparison: &Comparison) -> JsonComparison<'_> {
+    let status = Status::from(comparison);
+    let outcome = comparison.result.as_ref().ok().map(|outcome| JsonOutcome {
+        matched: outcome.matched,
+        sizes: outcome.sizes,
+        wrong_pixels: outcome.wrong_pixels,
+        total_pixels: outcome.total_pixels,
+        wrong_percentage: outcome.wrong_percentage(),
+        mae: outcome.mae,
+        rmse: outcome.rmse,
+        bbox: outcome.bbox.as_ref().map(to_json_box),
+        clusters: outcome.clusters.as_ref().map(|clusters| clusters.iter().map(|cluster| JsonCluster {
+            bbox: to_json_box(&cluster.bbox),
+            pixel_count: cluster.pixel_count,
+            max_error: cluster.max_error,
+        }).collect()),
+        failed_tiles: outcome.failed_tiles.iter().map(|tile| JsonTile {
+            column: tile.index.0,
+            row: tile.index.1,
+            bbox: to_json_box(&tile.bbox),
+            wrong_pixels: tile.wrong_pixels,
+        }).collect(),
+        tile_wrong_pixels: outcome.tiles.as_ref().map(|tiles| {
+            let columns = tiles.iter().map(|tile| tile.index.0 as usize + 1).max().unwrap_or(1);
+            tiles.chunks(columns).map(|row| row.iter().map(|tile| tile.wrong_pixels).collect()).collect()
+        }),
+        max_error_pixel: outcome.max_error_pixel.map(|pixel| JsonPixelError {
+            x: pixel.x,
+            y: pixel.y,
+            r_diff: pixel.channel_errors[0],
+            g_diff: pixel.channel_errors[1],
+            b_diff: pixel.channel_errors[2],
+        }),
+        alignment_offset: outcome.alignment_offset,
+        normalization: outcome.normalization.map(|normalization| JsonNormalization { gain: normalization.gain, offset: normalization.offset }),
+        pyramid: outcome.pyramid.as_ref().map(|pyramid| JsonPyramid {
+            levels: pyramid.levels.iter().map(|level| JsonPyramidLevel {
+                level: level.level,
+                width: level.size.0,
+                height: level.size.1,
+                wrong_pixels: level.wrong_pixels,
+                total_pixels: level.total_pixels,
+                matched: level.matched,
+            }).collect(),
+            mismatch_level: pyramid.mismatch_level,
+        }),
+        zscore: outcome.zscore.map(|zscore| JsonZScore { z: zscore.z, mean: zscore.mean, deviation: zscore.deviation, threshold: zscore.threshold }),
+        perceptual_hash: outcome.hash.map(|hash| JsonHash {
+            algorithm: hash.algorithm.name(),
+            hashes: hash.hashes.map(|hash| format!("{:016x}", hash)),
+            distance: hash.distance,
+            threshold: hash.threshold,
+        }),
+        byte_identical: outcome.byte_identical,
+        sampled: outcome.sampled,
+        flip: outcome.flip.map(|flip| JsonFlipStats { mean: flip.mean, max: flip.max }),
+        fft: outcome.fft.map(|fft| JsonFftStats { mean: fft.mean, max: fft.max }),
+        edges: outcome.edges.map(|edges| JsonEdgeStats { hausdorff: edges.hausdorff, edge_pixels: edges.edge_pixels }),
+        percentile_error: outcome.percentile_error,
+        error_histogram: outcome.error_histogram.as_deref().map(|histogram| stats::merge_bins(histogram, HISTOGRAM_BINS)),
+        channels: outcome.channel_stats.as_ref().map(|[r, g, b]| JsonChannels {
+            r: to_json_channel_stats(r),
+            g: to_json_channel_stats(g),
+            b: to_json_channel_stats(b),
+        }),
+        sweep: outcome.sweep.as_ref().map(|sweep| sweep.iter().map(|point| JsonSweepPoint {
+            threshold: point.value_threshold,
+            wrong_pixels: point.wrong_pixels,
+            wrong_percentage: if outcome.total_pixels == 0 { 0f32 } else { point.wrong_pixels as f32 * 100f32 / outcome.total_pixels as f32 },
+            matched: point.matched,
+        }).collect()),
+        worst_pixels: outcome.worst_pixels.as_ref().map(|pixels| pixels.iter().map(|pixel| JsonWorstPixel {
+            x: pixel.x,
+            y: pixel.y,
+            frame: pixel.frame,
+            expected: pixel.values[0],
+            actual: pixel.values[1],
+            difference: pixel.channel_errors(),
+        }).collect()),
+        suggestions: outcome.suggestions.as_ref().filter(|_| !outcome.matched).map(to_json_suggestions),
+        frame_counts: outcome.animation.as_ref().map(|animation| animation.frame_counts),
+        frames: outcome.animation.as_ref().map(|animation| animation.frames.iter().map(|frame| JsonFrame {
+            matched: frame.matched,
+            wrong_pixels: frame.wrong_pixels,
+            total_pixels: frame.total_pixels,
+            bbox: frame.bbox.as_ref().map(to_json_box),
+        }).collect()),
+    });
+    JsonComparison {
+        expected: &comparison.image_paths[0],
+        actual: &comparison.image_paths[1],
+        status: status.name(),
+        missing_baseline: status == Status::MissingBaseline,
+        updated: comparison.updated,
+        output: comparison.output.as_deref(),
+        error: comparison.result.as_ref().err().filter(|_| !comparison.updated).map(|err| format!("{err:#}")),
+        outcome,
+    }
+}
+
+// Return the response of the server mode to a request with the given id, written on a single line:
+// the report of the comparison (like a comparison of the JSON report), or the error of a request that couldn't be read (an invalid argument).
+pub fn response_line(id: Option<&serde_json::Value>, comparison: Result<&Comparison, &anyhow::Error>) -> String {
+    let response = match comparison {
+        Ok(comparison) => JsonResponse { id, comparison: Some(json_comparison(comparison)), status: None, error: None },
+        Err(err) => JsonResponse { id, comparison: None, status: Some(Status::InvalidArguments.name()), error: Some(format!("{err:#}")) },
+    };
+    serde_json::to_string(&response).expect("A response can always be serialized")
+}
+
+// Write a JSON report containing the outcome of each of the given comparisons and a summary of their statuses.
+pub fn write_report(path: &str, comparisons: &[Comparison]) -> anyhow::Result<()> {
+    let statuses: Vec<Status> = comparisons.iter().map(Status::from).collect();
+    let matched = statuses.iter().filter(|&&status| status == Status::Match).count();
+    let failed = statuses.iter().filter(|&&status| status >= Status::IoError).count();
+    let report = JsonReport {
+        summary: JsonSummary { total: comparisons.len(), matched, mismatched: comparisons.len() - matched - failed, failed },
+        comparisons: comparisons.iter().map(json_comparison).collect(),
+    };
+    let text = serde_json::to_string_pretty(&report)?;
+    fs::write(path, text + "\n").context(format!("Failed to write {}", path))
+}
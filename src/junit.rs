@@ -0,0 +1,72 @@
+use std::fs;
+
+use anyhow::Context;
+
+use crate::batch::Comparison;
+
+// Escape the characters that are not allowed to appear as-is in XML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Return the JUnit XML test case of a single comparison, along with its number of failures and errors.
+// A mismatch is reported as a <failure>, while a comparison error or a size mismatch is reported as an <error>.
+fn test_case(comparison: &Comparison) -> (u32, u32, String) {
+    let name = escape(&format!("{} vs {}", comparison.image_paths[0], comparison.image_paths[1]));
+
+    let (failures, errors, body) = match &comparison.result {
+        Ok(outcome) if outcome.is_size_mismatch() => {
+            let [size1, size2] = outcome.sizes;
+            let message = format!("Images have different sizes (Got ({}x{}) and ({}x{})).", size1.0, size1.1, size2.0, size2.1);
+            (0, 1, format!("      <error type=\"SizeMismatch\" message=\"{}\"/>\n", escape(&message)))
+        },
+        Ok(outcome) if !outcome.matched => {
+            let message = format!("MISMATCH DETECTED: {} of {} pixels differ ({}%)",
+                outcome.wrong_pixels, outcome.total_pixels, outcome.wrong_percentage());
+            let mut text = message.clone();
+            if let Some(output) = &comparison.output {
+                text.push_str(&format!("\nError image: {}", output));
+            }
+            (1, 0, format!("      <failure type=\"Mismatch\" message=\"{}\">{}</failure>\n", escape(&message), escape(&text)))
+        },
+        Ok(_) => (0, 0, String::new()),
+        Err(err) => {
+            (0, 1, format!("      <error type=\"Error\" message=\"{}\">{}</error>\n", escape(&err.to_string()), escape(&format!("{err:?}"))))
+        },
+    };
+
+    (failures, errors, format!("    <testcase classname=\"imgcmp\" name=\"{name}\">\n{body}    </testcase>\n"))
+}
+
+// Write a JUnit XML report containing a test case for each of the given comparisons.
+pub fn write_report(path: &str, comparisons: &[Comparison]) -> anyhow::Result<()> {
+    let (mut failures, mut errors, mut test_cases) = (0, 0, String::new());
+    for comparison in comparisons {
+        let (case_failures, case_errors, test_case) = test_case(comparison);
+        failures += case_failures;
+        errors += case_errors;
+        test_cases.push_str(&test_case);
+    }
+    let tests = comparisons.len();
+
+    let report = format!(
+"<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<testsuites>
+  <testsuite name=\"imgcmp\" tests=\"{tests}\" failures=\"{failures}\" errors=\"{errors}\">
+{test_cases}  </testsuite>
+</testsuites>
+");
+
+    fs::write(path, report).context(format!("Failed to write {}", path))
+}
@@ -0,0 +1,1294 @@
+pub mod align;
+pub mod alpha;
+pub mod clusters;
+pub mod colormap;
+pub mod cvd;
+pub mod edges;
+pub mod explain;
+pub mod fft;
+pub mod flip;
+pub mod hash;
+pub mod normalize;
+pub mod percentile;
+pub mod pyramid;
+pub mod render;
+pub mod rows;
+pub mod sample;
+pub mod stats;
+pub mod tiles;
+pub mod zscore;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "wasm")]
+mod wasm;
+use clusters::{Cluster, Connectivity};
+use percentile::ErrorDistribution;
+use render::{DiffStyle, ErrorBackground};
+use stats::{ChannelAccumulator, ChannelStats, HISTOGRAM_BINS};
+use tiles::{Tile, TileGrid, TileLayout};
+
+// This enum defines a threshold either as:
+// - An absolute integer value (e.g. the number of pixels in the image)
+// - A ratio value (e.g. the percentage of pixels in the image)
+// - A range between two optional bounds, each of which is an absolute or a ratio value
+#[derive(Debug, Clone)]
+pub enum Threshold {
+    Absolute(u32),
+    Ratio(f32),
+    Range(Option<Box<Threshold>>, Option<Box<Threshold>>),
+}
+
+impl Threshold {
+    // Given the image size, return the threshold value in number of pixels. 
+    // For a range, this is the upper bound.
+    pub fn get_actual_threshold(&self, image_size: (u32, u32)) -> u32 {
+        self.get_actual_range(image_size).1
+    }
+
+    // Given the image size, return the (inclusive) range of accepted values in number of pixels.
+    // An absolute or ratio threshold accepts any value from 0 up to the threshold,
+    // while a missing lower or upper bound in a range defaults to 0 or unlimited respectively.
+    pub fn get_actual_range(&self, image_size: (u32, u32)) -> (u32, u32) {
+        match self {
+            Threshold::Absolute(value) => (0, *value),
+            Threshold::Ratio(ratio) => (0, (ratio * (image_size.0 * image_size.1) as f32) as u32),
+            Threshold::Range(min, max) => (
+                min.as_ref().map_or(0, |min| min.get_actual_threshold(image_size)),
+                max.as_ref().map_or(u32::MAX, |max| max.get_actual_threshold(image_size)),
+            ),
+        }
+    }
+
+    // Given the image size, return true if the value is within the range accepted by the threshold.
+    pub fn accepts(&self, value: u32, image_size: (u32, u32)) -> bool {
+        let (min, max) = self.get_actual_range(image_size);
+        min <= value && value <= max
+    }
+}
+
+impl std::fmt::Display for Threshold {
+    // Write the threshold in the syntax parsed by `try_from`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Threshold::Absolute(value) => write!(f, "{}", value),
+            Threshold::Ratio(ratio) => write!(f, "{}%", ratio * 100f32),
+            Threshold::Range(min, max) => {
+                if let Some(min) = min { write!(f, "{}", min)?; }
+                write!(f, "..")?;
+                if let Some(max) = max { write!(f, "{}", max)?; }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl TryFrom<&str> for Threshold {
+    type Error = anyhow::Error;
+    // Try to parse a string into a threshold.
+    // If the string contains "..", it is a range whose bounds (which can be omitted) are parsed separately.
+    // If the string ends with "%", it is a ratio. Otherwise, it is an absolute value.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Some((min, max)) = value.split_once("..") {
+            let parse_bound = |bound: &str| -> anyhow::Result<Option<Box<Threshold>>> {
+                let bound = bound.trim();
+                if bound.is_empty() { return Ok(None); }
+                match Threshold::try_from(bound)? {
+                    Threshold::Range(..) => Err(anyhow::Error::msg(format!("Invalid range \"{}\"", value))),
+                    bound => Ok(Some(Box::new(bound))),
+                }
+            };
+            let (min, max) = (parse_bound(min)?, parse_bound(max)?);
+            // Bounds of the same kind can be compared regardless of the image size, and an inverted range could never be satisfied.
+            let inverted = match (min.as_deref(), max.as_deref()) {
+                (Some(Threshold::Absolute(min)), Some(Threshold::Absolute(max))) => min > max,
+                (Some(Threshold::Ratio(min)), Some(Threshold::Ratio(max))) => min > max,
+                _ => false,
+            };
+            if inverted {
+                return Err(anyhow::Error::msg(format!("Invalid range \"{}\" (the lower bound is above the upper one)", value)));
+            }
+            Ok(Threshold::Range(min, max))
+        } else if value.ends_with("%") {
+            Ok(Threshold::Ratio(value[0..value.len()-1].parse::<f32>()? / 100f32))
+        } else {
+            Ok(Threshold::Absolute(value.parse::<u32>()?))
+        }
+    }
+}
+
+// The metric used to decide whether a pair of pixels is different.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Pixel,  // The largest difference among the compared channels (optionally blended with the luminance difference).
+    Flip,   // The FLIP perceptual error, which weighs the color difference by how visible it is to a human observer.
+    Edges,  // The distance from each edge pixel to the closest edge of the other image, which tolerates small stroke changes in drawings.
+    Gradient, // The difference between the Sobel gradient magnitudes of the luminance, which finds the edges that got blurrier or sharper.
+    Fft,    // The difference between the magnitude spectra of the images, which finds the artifacts spread over the whole image (see `fft`).
+}
+
+impl TryFrom<&str> for Metric {
+    type Error = anyhow::Error;
+    // Try to parse a string into a metric.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "pixel" => Ok(Metric::Pixel),
+            "flip" => Ok(Metric::Flip),
+            "edges" => Ok(Metric::Edges),
+            "gradient" => Ok(Metric::Gradient),
+            "fft" => Ok(Metric::Fft),
+            _ => Err(anyhow::Error::msg(format!("Invalid metric \"{}\" (expected pixel, flip, edges, gradient or fft)", value))),
+        }
+    }
+}
+
+// Parse a channel name (R, G, B or A in either case) into its index in an RGBA pixel.
+pub fn parse_channel(value: &str) -> anyhow::Result<usize> {
+    match value.to_ascii_uppercase().as_str() {
+        "R" => Ok(0),
+        "G" => Ok(1),
+        "B" => Ok(2),
+        "A" => Ok(3),
+        _ => Err(anyhow::Error::msg(format!("Invalid channel \"{}\" (expected R, G, B or A)", value))),
+    }
+}
+
+// The channels compared when no specific channels are selected.
+pub const RGB_CHANNELS: [usize; 3] = [0, 1, 2];
+
+// An axis-aligned box enclosing a set of pixels (both corners are inclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: (u32, u32),
+    pub max: (u32, u32),
+}
+
+impl BoundingBox {
+    // Create a box that contains a single pixel.
+    pub fn new(x: u32, y: u32) -> Self {
+        BoundingBox { min: (x, y), max: (x, y) }
+    }
+
+    // Grow the box (if needed) to contain the given pixel.
+    pub fn include(&mut self, x: u32, y: u32) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    // Grow the box (if needed) to contain another box.
+    pub fn merge(&mut self, other: &BoundingBox) {
+        self.include(other.min.0, other.min.1);
+        self.include(other.max.0, other.max.1);
+    }
+
+    // Return the size of the box in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.max.0 - self.min.0 + 1, self.max.1 - self.min.1 + 1)
+    }
+
+    // Return true if the box contains the given pixel.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        self.min.0 <= x && x <= self.max.0 && self.min.1 <= y && y <= self.max.1
+    }
+
+    // Return the box covering the pixels in both boxes (None if they don't overlap).
+    pub fn intersection(&self, other: &BoundingBox) -> Option<BoundingBox> {
+        let min = (self.min.0.max(other.min.0), self.min.1.max(other.min.1));
+        let max = (self.max.0.min(other.max.0), self.max.1.min(other.max.1));
+        if min.0 <= max.0 && min.1 <= max.1 { Some(BoundingBox { min, max }) } else { None }
+    }
+
+    // Draw the box outline on the given image.
+    pub fn draw(&self, image: &mut image::RgbImage, color: image::Rgb<u8>) {
+        for x in self.min.0..=self.max.0 {
+            image.put_pixel(x, self.min.1, color);
+            image.put_pixel(x, self.max.1, color);
+        }
+        for y in self.min.1..=self.max.1 {
+            image.put_pixel(self.min.0, y, color);
+            image.put_pixel(self.max.0, y, color);
+        }
+    }
+}
+
+impl std::fmt::Display for BoundingBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{} .. {},{}", self.min.0, self.min.1, self.max.0, self.max.1)
+    }
+}
+
+// The color used to draw the bounding box of the differences on the error image.
+pub const BBOX_COLOR: image::Rgb<u8> = image::Rgb([255, 255, 0]);
+
+// The color used to draw the lines between the tiles on the error image.
+pub const TILE_GRID_COLOR: image::Rgb<u8> = image::Rgb([0, 128, 255]);
+
+// The options that control how two images are compared and how the error image is drawn.
+#[derive(Debug, Clone)]
+pub struct ComparisonOptions {
+    pub metric: Metric,         // The metric used to compare the pixels. With FLIP (or gradient), the float value threshold applies to the FLIP (or gradient) error [0-1].
+    pub edge_distance: f32,     // With the edges metric, the largest distance (in pixels) allowed between an edge pixel and the closest edge of the other image.
+    pub value_threshold: u8,    // A threshold [0-1] on the maximum allowed per-channel error.
+                                // if 0, any difference passes the threshold. if 1, nothing passes the threshold.
+    pub float_value_threshold: f32, // The same threshold used as-is when comparing floating-point (e.g. HDR) images.
+    pub luma_weight: f32,       // The weight [0-1] of the luminance difference blended with the largest channel difference (0 compares the channels only).
+    pub tolerance_map: Option<image::GrayImage>, // If set, the value threshold of each pixel is read from this map instead (255 always passes).
+    pub zscore_threshold: Option<f32>, // If set to Z, the value threshold is replaced by the mean of the per-pixel errors plus Z standard deviations
+                                // (see `zscore::zscore_threshold`), which adapts it to the noise of the images.
+    pub error_threshold: Threshold, // The number (or percentage) of pixels allowed to be different before the result is considered a mismatch.
+    pub percentile: Option<(f32, f32)>, // If set to (P, V), the result is a mismatch if the P-th percentile [0-100] of the per-pixel errors
+                                        // (the values the max error pixel is chosen by) exceeds V.
+    pub error_histogram: bool,  // If true, the histogram of the per-pixel errors (the largest channel difference) of all the compared pixels is computed.
+    pub suggest_thresholds: bool, // If true, the thresholds under which a mismatch would pass are suggested (see `stats::Suggestions`).
+    pub sweep: Option<Vec<f32>>, // If set, the outcome is also computed under each of these value thresholds [0-1] from the error histogram,
+                                 // which must be computed (see `stats::sweep`).
+    pub explain: Option<usize>, // If set, the N pixels with the largest differences are collected with their values (see `explain::WorstPixel`).
+    pub perceptual_hash: Option<(hash::HashAlgorithm, u32)>, // If set to (A, D), the images are compared by their perceptual hashes (computed with A)
+                                // instead of their pixels, and they match if the hashes differ in at most D bits (see `hash::compare_hashes`).
+    pub diff_mask: bool,        // If true, the mask of the wrong pixels is kept in the outcome (see `ComparisonOutcome::diff_mask`).
+    pub sample: Option<sample::Sampling>, // If set, only the pixels at random positions are compared, which approximates the outcome (see `sample::compare_sampled`).
+    pub draw_bbox: bool,        // If true, the bounding box of the different pixels is drawn on the error image.
+    pub diff_only: bool,        // If true, the error image has the raw differences of the different pixels (without remapping) and the matching pixels are black,
+                                // regardless of the diff style and error background (see `raw_error`).
+    pub clamp_output: Option<u8>, // If set, the errors drawn on the error image are capped at this value [1-255] and stretched, so the smaller errors are brighter.
+                                // It doesn't change the statistics.
+    pub diff_gain: f32,         // The factor the errors drawn on the error image are multiplied by (saturating at 255), so the small errors are visible.
+                                // It doesn't change the statistics either.
+    pub tile_size: Option<(u32, u32)>, // If set, the different pixels are counted per tile of this size (see `ComparisonOutcome::tiles`).
+    pub draw_tile_grid: bool,   // If true, the lines between the tiles of the tile size are drawn on the error image.
+    pub connectivity: Connectivity, // The connectivity used to group the different pixels into connected regions (clusters).
+    pub find_clusters: bool,    // If true, the different pixels are always grouped into clusters.
+    pub min_cluster_size: Option<u32>, // If set, clusters with fewer pixels are discarded before the threshold decision (and removed from the error image).
+    pub max_cluster_size: Option<u32>, // If set, the result is a mismatch if any cluster has more pixels.
+    pub diff_style: DiffStyle,  // The visual style of the error image.
+    pub extra_diff_styles: Vec<DiffStyle>, // The styles of the other error images drawn in the same pass (see `ComparisonOutcome::extra_error_images`).
+    pub highlight_color: image::Rgb<u8>, // The color of the different pixels in the overlay diff style.
+    pub error_background: ErrorBackground, // The color of the matching pixels in the difference diff style.
+    pub tile: Option<(TileLayout, Option<Threshold>)>, // If set, the image is divided into tiles with the given layout, and the tiles exceeding the given threshold
+                                        // fail, which makes the result a mismatch. Without a threshold, the tiles with any wrong pixel fail, but only for the report.
+    pub channels: Option<Vec<usize>>, // If set, only these channels (as RGBA indices) are compared and the error image is grayscale
+                                      // (unless the diff style is colorblind-safe).
+    pub shift_tolerance: u32,   // The radius within which a pixel can match a shifted counterpart in the other image.
+    pub ignore_border: u32,     // The width of the frame around the image that is excluded from the comparison.
+    pub alpha_threshold: Option<u8>, // If set, the pixels whose alpha is below this value [0-255] in both images always match (see `ImagePair::is_transparent`).
+    pub align: Option<u32>,     // If set, the second image is aligned to the first one by searching for the best translation within this radius,
+                                // and only the region where they overlap is compared.
+    pub normalize: bool,        // If true, the brightness and contrast of the second image are normalized to those of the first one before comparing them
+                                // (see `normalize::normalize`), so the outcome and the error image reflect the differences after the normalization.
+    pub pyramid: Option<u32>,   // If set, the images are compared at up to this many levels of a Gaussian pyramid, from the coarsest one,
+                                // and the comparison fails at the first level that exceeds its error threshold (see `pyramid::level_options`).
+    pub early_exit: bool,       // If true, the comparison stops as soon as there are more wrong pixels than the error threshold allows,
+                                // in which case the error image and the statistics of the outcome are incomplete.
+                                // It has no effect if the clusters, tiles, FLIP, gradient or edge statistics are needed.
+}
+
+impl ComparisonOptions {
+    // Return the value thresholds (for 8-bit and float images) of the pixel (x, y), taken from the tolerance map if there is one.
+    fn value_thresholds_at(&self, x: u32, y: u32) -> (u8, f32) {
+        match &self.tolerance_map {
+            Some(map) => {
+                let tolerance = map.get_pixel(x, y)[0];
+                (tolerance, if tolerance == u8::MAX { f32::INFINITY } else { tolerance as f32 / 255f32 })
+            },
+            None => (self.value_threshold, self.float_value_threshold),
+        }
+    }
+
+    // Return true if the different pixels should be grouped into clusters.
+    fn analyze_clusters(&self) -> bool {
+        self.find_clusters || self.min_cluster_size.is_some() || self.max_cluster_size.is_some()
+    }
+}
+
+impl Default for ComparisonOptions {
+    // The default options require the images to be identical.
+    fn default() -> Self {
+        ComparisonOptions {
+            metric: Metric::Pixel,
+            edge_distance: 1f32,
+            value_threshold: 0,
+            float_value_threshold: 0f32,
+            zscore_threshold: None,
+            luma_weight: 0f32,
+            tolerance_map: None,
+            error_threshold: Threshold::Absolute(0),
+            percentile: None,
+            error_histogram: false,
+            suggest_thresholds: false,
+            sweep: None,
+            explain: None,
+            perceptual_hash: None,
+            diff_mask: false,
+            sample: None,
+            draw_bbox: false,
+            diff_only: false,
+            clamp_output: None,
+            diff_gain: 1f32,
+            tile_size: None,
+            draw_tile_grid: false,
+            connectivity: Connectivity::Eight,
+            find_clusters: false,
+            min_cluster_size: None,
+            max_cluster_size: None,
+            diff_style: DiffStyle::Difference,
+            extra_diff_styles: Vec::new(),
+            highlight_color: image::Rgb([255, 0, 0]),
+            error_background: ErrorBackground::Black,
+            tile: None,
+            channels: None,
+            shift_tolerance: 0,
+            ignore_border: 0,
+            alpha_threshold: None,
+            align: None,
+            normalize: false,
+            pyramid: None,
+            early_exit: false,
+        }
+    }
+}
+
+// The pixel with the largest error and the absolute difference of its RGB channels
+// (in [0-255] for 8-bit images, and in linear float space for float images).
+#[derive(Debug, Clone, Copy)]
+pub struct PixelError {
+    pub x: u32,
+    pub y: u32,
+    pub channel_errors: [f32; 3],
+}
+
+// The result of comparing two images.
+pub struct ComparisonOutcome {
+    pub matched: bool,          // True if the images match.
+    pub sizes: [(u32, u32); 2], // The sizes of the two images.
+    pub wrong_pixels: u32,      // The number of pixels that differ by more than the value threshold.
+    pub total_pixels: u32,      // The number of compared pixels (0 if the images have different sizes).
+    pub allowed_wrong_pixels: (u32, u32), // The (inclusive) range of wrong pixels allowed by the error threshold.
+    pub bbox: Option<BoundingBox>, // The box enclosing all the wrong pixels (None if there are no wrong pixels).
+    pub clusters: Option<Vec<Cluster>>, // The clusters of wrong pixels (if cluster analysis was requested).
+    pub oversized_cluster: bool, // True if any cluster exceeds the maximum cluster size.
+    pub failed_tiles: Vec<Tile>, // The tiles that exceeded the tile error threshold (or that have any wrong pixel without one).
+    pub tiles: Option<Vec<Tile>>, // All the tiles of the tile size in row-major order, with their different pixels (if a tile size was given).
+    pub max_error_pixel: Option<PixelError>, // The compared pixel with the largest error among the compared channels (None if no pixel differs at all).
+    pub alignment_offset: Option<(i32, i32)>, // The translation of the second image found by the alignment (if requested).
+                                // In that case, the sizes, coordinates and error image refer to the overlapping region.
+    pub normalization: Option<normalize::Normalization>, // The transform applied to the second image by the normalization (if requested).
+    pub pyramid: Option<pyramid::PyramidOutcome>, // The levels compared by a pyramid comparison (if requested). In that case, the rest of the outcome
+                                // (and the error image) is that of the level that failed, or of the full resolution if all the levels matched.
+    pub zscore: Option<zscore::ZScoreThreshold>, // The statistics of the per-pixel errors and the value threshold derived from them (if requested).
+    pub flip: Option<flip::FlipStats>, // The mean and maximum FLIP error over the compared pixels (if the FLIP metric was used).
+    pub fft: Option<fft::FftStats>, // The statistics of the differences between the spectra (if the fft metric was used),
+                                // in which case the pixel counts are those of the frequency bins.
+    pub edges: Option<edges::EdgeStats>, // The Hausdorff distance between the edges of the images (if the edges metric was used).
+    pub percentile_error: Option<f32>, // The percentile of the per-pixel errors given by the options (if a percentile limit was given).
+    pub channel_stats: Option<[ChannelStats; 3]>, // The statistics of the differences of the R, G and B channels over the compared pixels.
+    pub error_histogram: Option<Vec<u64>>, // The number of compared pixels per 8-bit level of their largest channel difference (if requested).
+    pub mae: f64,               // The mean absolute error over the RGB channels of the compared pixels (in [0-255] for 8-bit images).
+    pub rmse: f64,              // The root mean squared error over the RGB channels of the compared pixels (in [0-255] for 8-bit images).
+    pub suggestions: Option<stats::Suggestions>, // The thresholds under which the images would match (if requested), which are only reported if they don't.
+    pub sweep: Option<Vec<stats::SweepPoint>>, // The outcomes under the value thresholds of the sweep (if requested).
+    pub worst_pixels: Option<Vec<explain::WorstPixel>>, // The pixels with the largest differences, from the largest one (if requested).
+    pub hash: Option<hash::HashComparison>, // The perceptual hashes of the images if they were compared by them, in which case no pixel was compared.
+    pub diff_mask: Option<Vec<bool>>, // The row-major mask of the pixels that differ by more than the value threshold (if requested and the whole images were compared).
+                                // Its pixels are those of the compared images, after the alignment and normalization (if any).
+    pub extra_error_images: Vec<image::DynamicImage>, // The error images in the extra diff styles of the options, in the same order (if the pixels were compared).
+    pub byte_identical: bool,   // True if the images were files with the same bytes, so they matched without being decoded.
+    pub sampled: bool,          // True if only a random sample of the pixels was compared, in which case the pixel counts are those of the samples.
+    pub animation: Option<AnimationOutcome>, // The outcomes of the frames if animated images were compared frame by frame.
+                                // In that case, the pixel counts are summed over the frames, and the clusters and tiles are only found in the frames.
+}
+
+// The outcomes of comparing two animated images frame by frame.
+pub struct AnimationOutcome {
+    pub frame_counts: [usize; 2],   // The number of frames of the two images.
+    pub frames: Vec<ComparisonOutcome>, // The outcome of each pair of frames (up to the smaller frame count).
+}
+
+impl ComparisonOutcome {
+    // Create the outcome of comparing two images of different sizes, which is always a mismatch.
+    fn size_mismatch(sizes: [(u32, u32); 2]) -> Self {
+        ComparisonOutcome {
+            matched: false,
+            sizes,
+            wrong_pixels: 0,
+            total_pixels: 0,
+            allowed_wrong_pixels: (0, 0),
+            bbox: None,
+            clusters: None,
+            oversized_cluster: false,
+            failed_tiles: Vec::new(),
+            tiles: None,
+            max_error_pixel: None,
+            alignment_offset: None,
+            normalization: None,
+            pyramid: None,
+            zscore: None,
+            flip: None,
+            fft: None,
+            edges: None,
+            percentile_error: None,
+            channel_stats: None,
+            error_histogram: None,
+            mae: 0f64,
+            rmse: 0f64,
+            suggestions: None,
+            sweep: None,
+            worst_pixels: None,
+            hash: None,
+            diff_mask: None,
+            extra_error_images: Vec::new(),
+            byte_identical: false,
+            sampled: false,
+            animation: None,
+        }
+    }
+
+    // Create the outcome of comparing two byte-identical files of the given size without decoding them, where no pixel differs.
+    // It is still a mismatch if the error threshold requires some different pixels.
+    pub fn byte_identical(size: (u32, u32), options: &ComparisonOptions) -> Self {
+        let border = options.ignore_border;
+        let region = (size.0.saturating_sub(2 * border), size.1.saturating_sub(2 * border));
+        let allowed_wrong_pixels = options.error_threshold.get_actual_range(region);
+        ComparisonOutcome {
+            matched: allowed_wrong_pixels.0 == 0,
+            total_pixels: region.0 * region.1,
+            allowed_wrong_pixels,
+            // No pixel differs under any value threshold of the sweep.
+            sweep: options.sweep.as_ref().map(|thresholds| thresholds.iter()
+                .map(|&value_threshold| stats::SweepPoint { value_threshold, wrong_pixels: 0, matched: allowed_wrong_pixels.0 == 0 }).collect()),
+            worst_pixels: options.explain.map(|_| Vec::new()),
+            byte_identical: true,
+            ..ComparisonOutcome::size_mismatch([size, size])
+        }
+    }
+
+    // Return true if the images could not be compared since they have different sizes.
+    pub fn is_size_mismatch(&self) -> bool {
+        self.sizes[0] != self.sizes[1]
+    }
+
+    // Return the (at most) N largest clusters by pixel count, in descending order (None if the clusters were not analyzed).
+    pub fn top_clusters(&self, count: usize) -> Option<Vec<Cluster>> {
+        let mut clusters = self.clusters.clone()?;
+        clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.pixel_count));
+        clusters.truncate(count);
+        Some(clusters)
+    }
+
+    // Return the percentage of wrong pixels.
+    pub fn wrong_percentage(&self) -> f32 {
+        if self.total_pixels == 0 { 0f32 } else { (self.wrong_pixels as f64 * 100f64 / self.total_pixels as f64) as f32 }
+    }
+}
+
+// The decoded pixels of the two compared images.
+#[derive(Clone)]
+pub enum ImagePair {
+    Integer(image::RgbaImage, image::RgbaImage),    // 8-bit images compared in the [0-255] range.
+    Float(image::Rgba32FImage, image::Rgba32FImage), // Floating-point (e.g. HDR) images compared in linear float space.
+}
+
+impl ImagePair {
+    // Create the pair from the decoded images.
+    // If any of them is a floating-point image, both are compared in float space to keep their high dynamic range.
+    pub fn new(img1: image::DynamicImage, img2: image::DynamicImage) -> Self {
+        let is_float = |image: &image::DynamicImage| matches!(image, image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_));
+        if is_float(&img1) || is_float(&img2) {
+            ImagePair::Float(img1.into_rgba32f(), img2.into_rgba32f())
+        } else {
+            ImagePair::Integer(img1.into_rgba8(), img2.into_rgba8())
+        }
+    }
+
+    // Return the sizes of the two images.
+    pub fn dimensions(&self) -> [(u32, u32); 2] {
+        match self {
+            ImagePair::Integer(img1, img2) => [img1.dimensions(), img2.dimensions()],
+            ImagePair::Float(img1, img2) => [img1.dimensions(), img2.dimensions()],
+        }
+    }
+
+    // Return true if the pixel (x, y) of one image matches any pixel within the shift tolerance (as a Chebyshev radius) around (x, y) in the other image,
+    // using the given value thresholds and the channels and luma weight of the options.
+    // The pixel is taken from the first image unless `reversed` is true. The search stops at the first match.
+    fn has_nearby_match(&self, x: u32, y: u32, reversed: bool, thresholds: (u8, f32), options: &ComparisonOptions) -> bool {
+        let (radius, luma_weight) = (options.shift_tolerance, options.luma_weight);
+        let channels = options.channels.as_deref().unwrap_or(&RGB_CHANNELS);
+        let (width, height) = self.dimensions()[0];
+        let (min_x, max_x) = (x.saturating_sub(radius), x.saturating_add(radius).min(width - 1));
+        let (min_y, max_y) = (y.saturating_sub(radius), y.saturating_add(radius).min(height - 1));
+        (min_y..=max_y).any(|ny| (min_x..=max_x).any(|nx| {
+            let is_different = match self {
+                ImagePair::Integer(img1, img2) => {
+                    let (center, neighborhood) = if reversed { (img2, img1) } else { (img1, img2) };
+                    compare_pixels(center.get_pixel(x, y), neighborhood.get_pixel(nx, ny), channels, thresholds.0, luma_weight).0
+                },
+                ImagePair::Float(img1, img2) => {
+                    let (center, neighborhood) = if reversed { (img2, img1) } else { (img1, img2) };
+                    compare_float_pixels(center.get_pixel(x, y), neighborhood.get_pixel(nx, ny), channels, thresholds.1, luma_weight).0
+                },
+            };
+            !is_different
+        }))
+    }
+
+    // Return true if the alpha of the pixel (x, y) is below the given threshold [0-255] in both images (float alphas are scaled to [0-255]).
+    fn is_transparent(&self, x: u32, y: u32, alpha_threshold: u8) -> bool {
+        match self {
+            ImagePair::Integer(img1, img2) => img1.get_pixel(x, y)[3] < alpha_threshold && img2.get_pixel(x, y)[3] < alpha_threshold,
+            ImagePair::Float(img1, img2) => [img1, img2].iter().all(|image| image.get_pixel(x, y)[3] * 255f32 < alpha_threshold as f32),
+        }
+    }
+
+    // Return the errors of the channels of the pixel (x, y) that are drawn in `remapped` (the others stay 0), scaled by the given function
+    // (see `stretch_error` and `amplify_error`), then remapped to [128-255] like in `compare_pixels` if requested.
+    // The differences of float images are scaled to [0-255] instead of tonemapped.
+    fn scaled_errors(&self, x: u32, y: u32, remapped: [u8; 4], scale: impl Fn(f32) -> u8, remap: bool) -> [u8; 4] {
+        let differences = match self {
+            ImagePair::Integer(img1, img2) => { let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y)); [0, 1, 2, 3].map(|c| pixel1[c].abs_diff(pixel2[c]) as f32) },
+            ImagePair::Float(img1, img2) => { let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y)); [0, 1, 2, 3].map(|c| (pixel1[c] - pixel2[c]).abs() * 255f32) },
+        };
+        std::array::from_fn(|c| match (remapped[c], remap) {
+            (0, _) => 0,
+            (_, true) => 128 | scale(differences[c]) >> 1,
+            (_, false) => scale(differences[c]),
+        })
+    }
+
+    // Return the raw differences [0-255] of the RGB channels of the pixel (x, y) for a diff-only image (see `raw_error`),
+    // or the largest difference among the given channels in all three if specific channels are selected.
+    fn raw_differences(&self, x: u32, y: u32, channels: Option<&[usize]>) -> [u8; 3] {
+        let differences = match self {
+            ImagePair::Integer(img1, img2) => { let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y)); [0, 1, 2, 3].map(|c| pixel1[c].abs_diff(pixel2[c])) },
+            ImagePair::Float(img1, img2) => { let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y)); [0, 1, 2, 3].map(|c| raw_error((pixel1[c] - pixel2[c]).abs())) },
+        };
+        match channels {
+            Some(channels) => [channels.iter().map(|&c| differences[c]).max().unwrap_or(0); 3],
+            None => [differences[0], differences[1], differences[2]],
+        }
+    }
+
+    // Return the color of the matching pixel (x, y) in the error image with the given background.
+    fn background_pixel(&self, x: u32, y: u32, background: ErrorBackground) -> [u8; 3] {
+        match background {
+            ErrorBackground::Black => [0; 3],
+            ErrorBackground::Original => self.dimmed_first_pixel(x, y, 1),
+            ErrorBackground::White => [255; 3],
+        }
+    }
+
+    // Return the largest error among the given channels of the pixel (x, y), normalized to [0-1] for 8-bit images.
+    fn pixel_error(&self, x: u32, y: u32, channels: &[usize]) -> f32 {
+        match self {
+            ImagePair::Integer(img1, img2) => {
+                let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y));
+                channels.iter().map(|&channel| pixel1[channel].abs_diff(pixel2[channel]) as f32 / 255f32).fold(0f32, f32::max)
+            },
+            ImagePair::Float(img1, img2) => {
+                let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y));
+                channels.iter().map(|&channel| (pixel1[channel] - pixel2[channel]).abs()).fold(0f32, f32::max)
+            },
+        }
+    }
+
+    // Return the RGB channels of the pixel (x, y) of the first image, divided by 2^shift to dim it (float images are clamped to [0-1]).
+    fn dimmed_first_pixel(&self, x: u32, y: u32, shift: u8) -> [u8; 3] {
+        match self {
+            ImagePair::Integer(img1, _) => { let pixel = img1.get_pixel(x, y); [0, 1, 2].map(|c| pixel[c] >> shift) },
+            ImagePair::Float(img1, _) => { let pixel = img1.get_pixel(x, y); [0, 1, 2].map(|c| ((pixel[c].clamp(0f32, 1f32) * 255f32) as u8) >> shift) },
+        }
+    }
+
+    // Return the RGB channels of the pixel (x, y) in both images (in [0-255] for 8-bit images).
+    fn pixel_values(&self, x: u32, y: u32) -> [[f32; 3]; 2] {
+        match self {
+            ImagePair::Integer(img1, img2) => [img1, img2].map(|image| { let pixel = image.get_pixel(x, y); [0, 1, 2].map(|c| pixel[c] as f32) }),
+            ImagePair::Float(img1, img2) => [img1, img2].map(|image| { let pixel = image.get_pixel(x, y); [0, 1, 2].map(|c| pixel[c]) }),
+        }
+    }
+
+    // Return the absolute difference of the RGB channels of the pixel (x, y).
+    pub fn channel_errors(&self, x: u32, y: u32) -> [f32; 3] {
+        match self {
+            ImagePair::Integer(img1, img2) => {
+                let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y));
+                [0, 1, 2].map(|channel| pixel1[channel].abs_diff(pixel2[channel]) as f32)
+            },
+            ImagePair::Float(img1, img2) => {
+                let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y));
+                [0, 1, 2].map(|channel| (pixel1[channel] - pixel2[channel]).abs())
+            },
+        }
+    }
+
+    // Return the first image as an 8-bit image (float images are clamped to [0-1]).
+    pub fn first_rgba8(&self) -> std::borrow::Cow<'_, image::RgbaImage> {
+        match self {
+            ImagePair::Integer(img1, _) => std::borrow::Cow::Borrowed(img1),
+            ImagePair::Float(img1, _) => std::borrow::Cow::Owned(image::DynamicImage::ImageRgba32F(img1.clone()).into_rgba8()),
+        }
+    }
+}
+
+// Return the luminance of an RGB color (using the Rec. 601 weights).
+pub(crate) fn luma(r: f32, g: f32, b: f32) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+// Scale an error in [0-1] to [0-255] for a diff-only image, rounding up so any nonzero error stays visible.
+fn raw_error(error: f32) -> u8 {
+    (error * 255f32).ceil().min(255f32) as u8
+}
+
+// Cap an error in [0-255] at the clamp value [1-255], and stretch it so the clamp value becomes 255.
+fn stretch_error(error: f32, clamp: u8) -> u8 {
+    (error.min(clamp as f32) * 255f32 / clamp as f32) as u8
+}
+
+// Multiply an error in [0-255] by the gain, saturating at 255.
+fn amplify_error(error: f32, gain: f32) -> u8 {
+    (error * gain).min(255f32) as u8
+}
+
+// Compare the given channels of two 8-bit pixels.
+// Return whether the pixels are different, and the error of each channel remapped for the error image.
+// With a nonzero luma weight, the pixels are different if a blend of their largest channel difference and their luminance difference exceeds the threshold.
+fn compare_pixels(pixel1: &image::Rgba<u8>, pixel2: &image::Rgba<u8>, channels: &[usize], value_threshold: u8, luma_weight: f32) -> (bool, [u8; 4]) {
+    let mut is_pixel_different = false;
+    // For each pair of compared channels, compute the absolute difference and check it exceeds the value threshold
+    // If the difference if below the threshold, we snap it to 0.
+    let mut remapped = [0u8; 4];
+    if luma_weight > 0f32 {
+        let max_diff = channels.iter().map(|&channel| pixel1[channel].abs_diff(pixel2[channel])).max().unwrap_or(0) as f32;
+        let [luma1, luma2] = [pixel1, pixel2].map(|pixel| luma(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32));
+        let error = (1f32 - luma_weight) * max_diff + luma_weight * (luma1 - luma2).abs();
+        if error > value_threshold as f32 {
+            is_pixel_different = true;
+            for &channel in channels {
+                let diff = pixel1[channel].abs_diff(pixel2[channel]);
+                if diff > 0 { remapped[channel] = 128 | diff >> 1; }
+            }
+        }
+        return (is_pixel_different, remapped);
+    }
+    for &channel in channels {
+        let diff = pixel1[channel].abs_diff(pixel2[channel]);
+        if diff > value_threshold {
+            is_pixel_different = true; // A pair of pixels are mismatched if their difference exceed the threshold in any channel.
+            remapped[channel] = 128 | diff >> 1; // To make sure that any wrong pixel is visible in the error image, we remap the error from [0-255] to [128-255].
+        }
+    }
+    (is_pixel_different, remapped)
+}
+
+// Compare the given channels of two floating-point pixels (the luma weight is used as for 8-bit pixels).
+// Since the difference is unbounded, it is tonemapped (using the Reinhard operator) to [0-255] before remapping it for the error image.
+fn compare_float_pixels(pixel1: &image::Rgba<f32>, pixel2: &image::Rgba<f32>, channels: &[usize], value_threshold: f32, luma_weight: f32) -> (bool, [u8; 4]) {
+    let mut is_pixel_different = false;
+    let mut remapped = [0u8; 4];
+    let remap = |diff: f32| 128 | ((255f32 * diff / (1f32 + diff)) as u8) >> 1;
+    if luma_weight > 0f32 {
+        let max_diff = channels.iter().map(|&channel| (pixel1[channel] - pixel2[channel]).abs()).fold(0f32, f32::max);
+        let [luma1, luma2] = [pixel1, pixel2].map(|pixel| luma(pixel[0], pixel[1], pixel[2]));
+        let error = (1f32 - luma_weight) * max_diff + luma_weight * (luma1 - luma2).abs();
+        if error > value_threshold {
+            is_pixel_different = true;
+            for &channel in channels {
+                let diff = (pixel1[channel] - pixel2[channel]).abs();
+                if diff > 0f32 { remapped[channel] = remap(diff); }
+            }
+        }
+        return (is_pixel_different, remapped);
+    }
+    for &channel in channels {
+        let diff = (pixel1[channel] - pixel2[channel]).abs();
+        if diff > value_threshold {
+            is_pixel_different = true;
+            remapped[channel] = remap(diff);
+        }
+    }
+    (is_pixel_different, remapped)
+}
+
+// Check that the tolerance map (if any) has the same size as the images.
+fn check_tolerance_map(size: (u32, u32), options: &ComparisonOptions) -> anyhow::Result<()> {
+    match &options.tolerance_map {
+        Some(map) if map.dimensions() != size => Err(anyhow::Error::msg(format!(
+            "The tolerance map ({}x{}) must have the same size as the images ({}x{})", map.width(), map.height(), size.0, size.1))),
+        _ => Ok(()),
+    }
+}
+
+// Return the region of images of the given size that is compared (everything except the ignored border),
+// or an error if the border leaves nothing to compare.
+fn compared_region(size: (u32, u32), options: &ComparisonOptions) -> anyhow::Result<BoundingBox> {
+    let border = options.ignore_border;
+    if 2 * border as u64 >= size.0.min(size.1) as u64 {
+        return Err(anyhow::Error::msg(format!("The ignored border ({} pixels) must be less than half the smaller image dimension ({}x{})", border, size.0, size.1)));
+    }
+    Ok(BoundingBox { min: (border, border), max: (size.0 - 1 - border, size.1 - 1 - border) })
+}
+
+// Return the tiles whose wrong pixels are outside the range allowed by the tile error threshold (if the images were divided into tiles),
+// or the tiles with any wrong pixel if there is no tile error threshold.
+fn failed_tiles(tile_grid: Option<&TileGrid>, options: &ComparisonOptions, region: &BoundingBox) -> Vec<Tile> {
+    match (tile_grid, &options.tile) {
+        (Some(tile_grid), Some((_, tile_error))) => tile_grid.tiles().filter(|tile| {
+            // Only the part of the tile inside the compared region counts towards its ratio.
+            match (tile.bbox.intersection(region), tile_error) {
+                (Some(compared), Some(tile_error)) => !tile_error.accepts(tile.wrong_pixels, compared.size()),
+                (Some(_), None) => tile.wrong_pixels > 0,
+                (None, _) => false,
+            }
+        }).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Return true if any tile failed and the tiles decide the result (i.e. there is a tile error threshold).
+fn exceeds_tile_limit(failed_tiles: &[Tile], options: &ComparisonOptions) -> bool {
+    matches!(&options.tile, Some((_, Some(_)))) && !failed_tiles.is_empty()
+}
+
+// Return true if the percentile of the per-pixel errors exceeds the limit given by the options.
+fn exceeds_percentile_limit(percentile_error: Option<f32>, options: &ComparisonOptions) -> bool {
+    matches!((percentile_error, options.percentile), (Some(error), Some((_, limit))) if error > limit)
+}
+
+// Compare the two images using the given options.
+// Return the outcome of the comparison and the error image (None if the images have different sizes),
+// or an error if the options can't be applied to these images.
+pub fn compare(images: &ImagePair, options: &ComparisonOptions) -> anyhow::Result<(ComparisonOutcome, Option<image::DynamicImage>)> {
+    compare_with_progress(images, options, &mut |_| {})
+}
+
+// The number of columns compared between two progress reports.
+const PROGRESS_COLUMNS: u32 = 64;
+
+// Compare the two images like `compare`, reporting the progress through the pixels to the given function.
+// The progress is the completed fraction [0-1] of the pixels, which is reported every few columns and once the comparison is complete.
+pub fn compare_with_progress(images: &ImagePair, options: &ComparisonOptions, progress: &mut dyn FnMut(f32)) -> anyhow::Result<(ComparisonOutcome, Option<image::DynamicImage>)> {
+    // Get the image size and check that both images has the same size.
+    let size = {
+        let [size1, size2] = images.dimensions();
+        if size1 != size2 {
+            return Ok((ComparisonOutcome::size_mismatch([size1, size2]), None));
+        }
+        size1
+    };
+
+    check_tolerance_map(size, options)?;
+
+    // Align the images then compare the region where they overlap (the tolerance map follows the first image).
+    if let Some(radius) = options.align {
+        let offset = align::estimate_offset(images, radius);
+        let aligned = align::crop_to_overlap(images, offset);
+        let tolerance_map = options.tolerance_map.as_ref().map(|map| align::crop_first_to_overlap(map, offset));
+        let (mut outcome, error_img) = compare_with_progress(&aligned, &ComparisonOptions { align: None, tolerance_map, ..options.clone() }, progress)?;
+        outcome.alignment_offset = Some(offset);
+        return Ok((outcome, error_img));
+    }
+
+    // Normalize the brightness and contrast of the second image, then compare the normalized images.
+    if options.normalize {
+        let (normalized, transform) = normalize::normalize(images);
+        let (mut outcome, error_img) = compare_with_progress(&normalized, &ComparisonOptions { normalize: false, ..options.clone() }, progress)?;
+        outcome.normalization = Some(transform);
+        return Ok((outcome, error_img));
+    }
+
+    // Compare the levels of the pyramid from the coarsest one, and stop at the first one that fails.
+    // The progress of each level is weighted by its number of pixels.
+    if let Some(levels) = options.pyramid {
+        if options.perceptual_hash.is_some() || options.sample.is_some() {
+            return Err(anyhow::Error::msg("The pyramid comparison can't be combined with a perceptual hash or sampling"));
+        }
+        // The ignored border is checked at the full resolution, since it is only clamped to the smaller levels.
+        compared_region(size, options)?;
+        let pyramid = pyramid::build(images, levels);
+        let pixel_count = |level: &ImagePair| { let (width, height) = level.dimensions()[0]; width as f32 * height as f32 };
+        let total = pyramid.iter().map(pixel_count).sum::<f32>();
+        let mut done = 0f32;
+        let mut compared = Vec::new();
+        let mut result = None;
+        for (index, level) in pyramid.iter().enumerate().rev() {
+            let level_size = level.dimensions()[0];
+            let level_options = pyramid::level_options(options, size, index as u32, level_size);
+            let count = pixel_count(level);
+            let (outcome, error_img) = compare_with_progress(level, &level_options, &mut |fraction| progress((done + fraction * count) / total))?;
+            done += count;
+            compared.push(pyramid::PyramidLevel {
+                level: index as u32,
+                size: level_size,
+                wrong_pixels: outcome.wrong_pixels,
+                total_pixels: outcome.total_pixels,
+                matched: outcome.matched,
+            });
+            let matched = outcome.matched;
+            result = Some((outcome, error_img));
+            if !matched {
+                break;
+            }
+        }
+        let (mut outcome, error_img) = result.ok_or(anyhow::Error::msg("The pyramid has no levels"))?;
+        let mismatch_level = compared.last().filter(|level| !level.matched).map(|level| level.level);
+        outcome.pyramid = Some(pyramid::PyramidOutcome { levels: compared, mismatch_level });
+        return Ok((outcome, error_img));
+    }
+
+    // A comparison of the perceptual hashes doesn't compare the pixels, so it has no error image.
+    if let Some((algorithm, threshold)) = options.perceptual_hash {
+        let hash = hash::compare_hashes(images, algorithm, threshold);
+        progress(1f32);
+        return Ok((ComparisonOutcome { matched: hash.matched(), hash: Some(hash), ..ComparisonOutcome::size_mismatch([size, size]) }, None));
+    }
+
+    // Compute the value threshold from the statistics of the per-pixel errors, then compare the images with it.
+    if let Some(z) = options.zscore_threshold {
+        if options.metric != Metric::Pixel || options.tolerance_map.is_some() || options.luma_weight > 0f32 {
+            return Err(anyhow::Error::msg("The z-score threshold requires the pixel metric, and can't be combined with a tolerance map or a luma weight"));
+        }
+        let channels = options.channels.as_deref().unwrap_or(&RGB_CHANNELS);
+        let zscore = zscore::zscore_threshold(images, &compared_region(size, options)?, channels, options.alpha_threshold, z);
+        let thresholds = ComparisonOptions {
+            value_threshold: (zscore.threshold * 255f32).clamp(0f32, 255f32) as u8,
+            float_value_threshold: zscore.threshold,
+            zscore_threshold: None,
+            ..options.clone()
+        };
+        let (mut outcome, error_img) = compare_with_progress(images, &thresholds, progress)?;
+        outcome.zscore = Some(zscore);
+        return Ok((outcome, error_img));
+    }
+
+    // The fft metric compares the frequency bins of the spectra instead of the pixels, so it has its own error image.
+    if options.metric == Metric::Fft {
+        let (outcome, error_img) = fft::compare_spectra(images, options)?;
+        progress(1f32);
+        return Ok((outcome, Some(error_img)));
+    }
+
+    // A sampled comparison only compares some of the pixels, so it has no error image.
+    if let Some(sampling) = &options.sample {
+        if let Some(outcome) = sample::compare_sampled(images, options, sampling)? {
+            progress(1f32);
+            return Ok((outcome, None));
+        }
+    }
+
+    // The region of the image that is compared (everything except the ignored border).
+    let border = options.ignore_border;
+    let region = compared_region(size, options)?;
+
+    // FLIP compares the colors as a whole, and the gradient metric compares the gradients of the luminance,
+    // so their error maps can't be restricted to some channels or matched against shifted pixels.
+    let error_map = if matches!(options.metric, Metric::Flip | Metric::Gradient) {
+        if options.channels.is_some() || options.shift_tolerance > 0 || options.luma_weight > 0f32 {
+            return Err(anyhow::Error::msg("The FLIP and gradient metrics can't be combined with selected channels, a shift tolerance or a luma weight"));
+        }
+        Some(match options.metric {
+            Metric::Flip => flip::flip_errors(images, flip::DEFAULT_PIXELS_PER_DEGREE),
+            _ => edges::gradient_errors(images),
+        })
+    } else {
+        None
+    };
+
+    // The edge comparison matches the edges of the images, so it ignores the colors and the pixel thresholds as well.
+    let edge_comparison = if options.metric == Metric::Edges {
+        if options.channels.is_some() || options.shift_tolerance > 0 || options.luma_weight > 0f32 || options.tolerance_map.is_some() || options.diff_only {
+            return Err(anyhow::Error::msg("The edges metric can't be combined with selected channels, a shift tolerance, a luma weight, a tolerance map or a diff-only error image"));
+        }
+        Some(edges::compare_edges(images))
+    } else {
+        None
+    };
+
+    let channels = options.channels.as_deref().unwrap_or(&RGB_CHANNELS);
+    let (min_wrong_pixels, max_wrong_pixels) = options.error_threshold.get_actual_range(region.size());
+    
+    // Allocate an image to store the error between the two images in each style (the diff style first, then the extra ones)
+    let styles: Vec<DiffStyle> = std::iter::once(options.diff_style).chain(options.extra_diff_styles.iter().copied()).collect();
+    let mut error_imgs = vec![image::RgbImage::new(size.0, size.1); styles.len()];
+    
+    let mut wrong_pixels: u32 = 0; // The number of pixels that differ by more than the value threshold
+    let mut bbox: Option<BoundingBox> = None; // The box enclosing all the wrong pixels (None if there are no wrong pixels)
+    let mut mask = vec![false; (size.0 * size.1) as usize]; // A row-major mask which is true for the wrong pixels
+    let mut errors = vec![0f32; if options.analyze_clusters() { mask.len() } else { 0 }]; // The row-major errors of the wrong pixels (only needed by the clusters)
+    let mut max_error: Option<(u32, u32, f32)> = None; // The position and error of the pixel with the largest error
+    let mut tile_grid = options.tile.as_ref().map(|(layout, _)| TileGrid::with_layout(size, *layout)).transpose()?; // The number of wrong pixels per tile
+    let mut tile_counts = options.tile_size.map(|tile_size| TileGrid::new(size, tile_size)); // The number of wrong pixels per tile of the tile size
+    let mut flip_sum = 0f64; // The sum of the FLIP errors of the compared pixels
+    let mut channel_sums = ChannelAccumulator::default(); // The statistics of the channel differences of the compared pixels
+    let mut error_histogram = options.error_histogram.then(|| vec![0u64; HISTOGRAM_BINS]); // The histogram of the largest channel differences (if requested)
+    let mut worst_pixels = options.explain.map(explain::WorstPixels::new); // The pixels with the largest channel differences (if requested)
+    // The errors of the compared pixels (only needed by the percentile), which are multiples of 1/255 with the pixel metric on 8-bit images.
+    let mut distribution = options.percentile.map(|_| ErrorDistribution::new(options.metric == Metric::Pixel && matches!(images, ImagePair::Integer(..))));
+
+    // The comparison can stop early only if the decision doesn't depend on the rest of the pixels.
+    let early_exit = options.early_exit && !options.analyze_clusters() && options.tile.is_none() && error_map.is_none() && edge_comparison.is_none()
+        && options.percentile.is_none() && !options.error_histogram && !options.suggest_thresholds && options.explain.is_none()
+        && !options.diff_mask && options.tile_size.is_none();
+
+    // Loop over all the pixels, compute the difference and populate the  error image
+    'pixels: for x in 0..size.0 {
+        if x.is_multiple_of(PROGRESS_COLUMNS) {
+            progress(x as f32 / size.0 as f32);
+        }
+        for y in 0..size.1 {
+
+            // The ignored border is drawn as a dimmed copy of the first image (or left black in a diff-only image).
+            if border > 0 && !region.contains(x, y) {
+                if !options.diff_only {
+                    let dimmed = images.dimmed_first_pixel(x, y, 2);
+                    error_imgs.iter_mut().for_each(|error_img| error_img.get_pixel_mut(x, y).0 = dimmed);
+                }
+                continue;
+            }
+
+            // The pixels that are nearly transparent in both images always match, and they are left black in the error image.
+            if options.alpha_threshold.is_some_and(|alpha_threshold| images.is_transparent(x, y, alpha_threshold)) {
+                continue;
+            }
+
+            let error = match (&error_map, &edge_comparison) {
+                (Some(error_map), _) => error_map[(y * size.0 + x) as usize],
+                (_, Some(edge_comparison)) => edge_comparison.error((y * size.0 + x) as usize),
+                _ => images.pixel_error(x, y, channels),
+            };
+            flip_sum += error as f64;
+            channel_sums.add(images.channel_errors(x, y));
+            if let Some(distribution) = &mut distribution { distribution.add(error); }
+            if error_histogram.is_some() || worst_pixels.is_some() {
+                // With FLIP, gradient or edges, the error isn't a channel difference.
+                let difference = if error_map.is_none() && edge_comparison.is_none() { error } else { images.pixel_error(x, y, channels) };
+                if let Some(histogram) = &mut error_histogram { histogram[stats::histogram_bin(difference)] += 1; }
+                if let Some(worst_pixels) = &mut worst_pixels { worst_pixels.add(x, y, None, difference, || images.pixel_values(x, y)); }
+            }
+            if error > max_error.map_or(0f32, |(_, _, max)| max) {
+                max_error = Some((x, y, error));
+            }
+
+            let thresholds = options.value_thresholds_at(x, y);
+            let (mut is_pixel_different, mut remapped) = match images {
+                _ if error_map.is_some() => (error > thresholds.1, [(error * 255f32) as u8; 4]),
+                _ if edge_comparison.is_some() => (error > options.edge_distance, [0; 4]),
+                ImagePair::Integer(img1, img2) => compare_pixels(img1.get_pixel(x, y), img2.get_pixel(x, y), channels, thresholds.0, options.luma_weight),
+                ImagePair::Float(img1, img2) => compare_float_pixels(img1.get_pixel(x, y), img2.get_pixel(x, y), channels, thresholds.1, options.luma_weight),
+            };
+
+            // With a shift tolerance, a different pixel is forgiven if it matches a nearby pixel in the other image in either direction.
+            if is_pixel_different && options.shift_tolerance > 0
+                && (images.has_nearby_match(x, y, false, thresholds, options)
+                    || images.has_nearby_match(x, y, true, thresholds, options)) {
+                is_pixel_different = false;
+                remapped = [0; 4];
+            }
+
+            // With a clamped output, the drawn errors are capped at the clamp value and stretched to the full intensity range,
+            // and with a gain, they are amplified instead. The raw diff style draws them without the remapping to [128-255].
+            let drawn_errors = |style: DiffStyle| {
+                if options.clamp_output.is_none() && options.diff_gain == 1f32 && style != DiffStyle::Raw {
+                    return remapped;
+                }
+                let scale = |error: f32| match options.clamp_output {
+                    Some(clamp) => stretch_error(error, clamp),
+                    None => amplify_error(error, options.diff_gain),
+                };
+                match error_map {
+                    Some(_) => [scale(error * 255f32); 4],
+                    None => images.scaled_errors(x, y, remapped, scale, style != DiffStyle::Raw),
+                }
+            };
+            
+            // When specific channels are selected, the error image is grayscale and shows the maximum error among them.
+            // The matching pixels are drawn using the error background, except with FLIP and gradient where the error map is drawn as is.
+            // With the edges metric, the error image shows the edges of both images (see `render::edge_color`).
+            // In the colorblind-safe style, the maximum error is mapped to a Viridis color instead.
+            // A diff-only image has the raw differences of the different pixels (or their FLIP or gradient error) instead, and the rest is black.
+            for (&style, error_img) in styles.iter().zip(error_imgs.iter_mut()) {
+                let remapped = drawn_errors(style);
+                let colorblind_safe = style == DiffStyle::ColorblindSafe;
+                error_img.get_pixel_mut(x, y).0 = if options.diff_only {
+                    match (is_pixel_different, &error_map) {
+                        (false, _) => [0; 3],
+                        (true, Some(_)) => [raw_error(error); 3],
+                        (true, None) => images.raw_differences(x, y, options.channels.as_deref()),
+                    }
+                } else if error_map.is_some() {
+                    if colorblind_safe { colormap::viridis(remapped[0]) } else { [remapped[0]; 3] }
+                } else if let Some(edge_comparison) = &edge_comparison {
+                    render::edge_color(edge_comparison, (y * size.0 + x) as usize, options.edge_distance, colorblind_safe)
+                } else if !is_pixel_different {
+                    images.background_pixel(x, y, options.error_background)
+                } else if colorblind_safe {
+                    colormap::viridis(remapped.iter().copied().max().unwrap_or(0))
+                } else if options.channels.is_some() {
+                    [remapped.iter().copied().max().unwrap_or(0); 3]
+                } else {
+                    [remapped[0], remapped[1], remapped[2]]
+                };
+            }
+            
+            if is_pixel_different {
+                wrong_pixels += 1;
+                mask[(y * size.0 + x) as usize] = true;
+                if !errors.is_empty() { errors[(y * size.0 + x) as usize] = error; }
+                if let Some(tile_grid) = &mut tile_grid { tile_grid.add(x, y); }
+                if let Some(tile_counts) = &mut tile_counts { tile_counts.add(x, y); }
+                match &mut bbox {
+                    Some(bbox) => bbox.include(x, y),
+                    None => bbox = Some(BoundingBox::new(x, y)),
+                }
+                if early_exit && wrong_pixels > max_wrong_pixels {
+                    break 'pixels;
+                }
+            }
+        }
+    }
+    progress(1f32);
+
+    // Group the wrong pixels into connected regions if requested.
+    let mut clusters = None;
+    let mut oversized_cluster = false; // True if any cluster exceeds the maximum cluster size
+    if options.analyze_clusters() {
+        // Discard the small clusters from the mask, the tiles and the error image (except the FLIP, gradient and edge maps, which are drawn whole).
+        if let Some(min_size) = options.min_cluster_size {
+            for index in clusters::discard_small_clusters(&mut mask, size, options.connectivity, min_size) {
+                let (x, y) = (index as u32 % size.0, index as u32 / size.0);
+                let background = if options.diff_only { Some([0; 3]) } else if error_map.is_none() && edge_comparison.is_none() {
+                    Some(images.background_pixel(x, y, options.error_background))
+                } else {
+                    None
+                };
+                if let Some(background) = background {
+                    error_imgs.iter_mut().for_each(|error_img| error_img.get_pixel_mut(x, y).0 = background);
+                }
+                if let Some(tile_grid) = &mut tile_grid { tile_grid.remove(x, y); }
+                if let Some(tile_counts) = &mut tile_counts { tile_counts.remove(x, y); }
+            }
+        }
+        let found = clusters::find_clusters(&mask, &errors, size, options.connectivity);
+        // Recount the wrong pixels (and their bounding box) from the remaining clusters.
+        if options.min_cluster_size.is_some() {
+            wrong_pixels = found.iter().map(|cluster| cluster.pixel_count).sum();
+            bbox = found.iter().map(|cluster| cluster.bbox).reduce(|mut merged, bbox| { merged.merge(&bbox); merged });
+        }
+        if let Some(max_size) = options.max_cluster_size {
+            oversized_cluster = found.iter().any(|cluster| cluster.pixel_count > max_size);
+        }
+        clusters = Some(found);
+    }
+
+    let mut error_imgs = styles.iter().zip(error_imgs).map(|(&style, mut error_img)| {
+        if style == DiffStyle::Overlay && !options.diff_only {
+            error_img = render::render_overlay(&images.first_rgba8(), &mask, options.highlight_color);
+        }
+
+        if let (true, Some(tile_size)) = (options.draw_tile_grid, options.tile_size) {
+            tiles::draw_grid(&mut error_img, tile_size, TILE_GRID_COLOR);
+        }
+
+        if options.draw_bbox {
+            if let Some(bbox) = &bbox {
+                bbox.draw(&mut error_img, BBOX_COLOR);
+            }
+        }
+
+        if options.channels.is_some() && style != DiffStyle::ColorblindSafe {
+            image::DynamicImage::ImageLuma8(image::DynamicImage::ImageRgb8(error_img).to_luma8())
+        } else {
+            image::DynamicImage::ImageRgb8(error_img)
+        }
+    }).collect::<Vec<_>>();
+    let error_img = error_imgs.remove(0);
+
+    let failed_tiles = failed_tiles(tile_grid.as_ref(), options, &region);
+
+    let percentile_error = options.percentile.zip(distribution).and_then(|((percentile, _), distribution)| distribution.percentile(percentile));
+
+    // The images are considered different if the number of wrong pixels is outside the range allowed by the error threshold,
+    // or if any cluster of wrong pixels is larger than allowed, or if any tile has exceeded its threshold, or if the error percentile exceeds its limit.
+    let mismatch  = wrong_pixels < min_wrong_pixels || wrong_pixels > max_wrong_pixels || oversized_cluster || exceeds_tile_limit(&failed_tiles, options)
+        || exceeds_percentile_limit(percentile_error, options);
+
+    let total_pixels = region.size().0 * region.size().1;
+    let (mae, rmse) = channel_sums.errors();
+    let mut outcome = ComparisonOutcome {
+        matched: !mismatch,
+        sizes: [size, size],
+        wrong_pixels,
+        total_pixels,
+        allowed_wrong_pixels: (min_wrong_pixels, max_wrong_pixels),
+        bbox,
+        clusters,
+        oversized_cluster,
+        failed_tiles,
+        tiles: tile_counts.as_ref().map(|tile_counts| tile_counts.tiles().collect()),
+        max_error_pixel: max_error.map(|(x, y, _)| PixelError { x, y, channel_errors: images.channel_errors(x, y) }),
+        alignment_offset: None,
+        normalization: None,
+        pyramid: None,
+        zscore: None,
+        fft: None,
+        edges: edge_comparison.as_ref().map(edges::EdgeComparison::stats),
+        flip: (options.metric == Metric::Flip).then(|| flip::FlipStats {
+            mean: (flip_sum / total_pixels as f64) as f32,
+            max: max_error.map_or(0f32, |(_, _, max)| max),
+        }),
+        percentile_error,
+        channel_stats: Some(channel_sums.finish()),
+        error_histogram,
+        mae,
+        rmse,
+        byte_identical: false,
+        sampled: false,
+        animation: None,
+        suggestions: None,
+        sweep: None,
+        worst_pixels: worst_pixels.map(explain::WorstPixels::finish),
+        hash: None,
+        diff_mask: options.diff_mask.then_some(mask),
+        extra_error_images: error_imgs,
+    };
+    outcome.suggestions = stats::suggest_thresholds(&outcome, max_error.map_or(0f32, |(_, _, max)| max), options);
+    outcome.sweep = stats::sweep(&outcome, options);
+    Ok((outcome, Some(error_img)))
+}
+
+// Return the sum of the pixel counts, accumulated in 64 bits (so the frames of a long animation can't overflow it) and saturated to a u32.
+pub(crate) fn saturating_sum(counts: impl Iterator<Item = u32>) -> u32 {
+    u32::try_from(counts.map(u64::from).sum::<u64>()).unwrap_or(u32::MAX)
+}
+
+// Compare the frames of two animated images pairwise (each pair is given as an image pair) using the given options.
+// The outcome combines the outcomes of the frames: it is a match only if the images have the same number of frames and every frame matches.
+// Return the outcome and the error image of each frame, or an error if the options can't be applied to some frame.
+pub fn compare_frames(frames: &[ImagePair], frame_counts: [usize; 2], options: &ComparisonOptions) -> anyhow::Result<(ComparisonOutcome, Vec<Option<image::DynamicImage>>)> {
+    let (outcomes, error_imgs): (Vec<ComparisonOutcome>, Vec<Option<image::DynamicImage>>) =
+        frames.iter().map(|images| compare(images, options)).collect::<anyhow::Result<Vec<_>>>()?.into_iter().unzip();
+    let Some(first) = outcomes.first() else { return Err(anyhow::Error::msg("There are no frames to compare")); };
+
+    // The pixel with the largest error is the one whose largest channel error is the largest among the frames.
+    let largest_error = |pixel: &PixelError| pixel.channel_errors.iter().copied().fold(0f32, f32::max);
+    let max_error_pixel = outcomes.iter().filter_map(|outcome| outcome.max_error_pixel)
+        .reduce(|max, pixel| if largest_error(&pixel) > largest_error(&max) { pixel } else { max });
+
+    // The errors of the frames are weighted by their numbers of pixels.
+    let total_pixels: u64 = outcomes.iter().map(|outcome| outcome.total_pixels as u64).sum();
+    let weighted_mean = |value: &dyn Fn(&ComparisonOutcome) -> f64| if total_pixels == 0 { 0f64 } else {
+        outcomes.iter().map(|outcome| value(outcome) * outcome.total_pixels as f64).sum::<f64>() / total_pixels as f64
+    };
+    let mut outcome = ComparisonOutcome {
+        matched: frame_counts[0] == frame_counts[1] && outcomes.iter().all(|outcome| outcome.matched),
+        sizes: first.sizes,
+        wrong_pixels: saturating_sum(outcomes.iter().map(|outcome| outcome.wrong_pixels)),
+        total_pixels: u32::try_from(total_pixels).unwrap_or(u32::MAX),
+        allowed_wrong_pixels: first.allowed_wrong_pixels,
+        bbox: outcomes.iter().filter_map(|outcome| outcome.bbox).reduce(|mut merged, bbox| { merged.merge(&bbox); merged }),
+        clusters: None,
+        oversized_cluster: outcomes.iter().any(|outcome| outcome.oversized_cluster),
+        failed_tiles: Vec::new(),
+        tiles: tiles::sum_frame_tiles(outcomes.iter().map(|outcome| outcome.tiles.as_deref())),
+        max_error_pixel,
+        alignment_offset: None,
+        normalization: None,
+        pyramid: None,
+        zscore: None,
+        flip: None,
+        fft: None,
+        edges: None,
+        percentile_error: None,
+        channel_stats: None,
+        // The histograms of the frames are summed.
+        error_histogram: outcomes.iter().map(|outcome| outcome.error_histogram.as_deref()).collect::<Option<Vec<&[u64]>>>()
+            .map(|histograms| histograms.iter().fold(vec![0u64; HISTOGRAM_BINS], |mut sum, histogram| { stats::add_histogram(&mut sum, histogram); sum })),
+        mae: weighted_mean(&|outcome| outcome.mae),
+        rmse: weighted_mean(&|outcome| outcome.rmse * outcome.rmse).sqrt(),
+        // The hashes are those of the frames that differ the most.
+        hash: outcomes.iter().filter_map(|outcome| outcome.hash).max_by_key(|hash| hash.distance),
+        byte_identical: false,
+        sampled: outcomes.iter().any(|outcome| outcome.sampled),
+        animation: Some(AnimationOutcome { frame_counts, frames: outcomes }),
+        suggestions: None,
+        sweep: None,
+        worst_pixels: None,
+        diff_mask: None,
+        extra_error_images: Vec::new(),
+    };
+    // The thresholds must let every frame pass, including those that already match.
+    outcome.suggestions = outcome.animation.as_ref().and_then(|animation| animation.frames.iter().filter_map(|frame| frame.suggestions).reduce(stats::Suggestions::merge));
+    outcome.sweep = stats::sweep(&outcome, options);
+    outcome.worst_pixels = options.explain.zip(outcome.animation.as_ref()).and_then(|(count, animation)|
+        explain::worst_frame_pixels(animation.frames.iter().map(|frame| frame.worst_pixels.as_deref()), count));
+    Ok((outcome, error_imgs))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::{BoundingBox, ComparisonOptions, ComparisonOutcome, ImagePair, Threshold, compare, saturating_sum};
+
+    // Return a black image and a copy of it where the given pixels are white.
+    fn images_with_changed_pixels(changed: &[(u32, u32)]) -> ImagePair {
+        let first = RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 255]));
+        let mut second = first.clone();
+        for &(x, y) in changed {
+            second.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+        ImagePair::new(DynamicImage::ImageRgba8(first), DynamicImage::ImageRgba8(second))
+    }
+
+    // Return the bounding box of the comparison of the images with the given changed pixels.
+    fn bbox(changed: &[(u32, u32)]) -> Option<BoundingBox> {
+        compare(&images_with_changed_pixels(changed), &ComparisonOptions::default()).unwrap().0.bbox
+    }
+
+    #[test]
+    fn bbox_of_identical_images() {
+        assert_eq!(bbox(&[]), None);
+    }
+
+    #[test]
+    fn bbox_of_one_different_pixel() {
+        let bbox = bbox(&[(5, 9)]).unwrap();
+        assert_eq!(bbox, BoundingBox { min: (5, 9), max: (5, 9) });
+        assert_eq!(bbox.size(), (1, 1));
+    }
+
+    #[test]
+    fn bbox_of_two_separated_pixels() {
+        let bbox = bbox(&[(12, 2), (3, 14)]).unwrap();
+        assert_eq!(bbox, BoundingBox { min: (3, 2), max: (12, 14) });
+        assert_eq!(bbox.size(), (10, 13));
+    }
+
+    #[test]
+    fn threshold_ranges_parse() {
+        let parse = |value: &str| Threshold::try_from(value).unwrap().to_string();
+        assert_eq!(parse("5..10"), "5..10");
+        assert_eq!(parse("10..10"), "10..10");
+        assert_eq!(parse("5%..50%"), "5%..50%");
+        assert_eq!(parse("..10"), "..10");
+        // Bounds of different kinds depend on the image size, so they aren't compared.
+        assert_eq!(parse("50..1%"), "50..1%");
+    }
+
+    #[test]
+    fn inverted_threshold_ranges_are_rejected() {
+        assert!(Threshold::try_from("50..10").is_err());
+        assert!(Threshold::try_from("10%..5%").is_err());
+    }
+
+    #[test]
+    fn wrong_percentage_of_many_pixels() {
+        // 100 times the wrong pixels doesn't fit in a u32.
+        let outcome = ComparisonOutcome { wrong_pixels: 50_000_000, total_pixels: 100_000_000, ..ComparisonOutcome::size_mismatch([(10_000, 10_000); 2]) };
+        assert_eq!(outcome.wrong_percentage(), 50f32);
+    }
+
+    #[test]
+    fn saturating_sum_of_many_frames() {
+        assert_eq!(saturating_sum([1u32, 2, 3].into_iter()), 6);
+        assert_eq!(saturating_sum([u32::MAX / 2 + 1; 3].into_iter()), u32::MAX);
+    }
+}
@@ -1,42 +1,207 @@
-use std::{process::ExitCode, io::Write};
+use std::{ffi::OsString, fs, path::Path, process::ExitCode, time::Duration, io::{BufRead, Cursor, IsTerminal, Read, Seek, Write}};
 
-use clap::{ArgAction, ArgMatches, arg, value_parser, command};
-use image;
+use clap::{ArgAction, ArgGroup, ArgMatches, arg, value_parser, command};
 use anyhow::{self, Context};
+use image::{AnimationDecoder, DynamicImage, GenericImageView, imageops::FilterType, Frames, ImageBuffer, ImageFormat, codecs::{gif::GifDecoder, jpeg::JpegEncoder, png::PngDecoder, webp::{WebPEncoder, WebPQuality}}, io::Reader};
 use itertools::Itertools;
 
-// This enum defines a threshold either as:
-// - An absolute integer value (e.g. the number of pixels in the image)
-// - A ratio value (e.g. the percentage of pixels in the image)
-#[derive(Clone, Copy)]
-enum Threshold {
-    Absolute(u32),
-    Ratio(f32),
+use imgcmp::{ComparisonOptions, ComparisonOutcome, ImagePair, Metric, Threshold, parse_channel, clusters::Connectivity, cvd, hash::HashAlgorithm, render::{self, DiffStyle, ErrorBackground}, rows::RowComparison, sample::{SampleSize, Sampling}, tiles::{Tile, TileLayout}};
+
+mod baseline;
+mod batch;
+mod benchmark;
+mod color;
+mod config;
+mod csv;
+mod dump;
+mod gha;
+mod html;
+mod http;
+mod json;
+mod junit;
+mod manifest;
+mod metadata;
+mod output_manifest;
+mod progress;
+mod server;
+mod stream;
+mod watch;
+
+// The exit codes of the tool.
+const EXIT_MATCH: u8 = 0;           // The images match.
+const EXIT_MISMATCH: u8 = 1;        // The images have different pixels.
+const EXIT_SIZE_MISMATCH: u8 = 2;   // The images have different sizes.
+const EXIT_IO_ERROR: u8 = 3;        // An image couldn't be read, decoded or written (or a report couldn't be written).
+const EXIT_INVALID_ARGUMENTS: u8 = 4; // The arguments are invalid (or can't be applied to the given images).
+const EXIT_MISSING_BASELINE: u8 = 5; // The first image (the baseline) is missing (with --missing-baseline fail).
+
+// The final status of the command, which decides its exit code.
+// The statuses are ordered by severity, and a batch run ends with the most severe status of its comparisons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Match,
+    Mismatch,
+    SizeMismatch,
+    MissingBaseline,
+    IoError,
+    InvalidArguments,
 }
 
-impl Threshold {
-    // Given the image size, return the threshold value in number of pixels. 
-    fn get_actual_threshold(&self, image_size: (u32, u32)) -> u32 {
+impl Status {
+    // Return the exit code corresponding to the status.
+    fn exit_code(self) -> u8 {
+        match self {
+            Status::Match => EXIT_MATCH,
+            Status::Mismatch => EXIT_MISMATCH,
+            Status::SizeMismatch => EXIT_SIZE_MISMATCH,
+            Status::MissingBaseline => EXIT_MISSING_BASELINE,
+            Status::IoError => EXIT_IO_ERROR,
+            Status::InvalidArguments => EXIT_INVALID_ARGUMENTS,
+        }
+    }
+
+    // Return the label of the status as printed in the summary of a batch run.
+    fn label(self) -> &'static str {
+        match self {
+            Status::Match => "MATCH",
+            Status::Mismatch => "MISMATCH",
+            Status::SizeMismatch => "SIZE MISMATCH",
+            Status::MissingBaseline => "MISSING BASELINE",
+            Status::IoError | Status::InvalidArguments => "ERROR",
+        }
+    }
+
+    // Return the color of the label of the status.
+    fn color(self) -> color::Color {
+        match self {
+            Status::Match => color::Color::Green,
+            Status::SizeMismatch | Status::MissingBaseline => color::Color::Yellow,
+            Status::Mismatch | Status::IoError | Status::InvalidArguments => color::Color::Red,
+        }
+    }
+
+    // Return the name of the status as written in the JSON report.
+    fn name(self) -> &'static str {
         match self {
-            Threshold::Absolute(value) => *value,
-            Threshold::Ratio(ratio) => (ratio * (image_size.0 * image_size.1) as f32) as u32,
+            Status::Match => "match",
+            Status::Mismatch => "mismatch",
+            Status::SizeMismatch => "size_mismatch",
+            Status::MissingBaseline => "missing_baseline",
+            Status::IoError => "io_error",
+            Status::InvalidArguments => "invalid_arguments",
+        }
+    }
+}
+
+impl From<&anyhow::Result<ComparisonOutcome>> for Status {
+    // Classify the result of the comparison.
+    // An error caused by an I/O or image error is an I/O error, any other error comes from the arguments.
+    fn from(result: &anyhow::Result<ComparisonOutcome>) -> Self {
+        match result {
+            Ok(outcome) if outcome.is_size_mismatch() => Status::SizeMismatch,
+            Ok(outcome) if outcome.matched => Status::Match,
+            Ok(_) => Status::Mismatch,
+            Err(err) if err.is::<MissingBaseline>() => Status::MissingBaseline,
+            Err(err) if err.chain().any(|cause| cause.is::<std::io::Error>() || cause.is::<image::ImageError>()) => Status::IoError,
+            Err(_) => Status::InvalidArguments,
+        }
+    }
+}
+
+impl From<&batch::Comparison> for Status {
+    // Classify a comparison. A comparison whose baseline was updated is considered a match.
+    fn from(comparison: &batch::Comparison) -> Self {
+        if comparison.updated { Status::Match } else { Status::from(&comparison.result) }
+    }
+}
+
+// The error of a comparison whose baseline is missing (with --missing-baseline fail), which is reported as a mismatch.
+#[derive(Debug)]
+struct MissingBaseline(String);
+
+impl std::fmt::Display for MissingBaseline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The baseline {} is missing", self.0)
+    }
+}
+
+impl std::error::Error for MissingBaseline {}
+
+// How a missing first image (the baseline) is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MissingBaselineMode {
+    Error,  // It is an I/O error like any unreadable image.
+    Fail,   // It is reported as a missing baseline, which has its own exit code.
+    Create, // The second image is copied into place, and the pair is considered a match.
+}
+
+impl TryFrom<&str> for MissingBaselineMode {
+    type Error = anyhow::Error;
+    // Try to parse a string into a missing baseline mode.
+    fn try_from(value: &str) -> Result<Self, anyhow::Error> {
+        match value {
+            "error" => Ok(MissingBaselineMode::Error),
+            "fail" => Ok(MissingBaselineMode::Fail),
+            "create" => Ok(MissingBaselineMode::Create),
+            _ => Err(anyhow::Error::msg(format!("Invalid missing baseline mode \"{}\" (expected error, fail or create)", value))),
         }
     }
 }
 
-impl TryFrom<&str> for Threshold {
+// The format of the report written with --report-file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    Text,   // The outcomes as printed in verbose mode.
+    Json,   // The same report as --json.
+}
+
+impl TryFrom<&str> for ReportFormat {
     type Error = anyhow::Error;
-    // Try to parse a string into a threshold.
-    // If the string ends with "%", it is a ratio. Otherwise, it is an absolute value.
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if value.ends_with("%") {
-            Ok(Threshold::Ratio(value[0..value.len()-1].parse::<f32>()? / 100f32))
-        } else {
-            Ok(Threshold::Absolute(value.parse::<u32>()?))
+    // Try to parse a string into a report format.
+    fn try_from(value: &str) -> Result<Self, anyhow::Error> {
+        match value {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(anyhow::Error::msg(format!("Invalid report format \"{}\" (expected text or json)", value))),
+        }
+    }
+}
+
+// How images with different sizes are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SizeMismatchPolicy {
+    Error,          // It is an error, which is reported like invalid arguments.
+    Fail,           // It is reported as a size mismatch, which has its own exit code.
+    ResizeFirst,    // The first image is resized to the size of the second one before comparing them.
+    ResizeSecond,   // The second image is resized to the size of the first one before comparing them.
+    ResizeSmallest, // The larger image (by area) is shrunk to the size of the smaller one before comparing them.
+    ResizeLargest,  // The smaller image (by area) is enlarged to the size of the larger one before comparing them.
+}
+
+impl TryFrom<&str> for SizeMismatchPolicy {
+    type Error = anyhow::Error;
+    // Try to parse a string into a size mismatch policy.
+    fn try_from(value: &str) -> Result<Self, anyhow::Error> {
+        match value {
+            "error" => Ok(SizeMismatchPolicy::Error),
+            "fail" => Ok(SizeMismatchPolicy::Fail),
+            "resize-first" => Ok(SizeMismatchPolicy::ResizeFirst),
+            "resize-second" => Ok(SizeMismatchPolicy::ResizeSecond),
+            "resize-smallest" => Ok(SizeMismatchPolicy::ResizeSmallest),
+            "resize-largest" => Ok(SizeMismatchPolicy::ResizeLargest),
+            _ => Err(anyhow::Error::msg(format!(
+                "Invalid size mismatch policy \"{}\" (expected error, fail, resize-first, resize-second, resize-smallest or resize-largest)", value))),
         }
     }
 }
 
+// When the first image (the baseline) is replaced by the second one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpdateMode {
+    MismatchOrMissing,  // The baseline is updated if the images don't match or the baseline is missing.
+    MissingOnly,        // The baseline is only created if it is missing.
+}
+
 // A type used to specify the level of verbosity (higher value -> more verbose).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct Verbosity(i32);
@@ -47,164 +212,1996 @@ impl Verbosity {
     pub const VERBOSE: Verbosity = Verbosity(2);    // Print extra information.
 }
 
-// The comparison options
+// The pages of multi-page TIFF images that are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pages {
+    One(usize), // Only the page with this index (starting from 0).
+    All,        // All the pages, which are compared pairwise like the frames of animated images.
+}
+
+impl TryFrom<&str> for Pages {
+    type Error = anyhow::Error;
+    // Try to parse a string into the pages to compare: "all" or the index of a page.
+    fn try_from(value: &str) -> Result<Self, anyhow::Error> {
+        match value {
+            "all" => Ok(Pages::All),
+            _ => value.parse::<usize>().map(Pages::One)
+                .map_err(|_| anyhow::Error::msg(format!("Invalid pages \"{}\" (expected all or a page index)", value))),
+        }
+    }
+}
+
+// The command options
+#[derive(Clone)]
 struct Options {
-    value_threshold: u8,        // A threshold [0-1] on the maximum allowed per-channel error.
-                                // if 0, any difference passes the threshold. if 1, nothing passes the threshold.
-    error_threshold: Threshold, // The number (or percentage) of pixels allowed to be different before the result is considered a mismatch.
-    output: Option<String>,     // The path to the pixel error image.
+    comparison: ComparisonOptions, // The options used to compare the images.
+    output: Option<String>,     // The path to the pixel error image (or the directory of the error images in batch mode).
+    extra_outputs: Vec<(DiffStyle, String)>, // The paths of the error images in other diff styles, which are drawn in the same pass.
+    output_on_mismatch_only: bool, // If true, the error image is only saved if the images don't match.
+    png_metadata: bool,         // If true, the statistics of the comparison are embedded in the error image if it is a PNG (unless --no-metadata is given).
+    output_format: Option<ImageFormat>, // The format of the error image (inferred from the extension of its path if not set).
+    png_compression: Option<png::Compression>, // The compression of PNG error images (the default one if not set).
+    jpeg_quality: Option<u8>,   // The quality [1-100] of JPEG error images (the default of the encoder if not set).
     verbosity: Verbosity,       // The level of verbosity of the comparison operation.
+    list_clusters: bool,        // If true, the bounding box and pixel count of every cluster is printed.
+    region_report: Option<usize>, // If set, the N largest clusters are reported as regions.
+    max_error_pixel: bool,      // If true, the position and error of the pixel with the largest error is printed.
+    junit_report: Option<String>, // The path to the JUnit XML report.
+    html_report: Option<(String, Option<u32>)>, // The path to the HTML report and the largest dimension of its embedded images (if they are downscaled).
+    json_report: Option<String>, // The path to the JSON report.
+    output_manifest: Option<(String, output_manifest::Format)>, // The path to the output manifest of a batch run and its format.
+    csv_report: Option<(String, bool)>, // The path to the CSV report of a batch run, and whether its rows are appended to an existing file.
+    report_file: Option<(String, ReportFormat)>, // The path to the detailed report and its format.
+    max_pixels: Option<u64>,    // If set, the images with more pixels are refused (unlimited otherwise).
+    github_actions: bool,       // If true, failures are reported as GitHub Actions annotations and in the job summary.
+    frame: Option<usize>,       // The index of the frame to compare in animated images (if not set, all the frames are compared).
+    pages: Pages,               // The pages to compare in multi-page TIFF images.
+    allow_page_count_mismatch: bool, // If true, only the pages that exist in both images are compared when all pages are compared.
+    preview: bool,              // If true, a text preview of the error image is printed.
+    progress: bool,             // If true, a progress bar is drawn on stderr during the comparisons.
+    fast_path: bool,            // If true, byte-identical files match without being decoded (unless the error image is needed).
+    streaming: bool,            // If true, TIFF images are compared band by band as they are decoded when no error image is needed.
+    glob: bool,                 // If true, the image paths are glob patterns whose matches are compared in pairs.
+    allow_unmatched: bool,      // If true, the files matched by only one glob pattern are skipped instead of failing the run.
+    benchmark: Option<u32>,     // If set, the comparison is timed over this number of runs before the actual run.
+    watch: bool,                // If true, the comparison is run again whenever the images (or the tolerance map) change, until Ctrl-C.
+    manifest: Option<String>,   // The path to a manifest listing the pairs of images to compare (instead of the image paths).
+    pairs_file: Option<String>, // The path to a file listing the pairs of image paths to compare (instead of the image paths).
+    summary_only: bool,         // If true, only the final tally and the failed pairs of a batch run are printed.
+    fail_fast: bool,            // If true, a batch run stops after the first pair that doesn't match.
+    update: Option<UpdateMode>, // If set, the baseline is replaced by the second image according to this mode.
+    missing_baseline: MissingBaselineMode, // How a missing baseline is handled (--update always creates it).
+    on_size_mismatch: SizeMismatchPolicy, // How images with different sizes are handled.
+    stdin_format: Option<ImageFormat>, // The format of the image read from stdin (guessed from its content if not set).
+    http_timeout: Duration,     // The timeout of each request made to read an image from an HTTP(S) URL.
+    timeout: Option<Duration>,  // If set, the comparison of each pair (from decoding to writing the error image) fails if it takes longer (see `run_with_timeout`).
+    expect_mismatch: bool,      // If true, the exit code of a match and a mismatch are swapped (e.g. for negative tests).
+    invert_result: bool,        // If true, the exit codes are swapped like with expect_mismatch, and the printed result says whether the expected difference was found.
+    color: bool,                // If true, the results printed on stdout are colored.
+    porcelain: bool,            // If true, the outcome is printed as a single tab-separated line instead (see `porcelain_line`).
+    server: bool,               // If true, the pairs are read from stdin and the outcomes written to stdout as JSON (see `server::serve`).
+    error_histogram_csv: Option<String>, // If set, the exact error histogram is written to this CSV file.
+    dump_diff_pixels: Option<(String, dump::Format)>, // If set, the different pixels are written to this file in this format (see `dump::write`).
+    dump_limit: Option<u64>,    // If set, at most this many different pixels are written to the dump.
+    unpremultiply: bool,        // If true, the images are assumed to have premultiplied alpha, which is divided out before comparing them.
+    simulate: Option<cvd::Deficiency>, // If set, both images are transformed to how they are seen with this color vision deficiency before comparing them.
+    mismatch_exit_code: Option<u8>, // If set, overrides the exit codes of pixel and size mismatches.
+    error_exit_code: Option<u8>,    // If set, overrides the exit codes of the errors that occur during the comparison.
+}
+
+impl Options {
+    // Return the exit code for the given status, taking the overridden exit codes into account.
+    fn exit_code(&self, status: Status) -> u8 {
+        let overridden = match status {
+            Status::Match => None,
+            Status::Mismatch | Status::SizeMismatch | Status::MissingBaseline => self.mismatch_exit_code,
+            Status::IoError | Status::InvalidArguments => self.error_exit_code,
+        };
+        overridden.unwrap_or(status.exit_code())
+    }
+
+    // Return the status that decides the exit code: with --expect-mismatch, a match and a mismatch are swapped, and the errors still fail.
+    fn expected_status(&self, status: Status) -> Status {
+        match (self.expect_mismatch, status) {
+            (true, Status::Match) => Status::Mismatch,
+            (true, Status::Mismatch | Status::SizeMismatch) => Status::Match,
+            (_, status) => status,
+        }
+    }
+
+    // Return the label of the status, colored if the output is colored.
+    fn paint_status(&self, status: Status) -> String {
+        color::paint(status.label(), status.color(), self.color)
+    }
+
+    // Return true if the run compares many pairs of images (with --glob, --manifest or --pairs-file).
+    fn is_batch(&self) -> bool {
+        self.glob || self.manifest.is_some() || self.pairs_file.is_some()
+    }
+
+    // Return true if the error image is written to stdout (with -o -).
+    fn output_to_stdout(&self) -> bool {
+        self.output.as_deref() == Some(STDOUT_PATH)
+    }
+
+    // Return the stream of the human-readable output: stdout, or stderr if stdout carries the error image.
+    fn out(&self) -> Box<dyn Write> {
+        if self.output_to_stdout() { Box::new(std::io::stderr()) } else { Box::new(std::io::stdout()) }
+    }
+}
+
+// Try to extract the comparison options from the arguments
+fn parse_comparison_options(args: &ArgMatches) -> anyhow::Result<ComparisonOptions> {
+
+    let metric = *args.get_one::<Metric>("metric").ok_or(anyhow::Error::msg("Failed to parse metric"))?;
+    let edge_distance = *args.get_one::<f32>("edge-distance").unwrap_or(&1f32);
+    if edge_distance < 0f32 {
+        return Err(anyhow::Error::msg(format!("The edge distance ({}) must not be negative", edge_distance)));
+    }
+
+    let float_value_threshold = *args.get_one::<f32>("threshold").unwrap_or(&0.0f32);
+    let value_threshold = (float_value_threshold * 255f32) as u8;
+    
+    let luma_weight = *args.get_one::<f32>("luma-weight").unwrap_or(&0f32);
+    if !(0f32..=1f32).contains(&luma_weight) {
+        return Err(anyhow::Error::msg(format!("The luma weight ({}) must be in [0-1]", luma_weight)));
+    }
+
+    let tolerance_map = match args.get_one::<String>("tolerance-map") {
+        Some(path) => Some(image::open(path).context(format!("Failed to read {}", path))?.into_luma8()),
+        None => None,
+    };
+
+    let zscore_threshold = args.get_one::<f32>("zscore-threshold").copied();
+    if zscore_threshold.is_some_and(|z| !(z >= 0f32 && z.is_finite())) {
+        return Err(anyhow::Error::msg("The z-score threshold must not be negative"));
+    }
+
+    let error_threshold = args.get_one::<Threshold>("error").ok_or(anyhow::Error::msg("Failed to parse error threshold"))?.clone();
+    let percentile = args.get_one::<f32>("percentile").copied().zip(args.get_one::<f32>("percentile-error").copied());
+
+    // Without a seed, the positions are sampled differently on every run (the seed is printed in verbose output to reproduce one).
+    let sample_size = match (args.get_one::<u32>("sample"), args.get_one::<f32>("sample-rate")) {
+        (Some(&count), _) => Some(SampleSize::Count(count)),
+        (_, Some(&rate)) => Some(SampleSize::Rate(rate)),
+        _ => None,
+    };
+    // The sweep is computed from the error histogram.
+    let sweep = args.get_one::<Vec<f32>>("sweep").cloned();
+    let error_histogram = args.get_flag("error-histogram") || args.get_one::<String>("error-histogram-csv").is_some() || sweep.is_some();
+
+    let sample = sample_size.map(|size| Sampling { size, seed: args.get_one::<u64>("seed").copied().unwrap_or_else(random_seed) });
+
+    let draw_bbox = args.get_flag("draw-bbox");
+    let clamp_output = args.get_one::<u8>("clamp-output").copied();
+    let diff_gain = args.get_one::<f32>("diff-gain").copied().unwrap_or(1f32);
+    let tile_size = args.get_one::<(u32, u32)>("tile-size").copied();
+    let draw_tile_grid = args.get_flag("tile-grid-overlay");
+
+    let connectivity = *args.get_one::<Connectivity>("connectivity").ok_or(anyhow::Error::msg("Failed to parse connectivity"))?;
+    let find_clusters = args.get_flag("clusters");
+    let min_cluster_size = args.get_one::<u32>("min-cluster-size").copied();
+    let max_cluster_size = args.get_one::<u32>("max-cluster-size").copied();
+
+    let diff_style = *args.get_one::<DiffStyle>("diff-style").ok_or(anyhow::Error::msg("Failed to parse diff style"))?;
+    let tile_layout = match (args.get_one::<u32>("tile"), args.get_one::<(u32, u32)>("tiles")) {
+        (Some(&size), _) => Some(TileLayout::Size(size, size)),
+        (None, Some(&(columns, rows))) => Some(TileLayout::Grid(columns, rows)),
+        (None, None) => None,
+    };
+    let tile = tile_layout.map(|layout| (layout, args.get_one::<Threshold>("tile-error").cloned()));
+
+    let highlight_color = *args.get_one::<image::Rgb<u8>>("diff-highlight-color").ok_or(anyhow::Error::msg("Failed to parse highlight color"))?;
+
+    let error_background = *args.get_one::<ErrorBackground>("error-background").ok_or(anyhow::Error::msg("Failed to parse error background"))?;
+
+    let channels = args.get_many::<usize>("channel").map(|channels| channels.copied().unique().collect());
+
+    let shift_tolerance = *args.get_one::<u32>("shift-tolerance").unwrap_or(&0);
+
+    let ignore_border = *args.get_one::<u32>("ignore-border").unwrap_or(&0);
+
+    let alpha_threshold = args.get_one::<u8>("alpha-threshold").copied();
+
+    let align = if args.get_flag("align") { args.get_one::<u32>("align-window").copied() } else { None };
+
+    // The histogram only has the largest channel differences, which the other ways of comparing the pixels don't decide on.
+    if sweep.is_some() && (metric != Metric::Pixel || luma_weight > 0f32 || tolerance_map.is_some() || shift_tolerance > 0) {
+        return Err(anyhow::Error::msg("--sweep requires the pixel metric, without a luma weight, a tolerance map or a shift tolerance"));
+    }
+    
+    Ok(ComparisonOptions {
+        metric,
+        edge_distance,
+        value_threshold,
+        float_value_threshold,
+        zscore_threshold,
+        luma_weight,
+        tolerance_map,
+        error_threshold,
+        percentile,
+        error_histogram,
+        suggest_thresholds: args.get_flag("suggest-thresholds"),
+        sweep,
+        explain: args.get_one::<u32>("explain").map(|&count| count as usize),
+        perceptual_hash: args.get_flag("phash").then(|| (
+            *args.get_one::<HashAlgorithm>("phash-algorithm").expect("The hash algorithm has a default value"),
+            *args.get_one::<u32>("phash-threshold").expect("The hash threshold has a default value"))),
+        diff_mask: false, // Decided by the command options (see `Options::try_from`).
+        sample,
+        draw_bbox,
+        diff_only: args.get_flag("diff-only"),
+        clamp_output,
+        diff_gain,
+        tile_size,
+        draw_tile_grid,
+        connectivity,
+        find_clusters,
+        min_cluster_size,
+        max_cluster_size,
+        diff_style,
+        extra_diff_styles: Vec::new(), // Decided by the outputs (see `Options::try_from`).
+        highlight_color,
+        error_background,
+        tile,
+        channels,
+        shift_tolerance,
+        ignore_border,
+        alpha_threshold,
+        align,
+        normalize: args.get_flag("normalize"),
+        pyramid: args.get_one::<u32>("pyramid").copied(),
+        early_exit: false, // Decided by the command options (see `Options::try_from`).
+    })
 }
 
 impl TryFrom<&ArgMatches> for Options {
     type Error = anyhow::Error;
-    // Try to extract the comparison options from the arguments
+    // Try to extract the command options from the arguments
     fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
 
-        let value_threshold = (args.get_one::<f32>("threshold").unwrap_or(&0.0f32) * 255f32) as u8;
-        
-        let error_threshold = args.get_one::<Threshold>("error").ok_or(anyhow::Error::msg("Failed to parse error threshold"))?.clone();
+        let region_report = args.get_one::<usize>("region-report").copied();
 
-        let output = args.get_one::<String>("output").map(|s| s.clone());
+        // The region report needs the clusters.
+        let mut comparison = parse_comparison_options(args)?;
+        comparison.find_clusters |= region_report.is_some();
+
+        // The first error image is drawn in the diff style (unless it has its own), and the others must have theirs.
+        let outputs: Vec<(Option<DiffStyle>, String)> = args.get_many("output").into_iter().flatten().cloned().collect();
+        if comparison.diff_only && outputs.iter().any(|(style, _)| style.is_some()) {
+            return Err(anyhow::Error::msg("A diff-only error image can't have a style"));
+        }
+        let mut outputs = outputs.into_iter();
+        let output = outputs.next().map(|(style, path)| {
+            if let Some(style) = style { comparison.diff_style = style; }
+            path
+        });
+        let extra_outputs = outputs.map(|(style, path)| match style {
+            Some(style) => Ok((style, path)),
+            None => Err(anyhow::Error::msg(format!("The error image {} needs a style, given as STYLE=PATH (only the first --output can omit it)", path))),
+        }).collect::<anyhow::Result<Vec<_>>>()?;
+        comparison.extra_diff_styles = extra_outputs.iter().map(|(style, _)| *style).collect();
+        let output_on_mismatch_only = args.get_flag("output-on-mismatch-only");
+
+        let max_error_pixel = args.get_flag("max-error-pixel");
 
         let verbosity = 
             if args.get_flag("silent") { Verbosity::SILENT }
             else if args.get_flag("verbose") { Verbosity::VERBOSE }
             else { Verbosity::DEFAULT };
+
+        let list_clusters = args.get_flag("clusters");
+
+        let junit_report = args.get_one::<String>("report-junit").cloned();
+        let html_report = args.get_one::<String>("report-html").map(|path| (path.clone(), args.get_one::<u32>("report-max-dim").copied()));
+        let json_report = args.get_one::<String>("json").cloned();
+        // The report isn't written to stdout, which carries the human-readable output (or the error image), so - would be a file named -.
+        if json_report.as_deref() == Some(STDOUT_PATH) {
+            return Err(anyhow::Error::msg("The JSON report can't be written to stdout (--json -), give it the path of a file"));
+        }
+        // The format is inferred before the pairs are compared, so a wrong extension fails right away.
+        let output_manifest = args.get_one::<String>("output-manifest")
+            .map(|path| output_manifest::Format::from_path(path).map(|format| (path.clone(), format))).transpose()?;
+        let csv_report = args.get_one::<String>("report-csv").map(|path| (path.clone(), args.get_flag("report-append")));
+        let report_file = args.get_one::<String>("report-file").map(|path| (path.clone(), *args.get_one::<ReportFormat>("format").expect("The format has a default value")));
+
+        let dump_diff_pixels = args.get_one::<String>("dump-diff-pixels")
+            .map(|path| (path.clone(), *args.get_one::<dump::Format>("dump-format").expect("The dump format has a default value")));
+
+        let github_actions = args.get_flag("gha") || gha::is_github_actions();
+
+        let frame = args.get_one::<usize>("frame").copied();
+        let pages = match args.get_one::<Pages>("tiff-pages") {
+            Some(&pages) => pages,
+            None if args.get_flag("all-pages") => Pages::All,
+            None => Pages::One(*args.get_one::<usize>("page").unwrap_or(&0)),
+        };
+
+        let preview = args.get_flag("preview");
+
+        // The progress bar is drawn by default (--progress only makes it explicit), but never if stderr isn't a terminal or nothing else is printed there.
+        let progress = !args.get_flag("no-progress") && verbosity > Verbosity::SILENT && json_report.is_none() && std::io::stderr().is_terminal();
+
+        // The image paths are also treated as glob patterns if they both have wildcards and aren't existing files (e.g. on Windows, where the shell doesn't expand them).
+        let image_paths = image_path_args(args)?;
+        let glob = args.get_flag("glob") || (image_paths.len() == 2 && image_paths.iter().all(|path| {
+            batch::is_glob_pattern(path) && !http::is_url(path) && !Path::new(path).exists()
+        }));
+        let manifest = args.get_one::<String>("manifest").cloned();
+        let pairs_file = args.get_one::<String>("pairs-file").cloned();
+        let benchmark = args.get_one::<u32>("benchmark").copied();
+        let summary_only = args.get_flag("summary-only");
+        if summary_only && !glob && manifest.is_none() && pairs_file.is_none() {
+            return Err(anyhow::Error::msg("--summary-only requires a batch run (with --glob, --manifest or --pairs-file)"));
+        }
+        let fail_fast = args.get_flag("fail-fast");
+        if fail_fast && !glob && manifest.is_none() && pairs_file.is_none() {
+            return Err(anyhow::Error::msg("--fail-fast requires a batch run (with --glob, --manifest or --pairs-file)"));
+        }
+        // In a batch run, the output is the directory of the error images, so there can only be one.
+        if !extra_outputs.is_empty() && (glob || manifest.is_some() || pairs_file.is_some() || args.get_flag("server")) {
+            return Err(anyhow::Error::msg("Multiple --output paths can't be used in a batch run or in server mode"));
+        }
+        // The error image written to stdout is a PNG stream, so nothing else can be written there, and it isn't dumped on a terminal unless forced.
+        if output.as_deref() == Some(STDOUT_PATH) || extra_outputs.iter().any(|(_, path)| path == STDOUT_PATH) {
+            if output.as_deref() != Some(STDOUT_PATH) || !extra_outputs.is_empty() || glob || manifest.is_some() || pairs_file.is_some()
+                || args.get_flag("server") || args.get_flag("watch") || benchmark.is_some() || args.get_flag("porcelain") {
+                return Err(anyhow::Error::msg("--output - requires a single error image of a single comparison (not a batch run, server, watch, benchmark or porcelain mode)"));
+            }
+            if json_report.as_deref() == Some("/dev/stdout") {
+                return Err(anyhow::Error::msg("The JSON report can't be written to stdout along with the error image"));
+            }
+            if args.get_one::<ImageFormat>("output-format").is_some_and(|&format| format != ImageFormat::Png) {
+                return Err(anyhow::Error::msg("The error image is always written to stdout as a PNG"));
+            }
+            if std::io::stdout().is_terminal() && !args.get_flag("force") {
+                return Err(anyhow::Error::msg("Refusing to write the error image to a terminal (redirect stdout or use --force)"));
+            }
+        }
+
+        let stdin_format = args.get_one::<ImageFormat>("stdin-format").copied();
+        let http_timeout = Duration::from_secs(*args.get_one::<u64>("http-timeout").unwrap());
+
+        let update = match (args.get_flag("update"), args.get_flag("update-on-missing-only")) {
+            (false, _) => None,
+            (true, false) => Some(UpdateMode::MismatchOrMissing),
+            (true, true) => Some(UpdateMode::MissingOnly),
+        };
+        let missing_baseline = *args.get_one::<MissingBaselineMode>("missing-baseline").ok_or(anyhow::Error::msg("Failed to parse missing baseline mode"))?;
+
+        // An exit code of 0 would hide the failures, so it is only allowed for report-only workflows.
+        let mismatch_exit_code = args.get_one::<u8>("exit-code-on-mismatch").copied();
+        let error_exit_code = args.get_one::<u8>("exit-code-on-error").copied();
+        if !args.get_flag("allow-zero-exit") && (mismatch_exit_code == Some(0) || error_exit_code == Some(0)) {
+            return Err(anyhow::Error::msg("An exit code of 0 for mismatches or errors requires --allow-zero-exit"));
+        }
+
+        // The dump lists the wrong pixels from their mask, and the HTML report draws them without an error image.
+        comparison.diff_mask = dump_diff_pixels.is_some() || html_report.is_some();
+
+        // The comparison can stop at the first wrong pixel beyond the error threshold,
+        // unless the error image or any statistics (printed or reported) are needed.
+        comparison.early_exit = output.is_none() && verbosity != Verbosity::VERBOSE && !max_error_pixel && region_report.is_none() && !preview
+            && junit_report.is_none() && html_report.is_none() && json_report.is_none() && output_manifest.is_none() && csv_report.is_none() && report_file.is_none() && !github_actions && manifest.is_none() && pairs_file.is_none() && !args.get_flag("porcelain") && !args.get_flag("server");
         
         Ok(Options {
-            value_threshold,
-            error_threshold,
+            comparison,
             output,
+            extra_outputs,
+            output_on_mismatch_only,
+            png_metadata: !args.get_flag("no-metadata"),
+            output_format: args.get_one::<ImageFormat>("output-format").copied(),
+            png_compression: args.get_one::<png::Compression>("png-compression").copied(),
+            jpeg_quality: args.get_one::<u8>("jpeg-quality").copied(),
             verbosity,
+            list_clusters,
+            region_report,
+            max_error_pixel,
+            junit_report,
+            html_report,
+            json_report,
+            output_manifest,
+            csv_report,
+            report_file,
+            max_pixels: args.get_one::<u64>("max-pixels").copied(),
+            github_actions,
+            frame,
+            pages,
+            allow_page_count_mismatch: args.get_flag("allow-page-count-mismatch"),
+            preview,
+            progress,
+            fast_path: !args.get_flag("no-fast-path"),
+            streaming: !args.get_flag("no-streaming"),
+            glob,
+            allow_unmatched: args.get_flag("allow-unmatched"),
+            benchmark,
+            watch: args.get_flag("watch"),
+            manifest,
+            pairs_file,
+            summary_only,
+            fail_fast,
+            update,
+            missing_baseline,
+            on_size_mismatch: *args.get_one::<SizeMismatchPolicy>("on-size-mismatch").ok_or(anyhow::Error::msg("Failed to parse size mismatch policy"))?,
+            stdin_format,
+            http_timeout,
+            timeout: args.get_one::<u64>("timeout").map(|&seconds| Duration::from_secs(seconds)),
+            expect_mismatch: args.get_flag("expect-mismatch") || args.get_flag("invert-result"),
+            invert_result: args.get_flag("invert-result"),
+            porcelain: args.get_flag("porcelain"),
+            server: args.get_flag("server"),
+            error_histogram_csv: args.get_one::<String>("error-histogram-csv").cloned(),
+            dump_diff_pixels,
+            dump_limit: args.get_one::<u64>("dump-limit").copied(),
+            unpremultiply: args.get_flag("strip-alpha-premultiply"),
+            simulate: args.get_one::<cvd::Deficiency>("simulate").copied(),
+            color: args.get_one::<color::ColorMode>("color").ok_or(anyhow::Error::msg("Failed to parse color mode"))?.is_enabled(),
+            mismatch_exit_code,
+            error_exit_code,
         })
     }
 }
 
-// Run the comparison command for the given image paths, using the given options.
-// Return true if the images match and false otherwise.
-fn run(image_paths: [&String; 2], options: &Options) -> anyhow::Result<bool> {
-    // Read the two images and convert them to RGB (u8) Images.
-    let (img1, img2) = image_paths.iter()
-    .map(|&img_path| -> anyhow::Result<image::RgbImage> {
-        let reader = image::io::Reader::open(img_path).context(format!("Failed to read {}", img_path))?;
-        let image = reader.decode().context(format!("Failed to decode {}", img_path))?;
-        
-        Ok(image.to_rgb8())
-    }).collect_tuple().unwrap();
-    let (img1, img2) = (img1?, img2?);
-
-    // Get the image size and check that both images has the same size.
-    let size = {
-        let (size1, size2) = (img1.dimensions(), img2.dimensions());
-        if size1 != size2 {
-            if options.verbosity > Verbosity::SILENT {
-                println!("Images have different sizes (Got ({}x{}) and ({}x{})).", size1.0, size1.1, size2.0, size2.1);
+// Return the frame with the given index from the frames of an animated image, or all the frames if no index is given.
+fn select_frames(frames: Frames, index: Option<usize>, img_path: &str) -> anyhow::Result<Vec<DynamicImage>> {
+    let mut selected = Vec::new();
+    let mut count = 0;
+    for frame in frames {
+        let frame = frame.context(format!("Failed to decode {}", img_path))?;
+        match index {
+            Some(index) if count == index => return Ok(vec![DynamicImage::ImageRgba8(frame.into_buffer())]),
+            Some(_) => {},
+            None => selected.push(DynamicImage::ImageRgba8(frame.into_buffer())),
+        }
+        count += 1;
+    }
+    match index {
+        Some(index) => Err(anyhow::Error::msg(format!("Frame {} is out of range ({} has {} frames)", index, img_path, count))),
+        None if selected.is_empty() => Err(anyhow::Error::msg(format!("{} has no frames", img_path))),
+        None => Ok(selected),
+    }
+}
+
+// The number of bins of the histogram printed with --error-histogram (and written to the JSON report).
+const HISTOGRAM_BINS: usize = 16;
+
+// The path that reads an image from stdin.
+const STDIN_PATH: &str = "-";
+
+// The output path that writes the error image to stdout (as a PNG).
+const STDOUT_PATH: &str = "-";
+
+// The prefix of a second image that is a solid color (e.g. color:#1a1a1aff) instead of a path.
+const COLOR_PREFIX: &str = "color:";
+
+// Try to parse a percentile, which must be in [0-100].
+fn parse_percentile(value: &str) -> anyhow::Result<f32> {
+    let percentile = value.parse::<f32>()?;
+    if !(0f32..=100f32).contains(&percentile) {
+        return Err(anyhow::Error::msg(format!("Invalid percentile {} (expected a value in [0-100])", value)));
+    }
+    Ok(percentile)
+}
+
+// Try to parse a sample rate, which must be in (0-1].
+fn parse_sample_rate(value: &str) -> anyhow::Result<f32> {
+    let rate = value.parse::<f32>()?;
+    if !(rate > 0f32 && rate <= 1f32) {
+        return Err(anyhow::Error::msg(format!("Invalid sample rate {} (expected a value in (0-1])", value)));
+    }
+    Ok(rate)
+}
+
+// Try to parse the path of an error image, which can be prefixed by its diff style as STYLE=PATH
+// (where diff and heatmap are short for difference and colorblind-safe). A path whose prefix isn't a style is kept whole.
+fn parse_output(value: &str) -> anyhow::Result<(Option<DiffStyle>, String)> {
+    let styled = value.split_once('=').and_then(|(style, path)| {
+        let style = match style {
+            "diff" => Ok(DiffStyle::Difference),
+            "heatmap" => Ok(DiffStyle::ColorblindSafe),
+            style => DiffStyle::try_from(style),
+        };
+        style.ok().map(|style| (style, path))
+    });
+    match styled {
+        Some((_, "")) => Err(anyhow::Error::msg(format!("Invalid output \"{}\" (expected a path after the style)", value))),
+        Some((style, path)) => Ok((Some(style), path.to_string())),
+        None => Ok((None, value.to_string())),
+    }
+}
+
+// Try to parse the gain of the error image, which must be positive.
+fn parse_diff_gain(value: &str) -> anyhow::Result<f32> {
+    let gain = value.parse::<f32>()?;
+    if !(gain > 0f32 && gain.is_finite()) {
+        return Err(anyhow::Error::msg(format!("Invalid gain {} (expected a positive factor)", value)));
+    }
+    Ok(gain)
+}
+
+// Try to parse a tile size given as W,H, where both are at least 1.
+fn parse_tile_size(value: &str) -> anyhow::Result<(u32, u32)> {
+    let invalid = || anyhow::Error::msg(format!("Invalid tile size \"{}\" (expected W,H with both at least 1)", value));
+    let (width, height) = value.split_once(',').ok_or_else(invalid)?;
+    match (width.trim().parse::<u32>(), height.trim().parse::<u32>()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+// Try to parse a grid of tiles given as CxR, where both are at least 1.
+fn parse_tile_grid(value: &str) -> anyhow::Result<(u32, u32)> {
+    let invalid = || anyhow::Error::msg(format!("Invalid tile grid \"{}\" (expected CxR with both at least 1)", value));
+    let (columns, rows) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (columns.trim().parse::<u32>(), rows.trim().parse::<u32>()) {
+        (Ok(columns), Ok(rows)) if columns > 0 && rows > 0 => Ok((columns, rows)),
+        _ => Err(invalid()),
+    }
+}
+
+// The largest number of value thresholds in a sweep.
+const MAX_SWEEP_THRESHOLDS: usize = 1000;
+
+// Try to parse a sweep of value thresholds given as start:end:step, where 0 <= start <= end <= 1 and step > 0.
+// Return the thresholds from start to end (included) in steps.
+fn parse_sweep(value: &str) -> anyhow::Result<Vec<f32>> {
+    let invalid = || anyhow::Error::msg(format!("Invalid sweep \"{}\" (expected start:end:step with 0 <= start <= end <= 1 and step > 0)", value));
+    let bounds = value.split(':').map(|bound| bound.trim().parse::<f32>()).collect::<Result<Vec<f32>, _>>().map_err(|_| invalid())?;
+    let [start, end, step] = bounds[..] else { return Err(invalid()); };
+    if !(0f32 <= start && start <= end && end <= 1f32 && step > 0f32) {
+        return Err(invalid());
+    }
+    // The thresholds are computed from their index so the rounding errors don't add up, and a small tolerance keeps the end despite them.
+    let count = ((end - start) / step + 1e-4f32).floor() as usize + 1;
+    if count > MAX_SWEEP_THRESHOLDS {
+        return Err(anyhow::Error::msg(format!("The sweep \"{}\" has {} thresholds (at most {} are allowed)", value, count, MAX_SWEEP_THRESHOLDS)));
+    }
+    Ok((0..count).map(|index| (start + index as f32 * step).min(end)).collect())
+}
+
+// Return a random seed, taken from the random keys of the standard hash maps.
+fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new().build_hasher().finish()
+}
+
+// The options that name the environment variables the image paths can be read from, by the positional arguments they stand in for.
+const ENV_PATH_ARGS: [(&str, &str); 2] = [("first_image_path", "env-path1"), ("second_image_path", "env-path2")];
+
+// Return the image paths, each given as a positional argument or else read from the environment variable named by --env-path1 or --env-path2
+// (there are none with a manifest or in server mode). Return an error if a named environment variable isn't set.
+fn image_path_args(args: &ArgMatches) -> anyhow::Result<Vec<String>> {
+    let mut image_paths = Vec::new();
+    for (index, (positional, env_path)) in ENV_PATH_ARGS.into_iter().enumerate() {
+        if let Some(path) = args.get_one::<String>(positional) {
+            image_paths.push(path.clone());
+        } else if let Some(name) = args.get_one::<String>(env_path) {
+            let path = match std::env::var(name) {
+                Ok(path) if !path.is_empty() => path,
+                Ok(_) => return Err(anyhow::Error::msg(format!("The environment variable {} given by --{} is empty", name, env_path))),
+                Err(err) => return Err(anyhow::Error::msg(format!("Failed to read the environment variable {} given by --{}: {}", name, env_path, err))),
+            };
+            image_paths.push(if index == 1 { parse_second_image_path(&path)? } else { path });
+        }
+    }
+    Ok(image_paths)
+}
+
+// Check that a second image path that is a solid color reference has a valid color.
+fn parse_second_image_path(value: &str) -> anyhow::Result<String> {
+    if let Some(color) = value.strip_prefix(COLOR_PREFIX) {
+        render::parse_hex_color(color)?;
+    }
+    Ok(value.to_string())
+}
+
+// Create an image of the given size from the samples of a TIFF image (or a chunk of it) with the given color type,
+// which is converted to the matching image type (8 or 16-bit gray or RGB with optional alpha, or 32-bit float RGB).
+fn image_from_samples(color: tiff::ColorType, (width, height): (u32, u32), samples: tiff::decoder::DecodingResult) -> anyhow::Result<DynamicImage> {
+    use tiff::{ColorType, decoder::DecodingResult};
+    let img = match (color, samples) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8),
+        (ColorType::RGB(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+        (ColorType::Gray(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16),
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16),
+        (ColorType::RGB(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16),
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16),
+        (ColorType::RGB(32), DecodingResult::F32(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb32F),
+        (ColorType::RGBA(32), DecodingResult::F32(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba32F),
+        _ => return Err(anyhow::Error::msg(format!("Unsupported color type {:?}", color))),
+    };
+    img.ok_or_else(|| anyhow::Error::msg("The page has fewer pixels than its size"))
+}
+
+// Decode a page of a TIFF image (see `image_from_samples`).
+fn decode_page<R: Read + Seek>(decoder: &mut tiff::decoder::Decoder<R>) -> anyhow::Result<DynamicImage> {
+    let size = decoder.dimensions()?;
+    let color = decoder.colortype()?;
+    image_from_samples(color, size, decoder.read_image()?)
+}
+
+// Decode the pages of a multi-page TIFF image: the page with the given index, or all the pages.
+fn decode_pages<R: Read + Seek>(reader: R, pages: Pages, name: &str) -> anyhow::Result<Vec<DynamicImage>> {
+    // The decoding errors are I/O errors, like those of any image that can't be decoded.
+    let decode_error = || format!("Failed to decode {}", name);
+    let mut decoder = tiff::decoder::Decoder::new(reader).map_err(std::io::Error::other).context(decode_error())?;
+    let mut selected = Vec::new();
+    let mut count = 0;
+    loop {
+        if pages == Pages::All || pages == Pages::One(count) {
+            selected.push(decode_page(&mut decoder).map_err(|err| std::io::Error::other(format!("{err:#}"))).context(decode_error())?);
+        }
+        count += 1;
+        if pages == Pages::One(count - 1) || !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(std::io::Error::other).context(decode_error())?;
+    }
+    match pages {
+        Pages::One(index) if selected.is_empty() => Err(anyhow::Error::msg(format!("Page {} is out of range ({} has {} pages)", index, name, count))),
+        _ => Ok(selected),
+    }
+}
+
+// Decode the frames of an image from the readers created by the given function, where the name is used in the error messages.
+// For animated images (GIF and APNG), the frame with the given index is read, or all the frames if no index is given.
+// For multi-page TIFF images, the selected pages are read (see `decode_pages`).
+// Other images only have one frame (or page), so a nonzero frame (or page) index is ignored with a warning.
+fn decode_frames<R: BufRead + Seek>(open: impl Fn() -> anyhow::Result<Reader<R>>, name: &str, frame: Option<usize>, pages: Pages, verbosity: Verbosity) -> anyhow::Result<Vec<DynamicImage>> {
+    let reader = open()?;
+    let decode_error = || format!("Failed to decode {}", name);
+    let format = reader.format();
+    match format {
+        // Without the decoder, the image crate only reports that the format isn't supported.
+        #[cfg(not(feature = "avif"))]
+        Some(ImageFormat::Avif) => return Err(std::io::Error::other("Decoding AVIF images requires building with the avif feature"))
+            .context(decode_error()),
+        Some(ImageFormat::Tiff) if pages != Pages::One(0) => return decode_pages(reader.into_inner(), pages, name),
+        Some(ImageFormat::Gif) if frame != Some(0) => {
+            let decoder = GifDecoder::new(reader.into_inner()).context(decode_error())?;
+            return select_frames(decoder.into_frames(), frame, name);
+        },
+        Some(ImageFormat::Png) if frame != Some(0) => {
+            let decoder = PngDecoder::new(reader.into_inner()).context(decode_error())?;
+            if decoder.is_apng() {
+                return select_frames(decoder.apng().into_frames(), frame, name);
             }
+        },
+        _ => {},
+    }
+    if let (Some(frame @ 1..), true) = (frame, verbosity > Verbosity::SILENT) {
+        writeln!(std::io::stderr(), "Warning {} is not animated, so the frame index {} is ignored", name, frame).expect("Failed to write Warning");
+    }
+    if let (Pages::One(page @ 1..), true) = (pages, verbosity > Verbosity::SILENT) {
+        writeln!(std::io::stderr(), "Warning {} is not a TIFF image, so the page index {} is ignored", name, page).expect("Failed to write Warning");
+    }
+    // The reader may have been consumed above, so the image is opened again.
+    Ok(vec![open()?.decode().context(decode_error())?])
+}
+
+// Read the frames of the image at the given path (see `decode_frames`), from stdin if the path is "-", or from an HTTP(S) URL.
+// The format of an image read from stdin is guessed from its content unless a format is given, and the format of a URL is always guessed.
+fn read_frames(img_path: &str, options: &Options) -> anyhow::Result<Vec<DynamicImage>> {
+    let (bytes, name, format) = if img_path == STDIN_PATH {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes).context("Failed to read stdin")?;
+        (bytes, "stdin", options.stdin_format)
+    } else if http::is_url(img_path) {
+        (http::fetch(img_path, options.http_timeout)?, img_path, None)
+    } else {
+        let open = || Reader::open(img_path).context(format!("Failed to read {}", img_path));
+        return decode_frames(open, img_path, options.frame, options.pages, options.verbosity);
+    };
+    let open = || -> anyhow::Result<Reader<Cursor<&[u8]>>> {
+        let mut reader = Reader::new(Cursor::new(bytes.as_slice()));
+        match format {
+            Some(format) => reader.set_format(format),
+            None => reader = reader.with_guessed_format().context(format!("Failed to read {}", name))?,
+        }
+        Ok(reader)
+    };
+    decode_frames(open, name, options.frame, options.pages, options.verbosity)
+}
+
+// Write an error histogram to a CSV file, with the 8-bit level of each bin, its error [0-1] and its number of pixels.
+fn write_histogram_csv(path: &str, histogram: &[u64]) -> anyhow::Result<()> {
+    let mut text = String::from("level,error,pixels\n");
+    for (level, count) in histogram.iter().enumerate() {
+        text += &format!("{},{},{}\n", level, level as f32 / 255f32, count);
+    }
+    fs::write(path, text).context(format!("Failed to write {}", path))
+}
+
+// Write the wrong pixels of the mask of the outcome to the dump file in row-major order (see `dump::write`), along with their differences,
+// which are read from the images as they were compared (after the alignment and normalization, if any). Nothing is written without a mask
+// (e.g. if the images have different sizes). Verbose output tells how many pixels were written, and whether the dump was truncated.
+fn write_diff_pixels(path: &str, format: dump::Format, images: &ImagePair, outcome: &ComparisonOutcome, options: &Options) -> anyhow::Result<()> {
+    let Some(mask) = &outcome.diff_mask else { return Ok(()); };
+    let aligned = outcome.alignment_offset.map(|offset| imgcmp::align::crop_to_overlap(images, offset));
+    let images = aligned.as_ref().unwrap_or(images);
+    let normalized = outcome.normalization.map(|_| imgcmp::normalize::normalize(images).0);
+    let images = normalized.as_ref().unwrap_or(images);
+    let width = images.dimensions()[0].0;
+    let pixels = mask.iter().enumerate().filter(|&(_, &wrong)| wrong).map(|(index, _)| {
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        (x, y, images.channel_errors(x, y))
+    });
+    let written = dump::write(path, format, options.dump_limit, pixels)?;
+    if options.verbosity == Verbosity::VERBOSE {
+        if written.pixels < written.total {
+            writeln!(options.out(), "Dumped the first {} of {} different pixels to {} (truncated by --dump-limit)", written.pixels, written.total, path).expect("Failed to write the dump summary");
+        } else {
+            writeln!(options.out(), "Dumped {} different pixels to {}", written.pixels, path).expect("Failed to write the dump summary");
+        }
+    }
+    Ok(())
+}
+
+// Return the path of the error image of the frame (or page) with the given index (e.g. diff.0003.png for the frame 3 of diff.png).
+fn frame_output_path(output: &str, index: usize) -> String {
+    let path = Path::new(output);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{:04}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.{:04}", stem, index),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+// Try to parse an image format from its name (as a file extension, e.g. png or jpg).
+fn parse_image_format(value: &str) -> anyhow::Result<ImageFormat> {
+    ImageFormat::from_extension(value).ok_or(anyhow::Error::msg(format!("Unknown image format \"{}\"", value)))
+}
+
+// The formats in which the error image can be saved with --output-format.
+const OUTPUT_FORMATS: [(&str, ImageFormat); 7] = [
+    ("png", ImageFormat::Png), ("jpeg", ImageFormat::Jpeg), ("jpg", ImageFormat::Jpeg), ("bmp", ImageFormat::Bmp),
+    ("tiff", ImageFormat::Tiff), ("tif", ImageFormat::Tiff), ("webp", ImageFormat::WebP),
+];
+
+// Try to parse the format of the error image from its name (png, jpeg, bmp, tiff or webp).
+fn parse_output_format(value: &str) -> anyhow::Result<ImageFormat> {
+    OUTPUT_FORMATS.iter().find(|(name, _)| name.eq_ignore_ascii_case(value)).map(|&(_, format)| format)
+        .ok_or(anyhow::Error::msg(format!("Unsupported output format \"{}\" (expected png, jpeg, bmp, tiff or webp)", value)))
+}
+
+// Return the format in which the error image is saved to the given path: the given format if any, or the one inferred from its extension.
+fn output_format(path: &str, format: Option<ImageFormat>) -> anyhow::Result<ImageFormat> {
+    match format {
+        Some(format) => Ok(format),
+        None => ImageFormat::from_path(path).map_err(|_| anyhow::Error::msg(format!(
+            "Can't infer the format of the error image from the extension of {} (use --output-format to set it)", path))),
+    }
+}
+
+// Try to parse the compression of PNG error images: fast, default or best.
+fn parse_png_compression(value: &str) -> anyhow::Result<png::Compression> {
+    match value {
+        "fast" => Ok(png::Compression::Fast),
+        "default" => Ok(png::Compression::Default),
+        "best" => Ok(png::Compression::Best),
+        _ => Err(anyhow::Error::msg(format!("Invalid PNG compression \"{}\" (expected fast, default or best)", value))),
+    }
+}
+
+// Warn about the encoder options that don't apply to the format of the error image saved to the given path, which are ignored,
+// and in verbose mode, that a JPEG error image (the only lossy one, since WebP is saved losslessly) only approximates the errors.
+fn warn_encoder_options(path: &str, format: ImageFormat, options: &Options) {
+    if options.verbosity == Verbosity::SILENT {
+        return;
+    }
+    if options.png_compression.is_some() && format != ImageFormat::Png {
+        writeln!(std::io::stderr(), "Warning --png-compression is ignored for {}, which isn't a PNG", path).expect("Failed to write Warning");
+    }
+    if options.jpeg_quality.is_some() && format != ImageFormat::Jpeg {
+        writeln!(std::io::stderr(), "Warning --jpeg-quality is ignored for {}, which isn't a JPEG", path).expect("Failed to write Warning");
+    }
+    if format == ImageFormat::Jpeg && options.verbosity == Verbosity::VERBOSE {
+        writeln!(std::io::stderr(), "Warning {} is saved as a lossy JPEG, so the errors it shows are approximate", path).expect("Failed to write Warning");
+    }
+}
+
+// Save the error image to the given path in the given format, failing clearly if the format can't be written.
+// Since JPEG has no alpha channel, the alpha channel is dropped for it, and it is saved with the given quality (if any).
+fn save_error_image(path: &str, img: &DynamicImage, format: ImageFormat, jpeg_quality: Option<u8>) -> anyhow::Result<()> {
+    if !format.can_write() {
+        return Err(anyhow::Error::msg(format!("Saving the error image as {:?} is not supported", format)));
+    }
+    if format == ImageFormat::WebP {
+        // WebP is saved losslessly (the default is lossy), so the error values are preserved.
+        let img = img.to_rgba8();
+        let file = fs::File::create(path).context(format!("Failed to write {}", path))?;
+        WebPEncoder::new_with_quality(std::io::BufWriter::new(file), WebPQuality::lossless())
+            .encode(img.as_raw(), img.width(), img.height(), image::ColorType::Rgba8)
+    } else if let (ImageFormat::Jpeg, Some(quality)) = (format, jpeg_quality) {
+        let img = img.to_rgb8();
+        let file = fs::File::create(path).context(format!("Failed to write {}", path))?;
+        JpegEncoder::new_with_quality(std::io::BufWriter::new(file), quality).encode(img.as_raw(), img.width(), img.height(), image::ColorType::Rgb8)
+    } else if format == ImageFormat::Jpeg && img.color().has_alpha() {
+        DynamicImage::ImageRgb8(img.to_rgb8()).save_with_format(path, format)
+    } else {
+        img.save_with_format(path, format)
+    }.context(format!("Failed to write {}", path))
+}
+
+// Return the width of the text preview: the terminal width (as given by $COLUMNS) or 80 columns.
+fn preview_columns() -> u32 {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.parse::<u32>().ok()).filter(|&columns| columns > 0).unwrap_or(80)
+}
+
+// Resize the frames of one of the images to the size of the other one if their sizes differ, as the size mismatch policy dictates.
+// The images are left as is if they have the same size or if the policy doesn't resize them.
+fn match_sizes(mut frames: [Vec<DynamicImage>; 2], policy: SizeMismatchPolicy) -> anyhow::Result<[Vec<DynamicImage>; 2]> {
+    let sizes = [frames[0][0].dimensions(), frames[1][0].dimensions()];
+    if sizes[0] == sizes[1] {
+        return Ok(frames);
+    }
+    let area = |(width, height): (u32, u32)| width as u64 * height as u64;
+    let resized = match policy {
+        SizeMismatchPolicy::Error => return Err(anyhow::Error::msg(format!(
+            "Images have different sizes ({}x{} and {}x{})", sizes[0].0, sizes[0].1, sizes[1].0, sizes[1].1))),
+        SizeMismatchPolicy::Fail => return Ok(frames),
+        SizeMismatchPolicy::ResizeFirst => 0,
+        SizeMismatchPolicy::ResizeSecond => 1,
+        SizeMismatchPolicy::ResizeSmallest => if area(sizes[0]) > area(sizes[1]) { 0 } else { 1 },
+        SizeMismatchPolicy::ResizeLargest => if area(sizes[0]) < area(sizes[1]) { 0 } else { 1 },
+    };
+    let (width, height) = sizes[1 - resized];
+    frames[resized] = frames[resized].iter().map(|frame| frame.resize_exact(width, height, FilterType::Triangle)).collect();
+    Ok(frames)
+}
+
+// Return true if the image path refers to a file (rather than stdin, a solid color reference or a URL).
+fn is_file_path(path: &str) -> bool {
+    path != STDIN_PATH && !path.starts_with(COLOR_PREFIX) && !http::is_url(path)
+}
+
+// Return true if the two files have the same bytes. Their sizes are compared first, then their contents in chunks,
+// so neither file is read whole.
+fn files_identical(paths: [&String; 2]) -> std::io::Result<bool> {
+    const CHUNK_SIZE: usize = 1 << 16;
+    let [size1, size2] = [fs::metadata(paths[0])?.len(), fs::metadata(paths[1])?.len()];
+    if size1 != size2 {
+        return Ok(false);
+    }
+    let [mut file1, mut file2] = [fs::File::open(paths[0])?, fs::File::open(paths[1])?];
+    let (mut chunk1, mut chunk2) = (vec![0u8; CHUNK_SIZE], vec![0u8; CHUNK_SIZE]);
+    let mut remaining = size1;
+    while remaining > 0 {
+        let length = remaining.min(CHUNK_SIZE as u64) as usize;
+        file1.read_exact(&mut chunk1[..length])?;
+        file2.read_exact(&mut chunk2[..length])?;
+        if chunk1[..length] != chunk2[..length] {
             return Ok(false);
         }
-        size1
+        remaining -= length as u64;
+    }
+    Ok(true)
+}
+
+// Return the outcome of comparing the images if they are byte-identical files (whose size is read from the header of the first one),
+// or None if they aren't (or they aren't both files, or they can't be read, which the full comparison then reports).
+fn byte_identical_outcome(image_paths: [&String; 2], comparison: &ComparisonOptions) -> Option<ComparisonOutcome> {
+    if !image_paths.iter().all(|&path| is_file_path(path)) || !files_identical(image_paths).ok()? {
+        return None;
+    }
+    let size = image::image_dimensions(image_paths[0]).ok()?;
+    Some(ComparisonOutcome::byte_identical(size, comparison))
+}
+
+// Return an error if the image at the given path has more pixels than allowed by --max-pixels (if set).
+fn check_pixel_count(path: &str, (width, height): (u32, u32), options: &Options) -> anyhow::Result<()> {
+    match options.max_pixels {
+        Some(max_pixels) if width as u64 * height as u64 > max_pixels => Err(anyhow::Error::msg(format!(
+            "{} has {} pixels ({}x{}), which exceeds the limit of {} pixels set by --max-pixels", path, width as u64 * height as u64, width, height, max_pixels))),
+        _ => Ok(()),
+    }
+}
+
+// Check the pixel counts of both images, given their sizes (see `check_pixel_count`).
+fn check_pixel_counts(image_paths: [&String; 2], sizes: [(u32, u32); 2], options: &Options) -> anyhow::Result<()> {
+    image_paths.iter().zip(sizes).try_for_each(|(path, size)| check_pixel_count(path, size, options))
+}
+
+// Run the comparison command for the given image paths, using the given options, and save the error image to the given path (if any).
+// Return the outcome of the comparison and the error image (None if the images have different sizes).
+// If both images are animated (and no frame is selected), their frames are compared pairwise, and each frame has its own error image
+// (see `frame_output_path`). Then, the returned error image is the one of the first different frame (or the first frame).
+fn run(image_paths: [&String; 2], output: Option<&String>, comparison: &ComparisonOptions, options: &Options, progress: &mut dyn FnMut(f32)) -> anyhow::Result<(ComparisonOutcome, Option<DynamicImage>)> {
+    // Byte-identical files match without being decoded, unless the error image (or the dump of the different pixels) is needed.
+    let needs_error_img = (output.is_some() && !options.output_on_mismatch_only) || options.preview;
+    if options.fast_path && !needs_error_img && options.dump_diff_pixels.is_none() {
+        if let Some(outcome) = byte_identical_outcome(image_paths, comparison).filter(|outcome| outcome.matched) {
+            check_pixel_counts(image_paths, outcome.sizes, options)?;
+            return Ok((outcome, None));
+        }
+    }
+
+    // Without an error image, TIFF images (whose pixels are stored in strips or tiles) are compared band by band as they are decoded,
+    // so they are never held whole. Other images, and the options that need the whole images, fall back to decoding them whole.
+    if let (true, None, false, false, None, Pages::One(page)) = (options.streaming, output, options.preview, options.unpremultiply, options.simulate, options.pages) {
+        if RowComparison::supports(comparison) && image_paths.iter().all(|&path| is_file_path(path)) {
+            if let Some(result) = stream::compare_tiff_bands(image_paths, page, comparison, progress) {
+                let outcome = result?;
+                check_pixel_counts(image_paths, outcome.sizes, options)?;
+                return Ok((outcome, None));
+            }
+        }
+    }
+
+    // Read the two images. A solid color reference takes the size of the first image.
+    // Premultiplied images are converted to straight alpha (a solid color reference is never premultiplied).
+    // The pixel counts are checked right after decoding, before anything else is allocated for the images.
+    let read_image = |path: &str| read_frames(path, options).and_then(|frames| {
+        frames.iter().try_for_each(|frame| check_pixel_count(path, frame.dimensions(), options))?;
+        Ok(if options.unpremultiply { frames.into_iter().map(imgcmp::alpha::unpremultiply).collect() } else { frames })
+    });
+    let frames1 = read_image(image_paths[0])?;
+    let frames2 = match image_paths[1].strip_prefix(COLOR_PREFIX) {
+        Some(color) => vec![DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(frames1[0].width(), frames1[0].height(), render::parse_hex_color(color)?))],
+        None => read_image(image_paths[1])?,
     };
 
-    let value_threshold = options.value_threshold;
-    let error_thresold = options.error_threshold.get_actual_threshold(size);    
-    
-    // Allocate an image to store the error between the two images
-    let mut error_img = image::RgbImage::new(size.0, size.1);
-    
-    let mut wrong_pixels: u32 = 0; // The number of pixels that differ by more than the value threshold
-
-    // Loop over all the pixels, compute the difference and populate the  error image
-    for x in 0..size.0 {
-        for y in 0..size.1 {
-
-            let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y));
-            
-            let mut is_pixel_different = false;
-            // For each pair of channels, compute the absolute difference and check it exceeds the value threshold
-            let diff: (u8, u8, u8) = pixel1.0.iter().zip(pixel2.0.iter()).map(|(v1, v2)| {
-                let diff = v1.abs_diff(*v2);
-                if diff > value_threshold {
-                    is_pixel_different = true; // A pair of pixels are mismatched if their difference exceed the threshold in any channel.
-                    return 128 | diff >> 1; // To make sure that any wrong pixel is visible in the error image, we remap the error from [0-255] to [128-255].
+    // Both images (including a solid color reference) are seen with the simulated color vision deficiency, if any.
+    let [frames1, frames2] = [frames1, frames2].map(|frames| match options.simulate {
+        Some(deficiency) => frames.into_iter().map(|frame| cvd::simulate(frame, deficiency)).collect(),
+        None => frames,
+    });
+
+    let [mut frames1, mut frames2] = match_sizes([frames1, frames2], options.on_size_mismatch)?;
+
+    // When all the pages are compared, different page counts are an error, unless only the pages that exist in both images are compared.
+    if options.pages == Pages::All && frames1.len() != frames2.len() {
+        let counts = format!("{} has {} pages and {} has {}", image_paths[0], frames1.len(), image_paths[1], frames2.len());
+        if !options.allow_page_count_mismatch {
+            return Err(anyhow::Error::msg(format!("The images have different numbers of pages ({}), see --allow-page-count-mismatch", counts)));
+        }
+        let count = frames1.len().min(frames2.len());
+        if options.verbosity > Verbosity::SILENT {
+            writeln!(std::io::stderr(), "Warning {}, so only the first {} are compared", counts, count).expect("Failed to write Warning");
+        }
+        frames1.truncate(count);
+        frames2.truncate(count);
+    }
+
+    // With --all-pages, the images are compared pairwise if they have several pages.
+    let paired = (frames1.len() > 1 && frames2.len() > 1) || (options.pages == Pages::All && frames1.len().max(frames2.len()) > 1);
+    let frame_counts = [frames1.len(), frames2.len()];
+    let pairs: Vec<ImagePair> = if paired {
+        frames1.into_iter().zip(frames2).map(|(frame1, frame2)| ImagePair::new(frame1, frame2)).collect()
+    } else {
+        // If only one of the images is animated, its first frame is compared.
+        let [img1, img2] = [frames1, frames2].map(|frames| frames.into_iter().next().expect("An image has at least one frame"));
+        vec![ImagePair::new(img1, img2)]
+    };
+    let (outcome, error_imgs) = if paired {
+        imgcmp::compare_frames(&pairs, frame_counts, comparison)?
+    } else {
+        let (outcome, error_img) = imgcmp::compare_with_progress(&pairs[0], comparison, progress)?;
+        (outcome, vec![error_img])
+    };
+
+    // If an outut image path was given, save the error image to it (unless the images match with --output-on-mismatch-only).
+    if let (Some(output_path), false) = (output, options.output_on_mismatch_only && outcome.matched) {
+        for (index, error_img) in error_imgs.iter().enumerate() {
+            let Some(error_img) = error_img else { continue; };
+            let (path, frame_outcome) = match &outcome.animation {
+                Some(_) if output_path == STDOUT_PATH => (output_path.clone(), &outcome),
+                Some(animation) => (frame_output_path(output_path, index), &animation.frames[index]),
+                None => (output_path.clone(), &outcome),
+            };
+            let save = |path: &str, error_img: &DynamicImage| {
+                if path == STDOUT_PATH {
+                    if outcome.animation.is_some() {
+                        return Err(anyhow::Error::msg("The error images of animated images can't be written to stdout"));
+                    }
+                    let entries = if options.png_metadata { metadata::comparison_entries(image_paths, frame_outcome, comparison) } else { Vec::new() };
+                    return metadata::write_png(std::io::stdout().lock(), "stdout", error_img, &entries, options.png_compression.unwrap_or(png::Compression::Default));
+                }
+                let format = output_format(path, options.output_format)?;
+                warn_encoder_options(path, format, options);
+                if format == ImageFormat::Png {
+                    let entries = if options.png_metadata { metadata::comparison_entries(image_paths, frame_outcome, comparison) } else { Vec::new() };
+                    metadata::save_png(path, error_img, &entries, options.png_compression.unwrap_or(png::Compression::Default))
                 } else {
-                    return 0; // If the difference if below the threshold, we snap it to 0.
+                    if options.png_metadata && options.verbosity == Verbosity::VERBOSE {
+                        writeln!(std::io::stderr(), "Warning The metadata isn't embedded in {}, since only PNG error images carry it", path).expect("Failed to write Warning");
+                    }
+                    save_error_image(path, error_img, format, options.jpeg_quality)
                 }
-            }).collect_tuple().unwrap();
-            
-            error_img.get_pixel_mut(x, y).0 = [diff.0, diff.1, diff.2];
-            
-            if is_pixel_different { wrong_pixels += 1; }
+            };
+            save(&path, error_img)?;
+            // The error images in the other styles are saved like the first one (with a file per frame for animated images).
+            for ((_, extra_path), extra_img) in options.extra_outputs.iter().zip(&frame_outcome.extra_error_images) {
+                let extra_path = if outcome.animation.is_some() { frame_output_path(extra_path, index) } else { extra_path.clone() };
+                save(&extra_path, extra_img)?;
+            }
         }
     }
 
-    // If an outut image path was given, save the error image to it.
-    if let Some(output_path) = &options.output {
-        error_img.save(output_path)?;
+    if let (Some(path), Some(histogram)) = (&options.error_histogram_csv, &outcome.error_histogram) {
+        write_histogram_csv(path, histogram)?;
     }
 
-    // The images are considered different if the number of wrong pixels exceed the error threshold
-    let mismatch  = wrong_pixels > error_thresold;
-    
-    // Prints the results according to the given verbosity level
+    // Like the error images, each frame has its own dump.
+    if let Some((path, format)) = &options.dump_diff_pixels {
+        match &outcome.animation {
+            Some(animation) => for (index, (images, frame_outcome)) in pairs.iter().zip(&animation.frames).enumerate() {
+                write_diff_pixels(&frame_output_path(path, index), *format, images, frame_outcome, options)?;
+            },
+            None => write_diff_pixels(path, *format, &pairs[0], &outcome, options)?,
+        }
+    }
+
+    let shown = outcome.animation.as_ref().and_then(|animation| animation.frames.iter().position(|frame| !frame.matched)).unwrap_or(0);
+    Ok((outcome, error_imgs.into_iter().nth(shown).flatten()))
+}
+
+// A message from the thread of a comparison with a timeout: its progress, then its result.
+enum TimedMessage {
+    Progress(f32),
+    Done(Box<anyhow::Result<(ComparisonOutcome, Option<DynamicImage>)>>),
+}
+
+// Compare the pair of images like `run`, but on a worker thread if there is a timeout, so a comparison that exceeds it
+// (e.g. decoding a pathological image) fails with an I/O error of kind TimedOut. The progress is still reported on this thread.
+// A comparison that timed out can't be interrupted, so its thread is abandoned and keeps running in the background until it ends.
+fn run_with_timeout(image_paths: [&String; 2], output: Option<&String>, comparison: &ComparisonOptions, options: &Options, progress: &mut dyn FnMut(f32)) -> anyhow::Result<(ComparisonOutcome, Option<DynamicImage>)> {
+    let Some(timeout) = options.timeout else {
+        return run(image_paths, output, comparison, options, progress);
+    };
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let (paths, output, comparison, worker_options) = (image_paths.map(String::clone), output.cloned(), comparison.clone(), options.clone());
+    std::thread::spawn(move || {
+        let progress_sender = sender.clone();
+        let result = run([&paths[0], &paths[1]], output.as_ref(), &comparison, &worker_options, &mut |fraction| {
+            let _ = progress_sender.send(TimedMessage::Progress(fraction));
+        });
+        // The receiver is gone if the comparison timed out.
+        let _ = sender.send(TimedMessage::Done(Box::new(result)));
+    });
+
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        match receiver.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+            Ok(TimedMessage::Progress(fraction)) => progress(fraction),
+            Ok(TimedMessage::Done(result)) => return *result,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return Err(anyhow::Error::new(std::io::Error::new(std::io::ErrorKind::TimedOut,
+                format!("The comparison of {} and {} timed out after {:?}", image_paths[0], image_paths[1], timeout)))),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return Err(anyhow::Error::msg(
+                format!("The comparison of {} and {} stopped unexpectedly", image_paths[0], image_paths[1]))),
+        }
+    }
+}
+
+// Replace the first image (the baseline) by the second one according to the update mode (or create it if it is missing),
+// if the comparison calls for it. A missing baseline is only created if the second image can be decoded,
+// so a broken image never becomes a baseline. Return true if the baseline was updated.
+fn update_baseline(image_paths: [&String; 2], baseline_missing: bool, result: &anyhow::Result<ComparisonOutcome>, options: &Options) -> anyhow::Result<bool> {
+    let create_missing = options.update.is_some() || options.missing_baseline == MissingBaselineMode::Create;
+    let should_update = match (options.update, result) {
+        (_, Err(_)) if baseline_missing && create_missing => {
+            read_frames(image_paths[1], options)?;
+            true
+        },
+        (Some(UpdateMode::MismatchOrMissing), Ok(outcome)) => !outcome.matched,
+        _ => false,
+    };
+    if !should_update {
+        return Ok(false);
+    }
+    let baseline = Path::new(image_paths[0]);
+    if let Some(parent) = baseline.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::copy(image_paths[1], baseline).context(format!("Failed to copy {} to {}", image_paths[1], image_paths[0]))?;
     if options.verbosity > Verbosity::SILENT {
-        println!("{}", if mismatch {"MISMATCH DETECTED"} else {"MATCH"});
-        if options.verbosity == Verbosity::VERBOSE {
-            println!("Different Pixels: {}%", (100 * wrong_pixels) as f32 / (size.0 * size.1) as f32);
+        let verb = if baseline_missing { "Created" } else { "Updated" };
+        writeln!(options.out(), "{} {} from {}", verb, image_paths[0], image_paths[1]).expect("Failed to write the baseline update");
+    }
+    Ok(true)
+}
+
+// Compare the pair of images, then compare it again whenever the images (or the tolerance map) are modified, until Ctrl-C is pressed.
+// Each comparison prints a timestamped result line (followed by the usual output with --verbose) and rewrites the error image and the reports.
+// Return the exit code of the most recent comparison.
+fn watch_pair(image_paths: &[String; 2], args: &ArgMatches, mut options: Options) -> u8 {
+    let tolerance_map = args.get_one::<String>("tolerance-map");
+    let watched: Vec<&str> = image_paths.iter().filter(|&path| is_file_path(path)).chain(tolerance_map).map(String::as_str).collect();
+    let mut watcher = watch::Watcher::new(&watched);
+    watch::handle_interrupts();
+    let mut progress = progress::ProgressBar::new(options.progress);
+    loop {
+        let result = run_with_timeout([&image_paths[0], &image_paths[1]], options.output.as_ref(), &options.comparison, &options, &mut |fraction| progress.draw("Comparing", fraction));
+        progress.clear();
+        let (result, error_img) = match result {
+            Ok((outcome, error_img)) => (Ok(outcome), error_img),
+            Err(err) => (Err(err), None),
+        };
+        let mut status = Status::from(&result);
+        if options.verbosity > Verbosity::SILENT {
+            match &result {
+                Ok(outcome) if !outcome.is_size_mismatch() => println!("[{}] {} ({}% different pixels)", watch::timestamp(), options.paint_status(status), outcome.wrong_percentage()),
+                _ => println!("[{}] {}", watch::timestamp(), options.paint_status(status)),
+            }
+            match &result {
+                Ok(outcome) if options.verbosity == Verbosity::VERBOSE => print_outcome(outcome, error_img.as_ref(), &options),
+                Err(err) => writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error"),
+                _ => {},
+            }
+        }
+        let output = options.output.clone().filter(|_| !(options.output_on_mismatch_only && matches!(&result, Ok(outcome) if outcome.matched)));
+        let comparisons = [batch::Comparison { image_paths: image_paths.clone(), output, result, updated: false }];
+        let reports = [
+            options.junit_report.as_ref().map(|path| junit::write_report(path, &comparisons)),
+            options.json_report.as_ref().map(|path| json::write_report(path, &comparisons)),
+            options.html_report.as_ref().map(|(path, max_dim)| html::write_report(path, &comparisons, &options.comparison, *max_dim)),
+            write_report_file(&comparisons, &options),
+        ];
+        for result in reports.into_iter().flatten() {
+            if let Err(err) = result {
+                if options.verbosity > Verbosity::SILENT {
+                    writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+                }
+                status = status.max(Status::from(&Err(err)));
+            }
+        }
+        let exit_code = options.exit_code(options.expected_status(status));
+        watch::set_exit_code(exit_code);
+
+        if !watcher.wait_for_change() {
+            return exit_code;
+        }
+        // The options are parsed again, so a modified tolerance map is read again.
+        match Options::try_from(args) {
+            Ok(reparsed) => options = reparsed,
+            Err(err) => if options.verbosity > Verbosity::SILENT {
+                writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+            },
+        }
+    }
+}
+
+// Print the outcome of the comparison according to the given verbosity level.
+fn print_outcome(outcome: &ComparisonOutcome, error_img: Option<&DynamicImage>, options: &Options) {
+    if options.verbosity == Verbosity::SILENT {
+        return;
+    }
+    let mut text = String::new();
+    write_outcome(&mut text, outcome, error_img, options, options.verbosity == Verbosity::VERBOSE, options.color).expect("Failed to format the outcome");
+    write!(options.out(), "{}", text).expect("Failed to write the outcome");
+}
+
+// Write the different pixels per tile as a grid (one line per row of tiles), where the tile with the most different pixels
+// (the first one on a tie) is highlighted, followed by its position.
+fn write_tile_grid(out: &mut impl std::fmt::Write, tiles: &[Tile], color: bool) -> std::fmt::Result {
+    let columns = tiles.iter().map(|tile| tile.index.0 as usize + 1).max().unwrap_or(1);
+    let worst = tiles.iter().rev().max_by_key(|tile| tile.wrong_pixels).filter(|tile| tile.wrong_pixels > 0);
+    let width = tiles.iter().map(|tile| tile.wrong_pixels.to_string().len()).max().unwrap_or(1);
+    writeln!(out, "Different pixels per tile ({} columns, {} rows):", columns, tiles.len() / columns)?;
+    for row in tiles.chunks(columns) {
+        let cells: Vec<String> = row.iter().map(|tile| {
+            let cell = format!("{:>width$}", tile.wrong_pixels, width = width);
+            if worst.is_some_and(|worst| worst.index == tile.index) { color::paint(&cell, color::Color::Red, color) } else { cell }
+        }).collect();
+        writeln!(out, "    {}", cells.join(" "))?;
+    }
+    match worst {
+        Some(tile) => writeln!(out, "Worst tile: ({}, {}) at {} ({} different pixels)", tile.index.0, tile.index.1, tile.bbox, tile.wrong_pixels),
+        None => writeln!(out, "Worst tile: none (no pixel differs)"),
+    }
+}
+
+// Write the outcome of the comparison as printed with the given verbosity (all the statistics if verbose), colored if requested.
+// The preview is only written if the error image is given.
+fn write_outcome(out: &mut impl std::fmt::Write, outcome: &ComparisonOutcome, error_img: Option<&DynamicImage>, options: &Options, verbose: bool, color: bool) -> std::fmt::Result {
+    if outcome.is_size_mismatch() {
+        let [size1, size2] = outcome.sizes;
+        writeln!(out, "{}", color::paint(&format!("Images have different sizes (Got ({}x{}) and ({}x{})).", size1.0, size1.1, size2.0, size2.1), color::Color::Yellow, color))?;
+        if options.invert_result {
+            writeln!(out, "{}", color::paint("EXPECTED DIFFERENCE FOUND", color::Color::Green, color))?;
+        }
+        return Ok(());
+    }
+
+    if let Some(animation) = &outcome.animation {
+        let [count1, count2] = animation.frame_counts;
+        if count1 != count2 {
+            writeln!(out, "Images have different numbers of frames (Got {} and {}).", count1, count2)?;
+        }
+    }
+
+    // The result is green if it is the expected one.
+    let (result, expected) = match (options.invert_result, outcome.matched) {
+        (false, true) => ("MATCH", true),
+        (false, false) => ("MISMATCH DETECTED", false),
+        (true, true) => ("UNEXPECTED MATCH", false),
+        (true, false) => ("EXPECTED DIFFERENCE FOUND", true),
+    };
+    writeln!(out, "{}", color::paint(result, if expected { color::Color::Green } else { color::Color::Red }, color))?;
+    if let Some(hash) = &outcome.hash {
+        writeln!(out, "Perceptual hash distance: {} bits (limit {}, {} {:016x} vs {:016x})", hash.distance, hash.threshold, hash.algorithm.name(), hash.hashes[0], hash.hashes[1])?;
+    }
+    if verbose {
+        if let Some((dx, dy)) = outcome.alignment_offset {
+            let (width, height) = outcome.sizes[0];
+            writeln!(out, "Alignment offset: ({}, {}) (compared {}x{} overlapping pixels)", dx, dy, width, height)?;
+        }
+        if let Some(normalization) = &outcome.normalization {
+            writeln!(out, "Normalized the second image with gain {} and offset {}", normalization.gain, normalization.offset)?;
+        }
+        if let Some(pyramid) = &outcome.pyramid {
+            for level in &pyramid.levels {
+                let (width, height) = level.size;
+                writeln!(out, "Pyramid level {} ({}x{}): {} of {} different pixels ({})", level.level, width, height, level.wrong_pixels, level.total_pixels, if level.matched { "MATCH" } else { "MISMATCH" })?;
+            }
+            if let Some(level) = pyramid.mismatch_level {
+                writeln!(out, "The mismatch was detected at pyramid level {}", level)?;
+            }
+        }
+        if let Some(zscore) = &outcome.zscore {
+            writeln!(out, "Error mean: {:.6}, standard deviation: {:.6}, threshold at z = {}: {:.6}", zscore.mean, zscore.deviation, zscore.z, zscore.threshold)?;
+        }
+        if outcome.byte_identical {
+            writeln!(out, "The files are byte-identical (the images weren't decoded)")?;
+        }
+        if let (true, Some(sampling)) = (outcome.sampled, options.comparison.sample) {
+            writeln!(out, "Sampled {} random pixels (seed {}), so the result is approximate", outcome.total_pixels, sampling.seed)?;
+        }
+        writeln!(out, "Different Pixels: {}%", outcome.wrong_percentage())?;
+        writeln!(out, "MAE: {:.2}", outcome.mae)?;
+        writeln!(out, "RMSE: {:.2}", outcome.rmse)?;
+        if let Some(channel_stats) = &outcome.channel_stats {
+            for (name, stats) in ["R", "G", "B"].iter().zip(channel_stats) {
+                writeln!(out, "Channel {}: mean difference {} max difference {} ({} differing pixels)", name, stats.mean, stats.max, stats.differing)?;
+            }
+        }
+        if let Some(animation) = &outcome.animation {
+            let label = if options.pages == Pages::All { "Page" } else { "Frame" };
+            for (index, frame) in animation.frames.iter().enumerate() {
+                writeln!(out, "{} {}: {} different pixels ({})", label, index, frame.wrong_pixels, if frame.matched { "MATCH" } else { "MISMATCH" })?;
+            }
+        }
+        if let Some(histogram) = &outcome.error_histogram {
+            writeln!(out, "Error histogram (largest channel difference, log-scaled bars):")?;
+            write!(out, "{}", render::render_histogram(histogram, HISTOGRAM_BINS, options.comparison.value_threshold))?;
+        }
+        if let Some(flip) = &outcome.flip {
+            writeln!(out, "FLIP: mean {} max {}", flip.mean, flip.max)?;
+        }
+        if let Some(fft) = &outcome.fft {
+            writeln!(out, "FFT: {} of {} frequency bins ({}%) differ, mean error {} max error {}", outcome.wrong_pixels, outcome.total_pixels, outcome.wrong_percentage(), fft.mean, fft.max)?;
+        }
+        if let (Some(error), Some((percentile, limit))) = (outcome.percentile_error, options.comparison.percentile) {
+            writeln!(out, "Percentile error: {} at the {}th percentile (limit {})", error, percentile, limit)?;
+        }
+        if let Some(edges) = &outcome.edges {
+            writeln!(out, "Edges: Hausdorff distance {} ({} and {} edge pixels)", edges.hausdorff, edges.edge_pixels[0], edges.edge_pixels[1])?;
+        }
+        if let Some(bbox) = &outcome.bbox {
+            writeln!(out, "diff bbox: {}", bbox)?;
+        }
+        let has_tile_error = matches!(&options.comparison.tile, Some((_, Some(_))));
+        for tile in &outcome.failed_tiles {
+            if has_tile_error {
+                writeln!(out, "Tile ({}, {}) at {} exceeded the tile error threshold ({} different pixels)", tile.index.0, tile.index.1, tile.bbox, tile.wrong_pixels)?;
+            } else {
+                writeln!(out, "Tile ({}, {}) at {} has {} different pixels", tile.index.0, tile.index.1, tile.bbox, tile.wrong_pixels)?;
+            }
+        }
+        if let Some(tiles) = &outcome.tiles {
+            write_tile_grid(out, tiles, color)?;
+        }
+    }
+    if let Some(sweep) = &outcome.sweep {
+        writeln!(out, "Threshold sweep:")?;
+        writeln!(out, "    {:>9}  {:>10}  RESULT", "THRESHOLD", "DIFFERENT")?;
+        for point in sweep {
+            let percentage = if outcome.total_pixels == 0 { 0f32 } else { point.wrong_pixels as f32 * 100f32 / outcome.total_pixels as f32 };
+            let (label, status) = if point.matched { ("MATCH", Status::Match) } else { ("MISMATCH", Status::Mismatch) };
+            writeln!(out, "    {:>9.4}  {:>9.4}%  {}", point.value_threshold, percentage, color::paint(label, status.color(), color))?;
         }
     }
+    if let (Some(suggestions), false) = (&outcome.suggestions, outcome.matched) {
+        writeln!(out, "Suggestions:")?;
+        if let Some(threshold) = suggestions.value_threshold {
+            writeln!(out, "    --threshold {:.4} makes every pixel match", threshold)?;
+        }
+        writeln!(out, "    --error {} (or --error {:.4}%) allows the different pixels with the current settings", suggestions.error_pixels, suggestions.error_percentage)?;
+    }
+    if options.max_error_pixel {
+        match &outcome.max_error_pixel {
+            Some(pixel) => {
+                let [r, g, b] = pixel.channel_errors;
+                writeln!(out, "Max error pixel: (x={}, y={}) error=({}, {}, {})", pixel.x, pixel.y, r, g, b)?;
+            },
+            None => writeln!(out, "Max error pixel: none (no pixel differs)")?,
+        }
+    }
+    if let Some(worst_pixels) = &outcome.worst_pixels {
+        if worst_pixels.is_empty() {
+            writeln!(out, "Worst pixels: none (no pixel differs)")?;
+        } else {
+            writeln!(out, "Worst pixels:")?;
+        }
+        let rgb = |[r, g, b]: [f32; 3]| format!("({}, {}, {})", r, g, b);
+        for pixel in worst_pixels {
+            let frame = pixel.frame.map(|frame| format!("{} {} ", if options.pages == Pages::All { "Page" } else { "Frame" }, frame)).unwrap_or_default();
+            let [r, g, b] = pixel.channel_errors();
+            writeln!(out, "    {}({}, {}): {} vs {} \u{394}=({},{},{})", frame, pixel.x, pixel.y, rgb(pixel.values[0]), rgb(pixel.values[1]), r, g, b)?;
+        }
+    }
+    if let (true, Some(error_img)) = (options.preview, error_img) {
+        write!(out, "{}", render::render_preview(error_img, preview_columns()))?;
+    }
+    if let Some(clusters) = &outcome.clusters {
+        let largest = clusters.iter().map(|cluster| cluster.pixel_count).max().unwrap_or(0);
+        writeln!(out, "Clusters: {} (largest: {} pixels)", clusters.len(), largest)?;
+        if outcome.oversized_cluster {
+            writeln!(out, "A cluster exceeds the maximum cluster size.")?;
+        }
+        if options.list_clusters {
+            for (index, cluster) in clusters.iter().enumerate() {
+                writeln!(out, "    #{}: {} ({} pixels)", index + 1, cluster.bbox, cluster.pixel_count)?;
+            }
+        }
+    }
+    if let Some(regions) = options.region_report.and_then(|count| outcome.top_clusters(count)) {
+        writeln!(out, "Top {} regions:", regions.len())?;
+        for (index, region) in regions.iter().enumerate() {
+            let (width, height) = region.bbox.size();
+            writeln!(out, "    #{}: x={} y={} width={} height={} ({} pixels, max error {})",
+                index + 1, region.bbox.min.0, region.bbox.min.1, width, height, region.pixel_count, region.max_error)?;
+        }
+    }
+    Ok(())
+}
+
+// Write a text report of the comparisons to the given path, where each of them has all its statistics (as printed in verbose mode, without colors or previews)
+// or the error that prevented it, followed by a tally if there are several comparisons.
+fn write_text_report(path: &str, comparisons: &[batch::Comparison], options: &Options) -> anyhow::Result<()> {
+    let mut text = String::new();
+    for comparison in comparisons {
+        text += &format!("{} vs {}:\n", comparison.image_paths[0], comparison.image_paths[1]);
+        match &comparison.result {
+            Ok(outcome) => write_outcome(&mut text, outcome, None, options, true, false)?,
+            Err(_) if comparison.updated => text += &format!("Created {} from {}\n", comparison.image_paths[0], comparison.image_paths[1]),
+            Err(err) => text += &format!("Error {err:#}\n"),
+        }
+    }
+    if comparisons.len() > 1 {
+        let statuses: Vec<Status> = comparisons.iter().map(Status::from).collect();
+        let matched = statuses.iter().filter(|&&status| status == Status::Match).count();
+        let errors = statuses.iter().filter(|&&status| status >= Status::IoError).count();
+        text += &format!("Compared {} pairs: {} matched, {} mismatched, {} failed.\n", comparisons.len(), matched, comparisons.len() - matched - errors, errors);
+    }
+    fs::write(path, text).context(format!("Failed to write {}", path))
+}
 
-    Ok(!mismatch)
+// Write the report given by --report-file (if any) in its format.
+fn write_report_file(comparisons: &[batch::Comparison], options: &Options) -> Option<anyhow::Result<()>> {
+    options.report_file.as_ref().map(|(path, format)| match format {
+        ReportFormat::Text => write_text_report(path, comparisons, options),
+        ReportFormat::Json => json::write_report(path, comparisons),
+    })
+}
+
+// Return the outcome of the comparison as the single line printed with --porcelain, whose format is stable:
+// the status (match, mismatch, size-mismatch or error), the number of different pixels, the number of compared pixels,
+// the percentage of different pixels with 4 decimals and the path of the saved error image, separated by tabs.
+// The fields that aren't known (e.g. the pixel counts of an error) are "-". A missing baseline is a mismatch.
+fn porcelain_line(comparison: &batch::Comparison) -> String {
+    let status = match Status::from(comparison) {
+        Status::Match => "match",
+        Status::Mismatch | Status::MissingBaseline => "mismatch",
+        Status::SizeMismatch => "size-mismatch",
+        Status::IoError | Status::InvalidArguments => "error",
+    };
+    let counts = match &comparison.result {
+        Ok(outcome) if !outcome.is_size_mismatch() => [outcome.wrong_pixels.to_string(), outcome.total_pixels.to_string(), format!("{:.4}", outcome.wrong_percentage())],
+        _ => ["-".to_string(), "-".to_string(), "-".to_string()],
+    };
+    let output = comparison.output.as_deref().filter(|_| comparison.result.is_ok()).unwrap_or("-");
+    [status, &counts[0], &counts[1], &counts[2], output].join("\t")
 }
 
 fn main() -> anyhow::Result<ExitCode> {
     
     // Parse the commandline arguments
     
-    let args = command!()
+    let command = command!()
         .long_about(
 "imgcmp: a simple pixel-wise image comparator\n
     This tool compares between two images pixel by pixel.\n
     For each pixel, the channels are compared with their counterparts.\n
     If the value error for any channel exceeds the threshold, the whole pixel is considered different.\n
+    Floating-point images (e.g. EXR and HDR) are compared in linear float space, using the threshold as-is.\n
     If the number of different pixels exceeds the specified limit, the result is a mismatch.\n
-    The exit code will be 0 if the images match and -1 if they don't.\n
-    When generating an error image, channels that don't pass the threshold will be kept 0.\n
+    The exit code will be 0 if the images match, 1 if they have different pixels, 2 if they have different sizes,\n
+    3 if an image (or report) couldn't be read, decoded or written, 4 if the arguments are invalid,\n
+    and 5 if the baseline (the first image) is missing with --missing-baseline fail.\n
+    The exit codes of mismatches and errors can be changed with --exit-code-on-mismatch and --exit-code-on-error.\n
+    When generating an error image, channels that don't pass the threshold will be kept 0 (and matching pixels use the error background).\n
     Otherwise the channel's value will be 128 (half intensity) plus half the error value.\n"
         )
-        .arg(arg!([first_image_path] "The path to the first image in the comparison").required(true))
-        .arg(arg!([second_image_path] "The path to the second image in the comparison").required(true))
+        .arg(arg!([first_image_path] "The path to the first image in the comparison (or - to read it from stdin)").required_unless_present_any(["manifest", "pairs-file", "server", "env-path1"]))
+        .arg(arg!([second_image_path] "The path to the second image in the comparison (or - to read it from stdin, or color:#RRGGBB[AA] to compare against a solid color)")
+            .value_parser(parse_second_image_path).required_unless_present_any(["manifest", "pairs-file", "server", "env-path2"]))
+        .arg(arg!(--"env-path1" <VAR> "Reads the path to the first image from the given environment variable if it isn't given as an argument (which takes precedence, with a warning).")
+            .conflicts_with_all(["manifest", "pairs-file", "server"]))
+        .arg(arg!(--"env-path2" <VAR> "Reads the path to the second image from the given environment variable if it isn't given as an argument (which takes precedence, with a warning).")
+            .conflicts_with_all(["manifest", "pairs-file", "server"]))
         .arg(arg!(-t --threshold <THRESHOLD> "Sets a threshold [0-1] on the maximum allowed per-channel error. if 0, any difference passes the threshold. if 1, nothing passes the threshold.")
             .value_parser(value_parser!(f32)).default_value("0"))
-        .arg(arg!(-e --error <ERROR> "Sets the number of pixels allowed to be different before the result is considered a mismatch.")
+        .arg(arg!(--metric <METRIC> "Sets how the pixels are compared: \"pixel\" uses the per-channel error, \"flip\" uses the FLIP perceptual error [0-1] (to which --threshold then applies), \"edges\" compares the Sobel edges of the images, where an edge pixel is different if the closest edge of the other image is farther than --edge-distance, \"gradient\" compares the Sobel gradient magnitudes [0-1] of the luminance of the images (to which --threshold then applies), which finds the edges that got blurrier or sharper, and \"fft\" compares the log-magnitude spectra [0-1] of the images (per channel), where a frequency bin is different if its error exceeds --threshold and --error limits the different bins. With flip and gradient, the error image is the map of their errors, with edges, it shows the edges missing from the second image in red and the extra ones in green, and with fft, it is the map of the different bins with the zero frequency at its center.")
+            .value_parser(|s: &str| Metric::try_from(s)).default_value("pixel"))
+        .arg(arg!(--phash "Compares the perceptual hashes (64-bit fingerprints of their structure) of the images instead of their pixels, which is fast and tolerates small changes, and prints the Hamming distance between them. The images match if their hashes differ in at most --phash-threshold bits. There is no error image.")
+            .action(ArgAction::SetTrue).conflicts_with_all(["output", "preview", "dump-diff-pixels", "sampling"]))
+        .arg(arg!(--"phash-algorithm" <ALGORITHM> "Sets the perceptual hash: \"dhash\" compares the brightness of the neighboring pixels of a 9x8 thumbnail, and \"phash\" compares the lowest frequencies (of the DCT) of a 32x32 thumbnail to their median, which is more robust but slower.")
+            .value_parser(|s: &str| HashAlgorithm::try_from(s)).default_value("dhash").requires("phash"))
+        .arg(arg!(--"phash-threshold" <BITS> "Sets the largest Hamming distance (0-64) between the perceptual hashes under which the images match.")
+            .value_parser(value_parser!(u32).range(0..=64)).default_value("0").requires("phash"))
+        .arg(arg!(--"edge-distance" <PIXELS> "Sets the largest distance allowed between an edge pixel and the closest edge of the other image with the edges metric. The largest distance (truncated at 64 pixels) is the Hausdorff distance between the edges.")
+            .value_parser(value_parser!(f32)).default_value("1"))
+        .arg(arg!(--"luma-weight" <W> "Blends the largest channel difference with the luminance difference using the weight W [0-1] before applying the threshold. 0 compares the channels only, and 1 compares the luminance only.")
+            .value_parser(value_parser!(f32)).default_value("0"))
+        .arg(arg!(--"zscore-threshold" <Z> "Replaces the value threshold by one adapted to the noise of the images: the per-pixel errors (the largest channel error in [0-1]) are computed in a first pass, and a pixel is different if its error exceeds their mean plus Z times their standard deviation. Verbose output prints the mean, the standard deviation and the resulting threshold, and the JSON report has them under \"zscore\". It requires the pixel metric.")
+            .value_parser(value_parser!(f32)).conflicts_with_all(["threshold", "tolerance-map", "luma-weight"]))
+        .arg(arg!(--"tolerance-map" <PATH> "Reads the value threshold of each pixel from a grayscale image of the same size, instead of using the threshold everywhere. 0 means no tolerance and 255 means that the pixel always passes."))
+        .arg(arg!(-e --error <ERROR> "Sets the number (or percentage, e.g. 1%) of pixels allowed to be different before the result is considered a mismatch. A range (e.g. 100..500 or 1%..5%) also requires a minimum number of different pixels; either bound can be omitted.")
             .value_parser(|s: &str| Threshold::try_from(s)).default_value("0"))
-        .arg(arg!(-o --output <OUTPUT> "Outputs the pixel error into an image at the given path."))
+        .arg(arg!(--percentile <P> "Sets the percentile [0-100] of the per-pixel errors (the largest channel error in [0-1], or the FLIP error, gradient error or edge distance with those metrics) that must not exceed --percentile-error. For example, --percentile 99 --percentile-error 0.05 fails if more than 1% of the pixels have an error above 0.05. Verbose output prints the computed percentile.")
+            .value_parser(parse_percentile).requires("percentile-error"))
+        .arg(arg!(--"percentile-error" <V> "Sets the largest value allowed for the percentile of the per-pixel errors given by --percentile.")
+            .value_parser(value_parser!(f32)).requires("percentile"))
+        .arg(arg!(--sample <N> "Compares only the pixels at N random positions instead of all the pixels, and decides the mismatch on the ratio of different samples (the error threshold is scaled to N). This is approximate: a few different pixels can easily be missed. It is meant for quick smoke tests on huge images, and can't be combined with an error image, clusters, tiles or the FLIP and edges metrics.")
+            .value_parser(value_parser!(u32).range(1..)).conflicts_with_all(["output", "preview"]))
+        .arg(arg!(--"sample-rate" <R> "Like --sample, but samples the given fraction (0-1] of the compared pixels instead of a number of them.")
+            .value_parser(parse_sample_rate).conflicts_with_all(["sample", "output", "preview"]))
+        .arg(arg!(--seed <N> "Sets the seed of the random positions sampled by --sample or --sample-rate, which are the same for the same seed. Without it, a random seed is used, which verbose output prints so the run can be reproduced.")
+            .value_parser(value_parser!(u64)).requires("sampling"))
+        .group(ArgGroup::new("sampling").args(["sample", "sample-rate"]))
+        .group(ArgGroup::new("tiling").args(["tile", "tiles"]))
+        .arg(arg!(--"error-histogram" "Computes the histogram of the per-pixel errors (the largest channel difference) of all the compared pixels, including those below the threshold. Verbose output prints it in 16 bins, where the bin of the threshold is marked, and the JSON report has their counts.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"error-histogram-csv" <PATH> "Writes the exact histogram of the per-pixel errors (one bin per 8-bit level) to the given CSV file. Implies --error-histogram.")
+            .conflicts_with_all(["batch", "server"]))
+        .arg(arg!(--"dump-diff-pixels" <PATH> "Writes each different pixel (in row-major order) with the absolute difference of its RGB channels to the given file, as x,y,dr,dg,db lines (or as a JSON array of [x, y, dr, dg, db] arrays with --dump-format json). The pixels are written as they are listed, so the dump is never held in memory. For animated images or multiple pages, each frame has its own file, named like the error images.")
+            .conflicts_with_all(["batch", "server", "sampling"]))
+        .arg(arg!(--"dump-format" <FORMAT> "Sets the format of the dump of the different pixels: csv or json.")
+            .value_parser(|s: &str| dump::Format::try_from(s)).default_value("csv").requires("dump-diff-pixels"))
+        .arg(arg!(--"dump-limit" <N> "Writes at most N pixels to the dump of the different pixels, followed by a marker (a \"# truncated\" line in CSV, or a final {\"truncated\": total} object in JSON). Verbose output tells when the dump was truncated.")
+            .value_parser(value_parser!(u64).range(1..)).requires("dump-diff-pixels"))
+        .arg(arg!(--sweep <SWEEP> "Also computes the outcome under each value threshold from start to end in steps, given as start:end:step on the scale of --threshold (e.g. 0:0.1:0.01), and prints a table of the thresholds, the percentage of different pixels and whether the error threshold allows them (the JSON report has them under \"sweep\"). The images are only compared once, and the exit code still reflects --threshold. It requires the pixel metric, without a luma weight, a tolerance map or a shift tolerance. Implies --error-histogram.")
+            .value_parser(parse_sweep))
+        .arg(arg!(--"suggest-thresholds" "After a mismatch, prints the smallest --threshold under which every pixel would match (with the pixel metric and without a tolerance map), and the smallest --error (as a number of pixels and as a percentage) under which the different pixels would be allowed with the current settings. The JSON report has them under \"suggestions\".")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--simulate <DEFICIENCY> "Transforms both images to how they are seen with a color vision deficiency (protanopia, deuteranopia or tritanopia) before comparing them, using the matrices of Machado et al. (2009) in linear RGB. So the differences that a color-blind viewer can't see are ignored, and the error image shows the simulated differences.")
+            .value_parser(|s: &str| cvd::Deficiency::try_from(s)))
+        .arg(arg!(--"strip-alpha-premultiply" "Treats the images as having premultiplied alpha, and divides their colors by their alpha (clamped to the range of the channels) before comparing them, so the transparent regions are compared by their actual colors. Fully transparent pixels have no color, so they are compared as transparent black: they always match each other.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"alpha-threshold" <ALPHA> "Treats the pixels whose alpha is below ALPHA [1-255] in both images as transparent: they always match, whatever their colors, and they are black in the error image. A pixel that is visible in either image is compared as usual. The unpremultiplication of --strip-alpha-premultiply doesn't change the alpha, so it skips the same pixels, which are those whose colors are amplified the most by the division.")
+            .value_parser(value_parser!(u8).range(1..)))
+        .arg(arg!(-o --output <OUTPUT> "Outputs the pixel error into an image at the given path, or to stdout as a PNG if the path is \"-\" (then everything else is printed to stderr, and it is refused on a terminal without --force). It can be given several times as STYLE=PATH to draw the error image in several diff styles (difference or diff, overlay, raw, colorblind-safe or heatmap) in a single pass, e.g. -o diff=diff.png -o overlay=overlay.png. The style of the first path replaces --diff-style, and it can be omitted. Multiple paths can't be used in a batch run.")
+            .action(ArgAction::Append).value_parser(parse_output))
+        .arg(arg!(--"output-format" <FORMAT> "Sets the format of the error image (png, jpeg, bmp, tiff or webp) regardless of the extension of its path, which it is inferred from by default.")
+            .value_parser(parse_output_format).requires("output"))
+        .arg(arg!(--force "Writes the error image to stdout with -o - even if stdout is a terminal.")
+            .action(ArgAction::SetTrue).requires("output"))
+        .arg(arg!(--"png-compression" <LEVEL> "Sets the compression of PNG error images: fast, default or best (the smallest files, but the slowest to write). It is ignored with a warning for other formats.")
+            .value_parser(parse_png_compression).requires("output"))
+        .arg(arg!(--"jpeg-quality" <QUALITY> "Sets the quality [1-100] of JPEG error images. It is ignored with a warning for other formats.")
+            .value_parser(value_parser!(u8).range(1..=100)).requires("output"))
+        .arg(arg!(--"encode-diff-as-png-metadata" "Embeds the metadata of the comparison in a PNG error image, which is now the default (see --no-metadata). It is kept for compatibility.")
+            .action(ArgAction::SetTrue).requires("output").hide(true))
+        .arg(arg!(--"no-metadata" "Doesn't embed the metadata of the comparison in the error image. By default, a PNG error image carries the version of the tool (Software), the statistics of the comparison (wrong_pixels, total_pixels, wrong_ratio, value_threshold, error_threshold) and the paths of the compared images (expected_image, actual_image) as text chunks. Other formats never carry it.")
+            .action(ArgAction::SetTrue).requires("output").conflicts_with("encode-diff-as-png-metadata"))
+        .arg(arg!(--"output-on-mismatch-only" "Only saves the error image (given by --output) if the images don't match, so passing runs don't write any files. An existing file at the output path is left as is.")
+            .action(ArgAction::SetTrue).requires("output"))
+        .arg(arg!(--"diff-style" <STYLE> "Sets the style of the error image: \"difference\" shows the per-channel error, \"overlay\" draws the different pixels in the highlight color over a faded copy of the first image, \"raw\" shows the per-channel error as is (multiplied by --diff-gain), without the remapping to [128-255] that keeps small errors visible, and \"colorblind-safe\" colors the different pixels by their largest channel error using the Viridis colormap (from teal to yellow), which is readable with common color vision deficiencies.")
+            .visible_alias("error-style").value_parser(|s: &str| DiffStyle::try_from(s)).default_value("difference"))
+        .arg(arg!(--"diff-highlight-color" <COLOR> "Sets the color (R,G,B) of the different pixels in the overlay diff style.")
+            .value_parser(render::parse_color).default_value("255,0,0"))
+        .arg(arg!(--"error-background" <MODE> "Sets how the matching pixels are drawn in the difference diff style: \"black\", \"original\" (the first image at half intensity) or \"white\".")
+            .value_parser(|s: &str| ErrorBackground::try_from(s)).default_value("black"))
+        .arg(arg!(--"diff-only" "Draws an error image for programmatic analysis: the matching pixels are pure black (0,0,0), and the different pixels have the raw absolute difference of their channels (or their FLIP error), without the remapping to [128-255] that keeps small errors visible. So a pixel is different if and only if it isn't black. Float differences are scaled to [0-255] and rounded up.")
+            .action(ArgAction::SetTrue).requires("output").conflicts_with_all(["diff-style", "error-background", "clamp-output", "diff-gain", "draw-bbox", "tile-grid-overlay"]))
+        .arg(arg!(--"clamp-output" <VALUE> "Caps the errors drawn on the error image at VALUE [1-255] (on the scale of 8-bit channels) and stretches them, so an error of VALUE or more is drawn at full intensity and the small errors aren't drowned out by a few extreme ones. The statistics are still computed from the actual errors.")
+            .value_parser(value_parser!(u8).range(1..)).requires("output"))
+        .arg(arg!(--"diff-gain" <FACTOR> "Multiplies the errors drawn on the error image by FACTOR (saturating at 255), so the differences of a few levels are visible. They are remapped to [128-255] afterwards, unless --diff-style is raw. It is purely visual: the statistics and the result are still computed from the actual errors.")
+            .value_parser(parse_diff_gain).requires("output").conflicts_with("clamp-output"))
+        .arg(arg!(--"draw-bbox" "Draws the bounding box of the different pixels on the error image.")
+            .action(ArgAction::SetTrue).requires("output"))
+        .arg(arg!(--clusters "Groups the different pixels into connected regions and reports their bounding boxes and pixel counts.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"max-error-pixel" "Prints the position of the pixel with the largest error and the difference of its RGB channels.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--explain <N> "Prints the N pixels with the largest difference among the compared channels, from the largest one (ties go to the first pixel in row-major order), with their RGB values in both images and the absolute difference of their channels. The JSON report has them under \"worst_pixels\". It can't be combined with --sample.")
+            .value_parser(value_parser!(u32).range(1..)))
+        .arg(arg!(--"region-report" <N> "Reports the N largest regions (clusters) of different pixels with their position, size, pixel count and maximum error.")
+            .value_parser(value_parser!(usize)))
+        .arg(arg!(--connectivity <CONNECTIVITY> "Sets the pixel connectivity (4 or 8) used to group the different pixels into regions.")
+            .value_parser(|s: &str| Connectivity::try_from(s)).default_value("8"))
+        .arg(arg!(--"min-cluster-size" <N> "Discards clusters with fewer than N pixels before deciding whether the images match, so isolated noise (e.g. from dithering) isn't counted. Their pixels are removed from the different pixels, the tiles and the error image.")
+            .visible_alias("min-region").value_parser(value_parser!(u32)))
+        .arg(arg!(--"max-cluster-size" <N> "Considers the result a mismatch if any cluster has more than N pixels.")
+            .value_parser(value_parser!(u32)))
+        .arg(arg!(--tile <SIZE> "Divides the image into SIZExSIZE tiles. The result is a mismatch if any tile exceeds the tile error threshold.")
+            .value_parser(value_parser!(u32).range(1..)).requires("tile-error"))
+        .arg(arg!(--tiles <GRID> "Divides the image into a grid of C columns by R rows of tiles, given as CxR (e.g. 4x3), whose sizes differ by at most one pixel, and reports the tiles with different pixels (their index, bounds and number of different pixels) in verbose output and under \"failed_tiles\" in the JSON report. The result is still decided by --error, unless --tile-error is given: then only the tiles exceeding it are reported, and any of them makes the result a mismatch.")
+            .value_parser(parse_tile_grid).conflicts_with("tile"))
+        .arg(arg!(--"tile-error" <ERROR> "Sets the number (or percentage) of pixels allowed to be different in each tile of --tile or --tiles. Percentages are of the pixels of each tile, so the smaller tiles on the edges get smaller budgets.")
+            .value_parser(|s: &str| Threshold::try_from(s)).requires("tiling"))
+        .arg(arg!(--"tile-size" <SIZE> "Divides the image into tiles of W,H pixels and reports the number of different pixels per tile: verbose output prints them as a grid where the worst tile is highlighted, and the JSON report has them under \"tile_wrong_pixels\" (one array per row of tiles). It doesn't affect the result, and can't be combined with --sample.")
+            .value_parser(parse_tile_size).conflicts_with("sampling"))
+        .arg(arg!(--"tile-grid-overlay" "Draws the lines between the tiles of --tile-size on the error image.")
+            .action(ArgAction::SetTrue).requires_all(["tile-size", "output"]))
+        .arg(arg!(--"report-junit" <PATH> "Writes a JUnit XML report of the comparison to the given path."))
+        .arg(arg!(--"report-html" <PATH> "Writes a self-contained HTML report of the comparisons to the given path: the options, a summary table of the pairs, and a section per failed pair (or for the only pair) with its statistics and the expected, actual and difference images embedded, where a checkbox toggles between the expected and actual images. The difference image is the error image if one is written, or the different pixels drawn over the first image otherwise. A batch report also has a gallery of the difference images of the failed pairs, which link to their sections.")
+            .visible_alias("report"))
+        .arg(arg!(--"report-max-dim" <N> "Downscales the images embedded in the HTML report so their width and height are at most N pixels (keeping their aspect ratio).")
+            .value_parser(value_parser!(u32).range(1..)).requires("report-html"))
+        .arg(arg!(--json <PATH> "Writes a JSON report of the comparisons (with their outcomes, regions and a summary) to the given path."))
+        .arg(arg!(--"report-file" <PATH> "Writes a detailed report of the comparisons to the given path, regardless of the verbosity of the console output: all the statistics printed in verbose mode (in text format), or the same report as --json (in json format)."))
+        .arg(arg!(--format <FORMAT> "Sets the format of the report written by --report-file: \"text\" or \"json\".")
+            .value_parser(|s: &str| ReportFormat::try_from(s)).default_value("text").requires("report-file"))
+        .arg(arg!(--"shift-tolerance" <N> "Considers a pixel matching if it matches any pixel within N pixels (in either direction) in the other image. It is different only if no match is found around it in either image.")
+            .value_parser(value_parser!(u32)).default_value("0"))
+        .arg(arg!(--"ignore-border" <N> "Excludes a frame of N pixels around the image from the comparison (it is drawn dimmed in the error image).")
+            .value_parser(value_parser!(u32)).default_value("0"))
+        .arg(arg!(--align "Aligns the second image to the first one by finding the translation (within the align window) that maximizes their normalized cross-correlation. Only the overlapping region is compared.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"align-window" <N> "Sets the maximum translation (in pixels) searched by --align.")
+            .value_parser(value_parser!(u32)).default_value("8"))
+        .arg(arg!(--normalize "Normalizes the brightness and contrast of the second image to those of the first one before comparing them, by matching the mean and standard deviation of their luminance with a linear transform of the channels, so a uniform brightness or contrast shift isn't counted as a difference. The outcome and the error image then reflect the differences after the normalization.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--pyramid <LEVELS> "Compares the images at up to LEVELS levels of a Gaussian pyramid (the full resolution, then halved at each level down to 8 pixels), from the coarsest one, and fails at the first level that exceeds its error threshold, which is --error scaled to the area of the level. The ignored border, the tile sizes and the tolerance map are scaled to each level too. The coarse levels catch the large structural changes cheaply, and the fine ones the details. Verbose output prints the compared levels and the one that detected the mismatch, and the outcome and the error image are those of that level (or of the full resolution if all the levels match).")
+            .value_parser(value_parser!(u32).range(1..)).conflicts_with_all(["phash", "sampling"]))
+        .arg(arg!(--page <N> "Compares only the Nth page (starting from 0) of multi-page TIFF images, instead of the first one. Other images ignore it with a warning.")
+            .value_parser(value_parser!(usize)))
+        .arg(arg!(--"all-pages" "Compares all the pages of multi-page TIFF images pairwise, like the frames of animated images, where the error images are numbered by page (e.g. diff.0003.png). Images with different numbers of pages are an error, unless --allow-page-count-mismatch is given.")
+            .action(ArgAction::SetTrue).conflicts_with("page"))
+        .arg(arg!(--"tiff-pages" <PAGES> "Sets the pages of multi-page TIFF images to compare: \"all\" (like --all-pages) or the index of a page (like --page).")
+            .value_parser(|s: &str| Pages::try_from(s)).conflicts_with_all(["page", "all-pages"]))
+        .arg(arg!(--"allow-page-count-mismatch" "When all the pages are compared, compares only the pages that exist in both images (with a warning) if they have different numbers of pages, instead of failing with an error.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--frame <N> "Compares only the Nth frame (starting from 0) of animated images (GIF and APNG). Static images ignore it with a warning. By default, if both images are animated, all their frames are compared pairwise, and the error images are numbered (e.g. diff.0003.png).")
+            .value_parser(value_parser!(usize)))
+        .arg(arg!(-c --channel <CHANNEL> "Compares only the given channel (R, G, B or A). Can be repeated to compare several channels. The error image will be grayscale.")
+            .value_parser(parse_channel).action(ArgAction::Append))
+        .arg(arg!(--gha "Reports failures as GitHub Actions annotations and appends a summary to $GITHUB_STEP_SUMMARY. Enabled automatically when GITHUB_ACTIONS=true.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"no-fast-path" "Always decodes and compares the images, even if the files are byte-identical (which otherwise match right away, without writing an error image unless one was requested with --output).")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"no-streaming" "Always decodes TIFF images whole, instead of comparing them band by band (strip by strip, or row of tiles by row of tiles) as they are decoded when no error image is needed.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--progress "Draws a progress bar on stderr while the pixels (or the pairs in batch mode) are compared (the default). It is never drawn if stderr isn't a terminal, with --silent or with --json.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"no-progress" "Never draws the progress bar.")
+            .action(ArgAction::SetTrue).conflicts_with("progress"))
+        .arg(arg!(--color <WHEN> "Colors the results printed on stdout (MATCH in green, MISMATCH in red and different sizes in yellow): \"auto\" colors them only if stdout is a terminal and NO_COLOR isn't set, \"always\" and \"never\" do as they say. The text is the same either way.")
+            .value_parser(|s: &str| color::ColorMode::try_from(s)).default_value("auto"))
+        .arg(arg!(--preview "Prints a text preview of the error image, scaled to the terminal width (or 80 columns), where denser characters mark higher errors.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"exit-code-on-mismatch" <CODE> "Sets the exit code returned when the images have different pixels or sizes (instead of 1 and 2).")
+            .value_parser(value_parser!(u8)))
+        .arg(arg!(--"exit-code-on-error" <CODE> "Sets the exit code returned when the comparison fails due to an I/O or argument error (instead of 3 and 4).")
+            .value_parser(value_parser!(u8)))
+        .arg(arg!(--"expect-mismatch" "Inverts the exit code for negative tests: exits with 0 if the images don't match (in pixels or sizes), and with the exit code of a mismatch if they match. Errors still fail, and the printed messages don't change.")
+            .action(ArgAction::SetTrue).alias("invert-exit-code"))
+        .arg(arg!(--"invert-result" "Inverts the result for negative tests, like --expect-mismatch, and prints \"EXPECTED DIFFERENCE FOUND\" if the images don't match (exiting with 0) or \"UNEXPECTED MATCH\" if they match (exiting with the exit code of a mismatch). The thresholds are applied as usual.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"allow-zero-exit" "Allows 0 as an exit code for mismatches or errors (e.g. for report-only workflows).")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"max-pixels" <N> "Refuses to compare images with more than N pixels (width times height), with an error right after they are decoded, to guard against gigantic untrusted inputs. By default, there is no limit.")
+            .value_parser(value_parser!(u64).range(1..)))
+        .arg(arg!(--"stdin-format" <FORMAT> "Sets the format (e.g. png) of the image read from stdin, instead of guessing it from its content.")
+            .value_parser(parse_image_format))
+        .arg(arg!(--timeout <SECONDS> "Fails the comparison of a pair with an I/O error if it takes more than SECONDS, from reading the images to writing the error image, so a pathological input can't hang a batch run or the server. The pair is then counted as an error (not a mismatch), and the other pairs are compared as usual.")
+            .value_parser(value_parser!(u64).range(1..)))
+        .arg(arg!(--"http-timeout" <SECONDS> "Sets the timeout of each request made to read an image from an http:// or https:// URL (requires the http feature).")
+            .value_parser(value_parser!(u64).range(1..)).default_value("30"))
+        .arg(arg!(--glob "Treats the two image paths as glob patterns (e.g. 'golden/**/*.png'), and compares the files that have the same path relative to the static prefix of their pattern (ignoring the extension) in pairs, using the same options. The output is then a directory where each error image is named after the second image. Implied if both paths have wildcards and don't exist.")
+            .action(ArgAction::SetTrue).conflicts_with_all(["manifest", "pairs-file"]))
+        .arg(arg!(--"allow-unmatched" "Skips (with a warning) the files matched by only one of the glob patterns, which otherwise fail the run.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--manifest <PATH> "Compares the pairs of images listed in the given manifest instead of the two image paths. Each line has the expected and actual image paths followed by optional threshold=, error= and output= overrides (blank lines and # comments are ignored). A .json or .toml manifest lists the pairs as objects instead. The paths are relative to the manifest.")
+            .conflicts_with_all(["first_image_path", "second_image_path"]))
+        .arg(arg!(--"pairs-file" <PATH> "Compares the pairs of images listed in the given file instead of the two image paths, one pair per line, so the pairs don't need matching names. In a .csv file, each line has the expected and actual paths as two fields (which can be quoted), and a first line \"expected,actual\" is a header. In any other file, the paths are separated by a tab (or by whitespace if there is no tab). Blank lines and # comments are ignored, and the paths are relative to the file. The lines that can't be read or parsed are reported with their line numbers and fail the run, but the other pairs are still compared.")
+            .conflicts_with_all(["first_image_path", "second_image_path", "manifest"]))
+        .group(ArgGroup::new("batch").args(["glob", "manifest", "pairs-file"]))
+        .arg(arg!(--"output-manifest" <PATH> "Writes a manifest of the results of a batch run to the given path, with one row per pair: the image paths, the status, the number of different pixels, the path to the error image and the error of the pairs that couldn't be compared. It is a JSON array if the path ends with .json, or a CSV file if it ends with .csv. It is written even if some pairs failed.")
+            .requires("batch"))
+        .arg(arg!(--"report-csv" <PATH> "Writes a CSV report of the results of a batch run to the given path, with a header and one row per pair: the expected and actual paths, the width and height (of the first image), the number of different and compared pixels, the percentage of different pixels, the status and the path to the error image. The fields that aren't known (e.g. for the pairs that couldn't be compared) are empty.")
+            .requires("batch"))
+        .arg(arg!(--"report-append" "Appends the rows of the CSV report to the end of an existing file without writing a second header, so the results of several runs can be tracked in a single file.")
+            .action(ArgAction::SetTrue).requires("report-csv"))
+        .arg(arg!(--"summary-only" "Prints only the final tally and the pairs that didn't match in batch mode, instead of the outcome of every pair.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"fail-fast" "Stops a batch run after the first pair that doesn't match (or fails), instead of comparing all the pairs. The summary and the reports only have the pairs compared so far, and the exit code is that of the failed pair.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--update "Replaces the first image (the baseline) by the second one if they don't match or if the baseline is missing, and exits with 0. Parent directories are created as needed.")
+            .action(ArgAction::SetTrue))
+        .arg(arg!(--"update-on-missing-only" "Only creates missing baselines with --update, and never replaces existing ones.")
+            .action(ArgAction::SetTrue).requires("update"))
+        .arg(arg!(--"missing-baseline" <MODE> "Sets how a missing first image (the baseline) is handled: \"error\" is an I/O error, \"fail\" reports it as a missing baseline (with exit code 5), and \"create\" copies the second image into place and exits with 0. A missing second image is always an error.")
+            .value_parser(|s: &str| MissingBaselineMode::try_from(s)).default_value("error"))
+        .arg(arg!(--"on-size-mismatch" <POLICY> "Sets how images with different sizes are handled: \"error\" is an error (with exit code 4), \"fail\" reports a size mismatch (with exit code 2), and \"resize-first\", \"resize-second\", \"resize-smallest\" or \"resize-largest\" resize the first, second, larger or smaller image (by area) to the size of the other one before comparing them.")
+            .value_parser(|s: &str| SizeMismatchPolicy::try_from(s)).default_value("fail"))
+        .arg(arg!(--benchmark <N> "Runs the whole comparison (including reading the images from disk) N times without saving the error image, and reports the min, max, mean and median wall-clock time and the throughput in megapixels/s. The comparison is then run once more as usual.")
+            .value_parser(value_parser!(u32).range(1..)).conflicts_with_all(["batch", "update"]))
+        .arg(arg!(--watch "Compares the images again whenever they (or the tolerance map) are modified, printing a timestamped result line each time and rewriting the error image and the reports. A modification is only picked up once the files stop changing, so files written incrementally aren't compared halfway. Ctrl-C exits with the exit code of the most recent comparison.")
+            .action(ArgAction::SetTrue).conflicts_with_all(["batch", "benchmark", "update"]))
+        .arg(arg!(--server "Runs as a server that reads comparison requests from stdin, one JSON object per line with \"expected\" and \"actual\" image paths, an optional \"id\" and the optional \"threshold\", \"error\" and \"output\" overrides of a manifest. Each response is written to stdout as a JSON object on a single line, like a comparison of the JSON report (with the id of its request). A request that fails gets an error response without stopping the server, which exits once stdin is closed. The other options apply to every request.")
+            .action(ArgAction::SetTrue).conflicts_with_all(["first_image_path", "second_image_path", "batch", "benchmark", "watch", "update", "porcelain", "preview"]))
+        .arg(arg!(--config <PATH> "Reads the default options from the given TOML file, where the keys are the long flag names (e.g. threshold = 0.02). The options given on the command line override it, and it overrides the built-in defaults. If not given, imgcmp.toml in the current directory (or else ~/.imgcmp.toml) is read if it exists."))
+        .arg(arg!(--porcelain "Prints the outcome as a single tab-separated line with a stable format, for scripts: the status (match, mismatch, size-mismatch or error), the number of different pixels, the number of compared pixels, the percentage of different pixels (with 4 decimals) and the path of the saved error image. Unknown fields are \"-\". Errors are still written to stderr.")
+            .action(ArgAction::SetTrue).conflicts_with_all(["batch", "benchmark", "watch", "update", "silent", "verbose"]))
         .arg(arg!(-s --silent ... "Run in silent mode. No console output will be generated.").action(ArgAction::SetTrue))
         .arg(arg!(-v --verbose ... "Run in verbose mode. Extra console output will be generated.").action(ArgAction::SetTrue))
-        .get_matches();
+        .subcommand(baseline::command())
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .disable_help_subcommand(true)
+;
+
+    // Argument errors get their own exit code, while the help and version are printed as usual.
+    let mut command_line: Vec<OsString> = std::env::args_os().collect();
+    let mut args = match command.clone().try_get_matches_from(&command_line) {
+        Ok(args) => args,
+        Err(err) => {
+            err.print()?;
+            return Ok(ExitCode::from(if err.use_stderr() { EXIT_INVALID_ARGUMENTS } else { EXIT_MATCH }));
+        },
+    };
+
+    // The baseline subcommand either saves a baseline right away, or it is turned into the command line of the comparison it asks for.
+    if let Some(("baseline", baseline_args)) = args.subcommand() {
+        match baseline::run(baseline_args) {
+            Ok(baseline::Action::Saved(baseline_path)) => {
+                println!("Saved {}", baseline_path);
+                return Ok(ExitCode::from(EXIT_MATCH));
+            },
+            Ok(baseline::Action::Compare(comparison_line)) => {
+                command_line.truncate(1);
+                command_line.extend(comparison_line);
+                args = match command.clone().try_get_matches_from(&command_line) {
+                    Ok(args) => args,
+                    Err(err) => {
+                        err.print()?;
+                        return Ok(ExitCode::from(if err.use_stderr() { EXIT_INVALID_ARGUMENTS } else { EXIT_MATCH }));
+                    },
+                };
+            },
+            Err(err) => {
+                writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+                return Ok(ExitCode::from(Status::from(&Err(err)).exit_code()));
+            },
+        }
+    }
+
+    // If a configuration file was given (or imgcmp.toml or ~/.imgcmp.toml exists), its options are inserted before the command line arguments,
+    // and the arguments are parsed again. The options given on the command line are not taken from the configuration.
+    let config_path = args.get_one::<String>("config").cloned()
+        .or_else(|| config::Config::default_path().map(|path| path.to_string_lossy().into_owned()));
+    let args = match config_path {
+        Some(config_path) => {
+            let config_args = config::Config::load(&config_path).and_then(|config| config.to_args(&command, &args));
+            let config_args = match config_args {
+                Ok(config_args) => config_args,
+                Err(err) => {
+                    if !args.get_flag("silent") {
+                        writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+                    }
+                    return Ok(ExitCode::from(Status::from(&Err(err)).exit_code()));
+                },
+            };
+            let mut command_line = command_line.into_iter();
+            let program = command_line.next().into_iter();
+            match command.try_get_matches_from(program.chain(config_args).chain(command_line)) {
+                Ok(args) => args,
+                Err(err) => {
+                    err.print()?;
+                    return Ok(ExitCode::from(EXIT_INVALID_ARGUMENTS));
+                },
+            }
+        },
+        None => args,
+    };
     
     // Get the image paths and options from the arguments.
     
-    let image_paths: Vec<&String> = 
-        ["first_image_path", "second_image_path"].iter()
-        .map(|&name| -> anyhow::Result<&String> {
-            args.get_one::<String>(name).ok_or(anyhow::Error::msg(format!("{} is missing", name)))
-        }).collect::<anyhow::Result<Vec<&String>>>()?;
+    // With a manifest, there are no image paths (and the manifest paths never refer to stdin).
+    // The image paths given as positional arguments take precedence over those read from the environment variables.
+    if !args.get_flag("silent") {
+        for (positional, env_path) in ENV_PATH_ARGS {
+            if let (Some(path), Some(name)) = (args.get_one::<String>(positional), args.get_one::<String>(env_path)) {
+                writeln!(std::io::stderr(), "Warning The image path {} takes precedence over the environment variable {} given by --{}", path, name, env_path).expect("Failed to write Warning");
+            }
+        }
+    }
+    // An error reading them is reported by the parsing of the options.
+    let image_paths = image_path_args(&args);
+    let image_paths: Vec<&String> = image_paths.as_ref().map(|paths| paths.iter().collect()).unwrap_or_default();
+
+    let options = if !image_paths.is_empty() && image_paths.iter().all(|&path| path == STDIN_PATH) {
+        Err(anyhow::Error::msg("Only one of the images can be read from stdin"))
+    } else if args.get_flag("update") && image_paths.iter().any(|&path| path == STDIN_PATH || path.starts_with(COLOR_PREFIX) || http::is_url(path)) {
+        Err(anyhow::Error::msg("--update requires both images to be files"))
+    } else if args.get_flag("watch") && image_paths.iter().any(|&path| path == STDIN_PATH || http::is_url(path)) {
+        Err(anyhow::Error::msg("--watch can't watch images read from stdin or URLs"))
+    } else {
+        Options::try_from(&args)
+    };
+    let options = match options {
+        Ok(options) => options,
+        Err(err) => {
+            if !args.get_flag("silent") {
+                writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+            }
+            return Ok(ExitCode::from(Status::from(&Err(err)).exit_code()));
+        },
+    };
 
-    let options = Options::try_from(&args)?;
+    // In server mode, the pairs of images are read from stdin instead.
+    if options.server {
+        return Ok(ExitCode::from(if server::serve(&options) { EXIT_MATCH } else { EXIT_IO_ERROR }));
+    }
 
-    // Run the comparison and specify an exit code based on the result.
-    // If there was an error durng the comparison, we only print it if the silent flag was not set.
+    // Find the pairs of images to compare: either the two given images, the files matched by the two glob patterns, or the pairs in the manifest.
+    // In batch mode (with --glob or --manifest), the output is a directory, and every pair is compared using the same options
+    // (except for the overrides in the manifest).
 
-    match run([image_paths[0], image_paths[1]], &options) {
-        Ok(same) => {
-            Ok(if same { ExitCode::SUCCESS } else { ExitCode::FAILURE })
-        },
-        Err(err) => {
+    let mut invalid_pairs = 0; // The number of lines of the pairs file that couldn't be read or parsed.
+    let pairs = if options.is_batch() {
+        let pairs = match (&options.manifest, &options.pairs_file) {
+            (Some(manifest_path), _) => manifest::load(manifest_path),
+            // The invalid lines are reported, and they fail the run once the other pairs are compared.
+            (None, Some(pairs_path)) => manifest::load_pairs_file(pairs_path).map(|(pairs, errors)| {
+                for err in &errors {
+                    if options.verbosity > Verbosity::SILENT {
+                        writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+                    }
+                }
+                invalid_pairs = errors.len();
+                pairs
+            }),
+            (None, None) => batch::glob_pairs([image_paths[0], image_paths[1]]).map(|(mut pairs, unmatched)| {
+                // The unmatched files are compared to their missing counterparts, so they fail the run (and appear in the reports).
+                if options.verbosity > Verbosity::SILENT {
+                    for pair in &unmatched {
+                        let (file, missing) = if Path::new(&pair.image_paths[0]).exists() { (&pair.image_paths[0], &pair.image_paths[1]) } else { (&pair.image_paths[1], &pair.image_paths[0]) };
+                        writeln!(std::io::stderr(), "{} Unmatched file {} (no {})", if options.allow_unmatched { "Warning" } else { "Error" }, file, missing).expect("Failed to write Error");
+                    }
+                }
+                if !options.allow_unmatched {
+                    pairs.extend(unmatched);
+                }
+                pairs
+            }),
+        };
+        let pairs = pairs.and_then(|pairs| {
+            if let Some(output_dir) = &options.output {
+                std::fs::create_dir_all(output_dir).context(format!("Failed to create {}", output_dir))?;
+            }
+            Ok(pairs)
+        });
+        match pairs {
+            Ok(pairs) => pairs,
+            Err(err) => {
+                if options.verbosity > Verbosity::SILENT {
+                    writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+                }
+                return Ok(ExitCode::from(options.exit_code(Status::from(&Err(err)))));
+            },
+        }
+    } else {
+        vec![batch::Pair::new([image_paths[0].clone(), image_paths[1].clone()])]
+    };
+
+    // In benchmark mode, the comparison of the (single) pair is timed before it is run as usual.
+    if let Some(iterations) = options.benchmark {
+        let image_paths = &pairs[0].image_paths;
+        let timings = benchmark::Timings::measure(iterations, || {
+            let (outcome, _) = run([&image_paths[0], &image_paths[1]], None, &options.comparison, &options, &mut |_| {})?;
+            Ok(outcome.sizes[0].0 as u64 * outcome.sizes[0].1 as u64)
+        });
+        match timings {
+            Ok(timings) => if options.verbosity > Verbosity::SILENT {
+                println!("{}", timings);
+            },
+            Err(err) => {
+                if options.verbosity > Verbosity::SILENT {
+                    writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+                }
+                return Ok(ExitCode::from(options.exit_code(Status::from(&Err(err)))));
+            },
+        }
+    }
+
+    // In watch mode, the (single) pair is compared again whenever its files change.
+    if options.watch {
+        let image_paths = pairs.into_iter().next().expect("There is a single pair in watch mode").image_paths;
+        return Ok(ExitCode::from(watch_pair(&image_paths, &args, options)));
+    }
+
+    // Run the comparisons.
+    // If there was an error durng a comparison, we only print it if the silent flag was not set.
+
+    let mut comparisons = Vec::with_capacity(pairs.len());
+    let mut progress = progress::ProgressBar::new(options.progress);
+    let pair_count = pairs.len();
+    for (index, pair) in pairs.into_iter().enumerate() {
+        let label = if options.is_batch() { format!("Pair {}/{}", index + 1, pair_count) } else { "Comparing".to_string() };
+        let comparison_options = pair.comparison_options(&options.comparison);
+        let output = match (&pair.output, &options.output) {
+            (Some(output), _) => Some(output.clone()),
+            (None, Some(output_dir)) if options.is_batch() => Some(batch::output_path(output_dir, &pair.image_paths)),
+            (None, output) => output.clone(),
+        };
+        let image_paths = pair.image_paths;
+        // With --summary-only, the outcome of each pair is not printed (only the final tally is).
+        let print_pair = options.verbosity > Verbosity::SILENT && !options.summary_only && !options.porcelain;
+        if options.is_batch() && print_pair {
+            println!("{} vs {}:", image_paths[0], image_paths[1]);
+        }
+        // A missing baseline is reported as such (with --missing-baseline fail) only if the second image can be read,
+        // since a missing or broken candidate is always an error.
+        let baseline_missing = image_paths[0] != STDIN_PATH && !http::is_url(&image_paths[0]) && !Path::new(&image_paths[0]).exists();
+        let result = if baseline_missing && options.missing_baseline == MissingBaselineMode::Fail && options.update.is_none() {
+            read_frames(&image_paths[1], &options).and(Err(anyhow::Error::new(MissingBaseline(image_paths[0].clone()))))
+        } else {
+            // The progress bar is erased before anything is printed.
+            let result = run_with_timeout([&image_paths[0], &image_paths[1]], output.as_ref(), &comparison_options, &options, &mut |fraction| progress.draw(&label, fraction));
+            progress.clear();
+            result.map(|(outcome, error_img)| {
+                if print_pair {
+                    print_outcome(&outcome, error_img.as_ref(), &options);
+                }
+                outcome
+            })
+        };
+
+        // The baseline is only updated after the comparison, so it is never clobbered if the second image couldn't be read.
+        let updated = match update_baseline([&image_paths[0], &image_paths[1]], baseline_missing, &result, &options) {
+            Ok(updated) => updated,
+            Err(err) => {
+                if options.verbosity > Verbosity::SILENT {
+                    writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+                }
+                false
+            },
+        };
+        if let (Err(err), false) = (&result, updated) {
             if options.verbosity > Verbosity::SILENT {
                 writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
             }
-            Ok(ExitCode::FAILURE)
-        },
+        }
+        // The reports only link the error images that were saved.
+        let output = output.filter(|_| !(options.output_on_mismatch_only && matches!(&result, Ok(outcome) if outcome.matched)));
+        let comparison = batch::Comparison { image_paths, output, result, updated };
+        if options.porcelain {
+            println!("{}", porcelain_line(&comparison));
+        }
+
+        // Report the failures to GitHub Actions. Failing to write the job summary should not fail the comparison.
+        // The annotations are printed on stdout, so they are skipped if it carries the error image.
+        if options.github_actions {
+            if options.verbosity > Verbosity::SILENT && !options.porcelain && !options.output_to_stdout() {
+                gha::print_annotation(&comparison);
+            }
+            if let Err(err) = gha::write_step_summary(&comparison) {
+                if options.verbosity > Verbosity::SILENT {
+                    writeln!(std::io::stderr(), "Warning {err:?}").expect("Failed to write Warning");
+                }
+            }
+        }
+        let failed = options.expected_status(Status::from(&comparison)) != Status::Match;
+        comparisons.push(comparison);
+
+        // With --fail-fast, the remaining pairs are skipped after the first failure.
+        if options.fail_fast && failed {
+            let skipped = pair_count - index - 1;
+            if skipped > 0 && options.verbosity > Verbosity::SILENT && !options.porcelain {
+                println!("Stopped after the first failure (--fail-fast), skipping {} pair{}.", skipped, if skipped == 1 { "" } else { "s" });
+            }
+            break;
+        }
+    }
+
+    let mut status = comparisons.iter().map(Status::from).max().unwrap_or(Status::Match);
+    if invalid_pairs > 0 {
+        status = status.max(Status::InvalidArguments);
     }
-}
\ No newline at end of file
+    if options.is_batch() && options.verbosity > Verbosity::SILENT {
+        let matched = comparisons.iter().filter(|&comparison| Status::from(comparison) == Status::Match).count();
+        let errors = comparisons.iter().filter(|&comparison| Status::from(comparison) >= Status::IoError).count();
+        println!("Compared {} pairs: {} matched, {} mismatched, {} failed.", comparisons.len(), matched, comparisons.len() - matched - errors, errors);
+        if invalid_pairs > 0 {
+            println!("Skipped {} invalid lines of {}.", invalid_pairs, options.pairs_file.as_deref().unwrap_or_default());
+        }
+        if options.summary_only {
+            for comparison in &comparisons {
+                let status = Status::from(comparison);
+                if status != Status::Match {
+                    println!("    {}: {} vs {}", options.paint_status(status), comparison.image_paths[0], comparison.image_paths[1]);
+                }
+            }
+        } else if options.manifest.is_some() || options.pairs_file.is_some() {
+            // A manifest (or pairs file) run ends with a table of all the pairs.
+            println!("{:<16}  {:>10}  PAIR", "STATUS", "DIFFERENT");
+            for comparison in &comparisons {
+                let different = match &comparison.result {
+                    Ok(outcome) if !comparison.updated && !outcome.is_size_mismatch() => format!("{:.3}%", outcome.wrong_percentage()),
+                    _ => "-".to_string(),
+                };
+                // The label is padded before it is colored, so the escape codes don't break the alignment.
+                let status = Status::from(comparison);
+                let label = color::paint(&format!("{:<16}", status.label()), status.color(), options.color);
+                println!("{}  {:>10}  {} vs {}", label, different, comparison.image_paths[0], comparison.image_paths[1]);
+            }
+        }
+    }
+
+    // Write the reports (if requested) regardless of whether the comparisons succeeded.
+    let reports = [
+        options.junit_report.as_ref().map(|path| junit::write_report(path, &comparisons)),
+        options.json_report.as_ref().map(|path| json::write_report(path, &comparisons)),
+        options.html_report.as_ref().map(|(path, max_dim)| html::write_report(path, &comparisons, &options.comparison, *max_dim)),
+        write_report_file(&comparisons, &options),
+        options.output_manifest.as_ref().map(|(path, format)| output_manifest::write(path, *format, &comparisons)),
+        options.csv_report.as_ref().map(|(path, append)| csv::write_report(path, &comparisons, *append)),
+    ];
+    for result in reports.into_iter().flatten() {
+        if let Err(err) = result {
+            if options.verbosity > Verbosity::SILENT {
+                writeln!(std::io::stderr(), "Error {err:?}").expect("Failed to write Error");
+            }
+            status = status.max(Status::from(&Err(err)));
+        }
+    }
+
+    // With --expect-mismatch, only the exit code is inverted.
+    Ok(ExitCode::from(options.exit_code(options.expected_status(status))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Return the comparison of a gray 4x4 image with an image of the given size where the given number of pixels are white.
+    fn comparison(size: (u32, u32), changed: u32, output: Option<&str>) -> batch::Comparison {
+        let first = image::RgbaImage::from_pixel(4, 4, image::Rgba([128, 128, 128, 255]));
+        let mut second = image::RgbaImage::from_pixel(size.0, size.1, image::Rgba([128, 128, 128, 255]));
+        for index in 0..changed {
+            second.put_pixel(index % size.0, index / size.0, image::Rgba([255, 255, 255, 255]));
+        }
+        let images = ImagePair::new(DynamicImage::ImageRgba8(first), DynamicImage::ImageRgba8(second));
+        let (outcome, _) = imgcmp::compare(&images, &ComparisonOptions::default()).unwrap();
+        batch::Comparison { image_paths: ["a.png".to_string(), "b.png".to_string()], output: output.map(str::to_string), result: Ok(outcome), updated: false }
+    }
+
+    #[test]
+    fn porcelain_line_of_a_match() {
+        assert_eq!(porcelain_line(&comparison((4, 4), 0, None)), "match\t0\t16\t0.0000\t-");
+    }
+
+    #[test]
+    fn porcelain_line_of_a_mismatch() {
+        assert_eq!(porcelain_line(&comparison((4, 4), 3, Some("diff.png"))), "mismatch\t3\t16\t18.7500\tdiff.png");
+    }
+
+    #[test]
+    fn porcelain_line_of_a_size_mismatch() {
+        assert_eq!(porcelain_line(&comparison((4, 5), 0, Some("diff.png"))), "size-mismatch\t-\t-\t-\tdiff.png");
+    }
+
+    #[test]
+    fn porcelain_line_of_an_error() {
+        let error = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::NotFound));
+        let comparison = batch::Comparison { image_paths: ["a.png".to_string(), "b.png".to_string()], output: Some("diff.png".to_string()), result: Err(error), updated: false };
+        assert_eq!(porcelain_line(&comparison), "error\t-\t-\t-\t-");
+    }
+}
@@ -0,0 +1,161 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use imgcmp::Threshold;
+
+use crate::batch::Pair;
+
+// A pair of images as written in a JSON or TOML manifest.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    expected: String,
+    actual: String,
+    threshold: Option<f32>,
+    error: Option<String>,
+    output: Option<String>,
+}
+
+// A TOML manifest, where the pairs are written as `[[pairs]]` tables.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlManifest {
+    pairs: Vec<Entry>,
+}
+
+// Resolve a path written in the manifest relative to the directory of the manifest.
+fn resolve(directory: &Path, path: &str) -> String {
+    directory.join(path).to_string_lossy().into_owned()
+}
+
+impl Entry {
+    // Convert the entry into a pair whose paths are relative to the given directory.
+    fn into_pair(self, directory: &Path) -> anyhow::Result<Pair> {
+        Ok(Pair {
+            image_paths: [resolve(directory, &self.expected), resolve(directory, &self.actual)],
+            threshold: self.threshold,
+            error: self.error.as_deref().map(Threshold::try_from).transpose()?,
+            output: self.output.map(|output| resolve(directory, &output)),
+        })
+    }
+}
+
+// Parse a line of a line-based manifest: the expected and actual image paths followed by optional overrides
+// written as key=value (threshold, error or output), all separated by whitespace.
+fn parse_line(line: &str, directory: &Path) -> anyhow::Result<Pair> {
+    let mut words = line.split_whitespace();
+    let (Some(expected), Some(actual)) = (words.next(), words.next()) else {
+        return Err(anyhow::Error::msg("Expected the paths to the expected and actual images"));
+    };
+    let mut entry = Entry { expected: expected.to_string(), actual: actual.to_string(), threshold: None, error: None, output: None };
+    for word in words {
+        match word.split_once('=') {
+            Some(("threshold", value)) => entry.threshold = Some(value.parse().context(format!("Invalid threshold \"{}\"", value))?),
+            Some(("error", value)) => entry.error = Some(value.to_string()),
+            Some(("output", value)) => entry.output = Some(value.to_string()),
+            _ => return Err(anyhow::Error::msg(format!("Invalid override \"{}\" (expected threshold=, error= or output=)", word))),
+        }
+    }
+    entry.into_pair(directory)
+}
+
+// Read the pairs listed in a manifest file. A .json manifest is an array of objects and a .toml manifest has an array of `[[pairs]]` tables,
+// where each pair has `expected` and `actual` paths and optional `threshold`, `error` and `output` overrides.
+// Any other manifest has a pair per line (see `parse_line`), where blank lines and lines starting with # are ignored.
+// The paths in the manifest are relative to its directory.
+pub fn load(path: &str) -> anyhow::Result<Vec<Pair>> {
+    let text = fs::read_to_string(path).context(format!("Failed to read {}", path))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let pairs = match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+        Some("json") => {
+            let entries: Vec<Entry> = serde_json::from_str(&text).context(format!("Failed to parse {}", path))?;
+            entries.into_iter().map(|entry| entry.into_pair(directory)).collect::<anyhow::Result<Vec<Pair>>>()
+                .context(format!("Invalid pair in {}", path))?
+        },
+        Some("toml") => {
+            let manifest: TomlManifest = toml::from_str(&text).context(format!("Failed to parse {}", path))?;
+            manifest.pairs.into_iter().map(|entry| entry.into_pair(directory)).collect::<anyhow::Result<Vec<Pair>>>()
+                .context(format!("Invalid pair in {}", path))?
+        },
+        _ => text.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(index, line)| parse_line(line, directory).context(format!("Invalid pair at line {} of {}", index + 1, path)))
+            .collect::<anyhow::Result<Vec<Pair>>>()?,
+    };
+
+    if pairs.is_empty() {
+        return Err(anyhow::Error::msg(format!("The manifest {} doesn't list any pairs", path)));
+    }
+    Ok(pairs)
+}
+
+// Split a line of a CSV file into its fields, where quoted fields can contain separators and doubled quotes.
+fn csv_fields(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("There is always a field");
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => { field.push('"'); chars.next(); },
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow::Error::msg("Unterminated quoted field"));
+    }
+    Ok(fields)
+}
+
+// Parse a line of a pairs file into the expected and actual image paths: two CSV fields in a .csv file,
+// or two paths separated by a tab (or by whitespace if there is no tab) in any other file.
+fn parse_pair_line(line: &str, csv: bool) -> anyhow::Result<[String; 2]> {
+    let fields: Vec<String> = if csv {
+        csv_fields(line)?
+    } else if line.contains('\t') {
+        line.split('\t').map(str::to_string).collect()
+    } else {
+        line.split_whitespace().map(str::to_string).collect()
+    };
+    match <[String; 2]>::try_from(fields) {
+        Ok(paths) if paths.iter().all(|path| !path.trim().is_empty()) => Ok(paths.map(|path| path.trim().to_string())),
+        _ => Err(anyhow::Error::msg("Expected the paths to the expected and actual images")),
+    }
+}
+
+// Read the pairs of image paths listed in a pairs file, one pair per line (see `parse_pair_line`), where blank lines and lines starting with #
+// are ignored, and so is a first line of a CSV file that is the header "expected,actual". The paths are relative to the directory of the file.
+// Return the pairs along with the errors of the lines that couldn't be read or parsed (which name their line numbers),
+// or an error if the file can't be read or lists no pairs at all.
+pub fn load_pairs_file(path: &str) -> anyhow::Result<(Vec<Pair>, Vec<anyhow::Error>)> {
+    let bytes = fs::read(path).context(format!("Failed to read {}", path))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let csv = Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+
+    let (mut pairs, mut errors) = (Vec::new(), Vec::new());
+    for (index, line) in bytes.split(|&byte| byte == b'\n').enumerate() {
+        let context = || format!("Invalid pair at line {} of {}", index + 1, path);
+        let line = match std::str::from_utf8(line) {
+            Ok(line) => line.trim_end_matches('\r'),
+            Err(err) => { errors.push(anyhow::Error::new(err).context(context())); continue; },
+        };
+        if line.trim().is_empty() || line.trim_start().starts_with('#') || (csv && index == 0 && line.trim().eq_ignore_ascii_case("expected,actual")) {
+            continue;
+        }
+        match parse_pair_line(line, csv) {
+            Ok([expected, actual]) => pairs.push(Pair::new([resolve(directory, &expected), resolve(directory, &actual)])),
+            Err(err) => errors.push(err.context(context())),
+        }
+    }
+
+    if pairs.is_empty() && errors.is_empty() {
+        return Err(anyhow::Error::msg(format!("The pairs file {} doesn't list any pairs", path)));
+    }
+    Ok((pairs, errors))
+}
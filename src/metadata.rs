@@ -0,0 +1,91 @@
+use std::{fs::File, io::{BufWriter, Write}};
+
+use anyhow::Context;
+use image::DynamicImage;
+
+use imgcmp::{ComparisonOptions, ComparisonOutcome};
+
+// Return the version of the tool and the statistics of the comparison that are embedded in its error image as key-value pairs.
+pub fn comparison_entries(image_paths: [&String; 2], outcome: &ComparisonOutcome, options: &ComparisonOptions) -> Vec<(&'static str, String)> {
+    let wrong_ratio = if outcome.total_pixels == 0 { 0f32 } else { outcome.wrong_pixels as f32 / outcome.total_pixels as f32 };
+    vec![
+        ("Software", format!("{} {}", clap::crate_name!(), clap::crate_version!())),
+        ("wrong_pixels", outcome.wrong_pixels.to_string()),
+        ("total_pixels", outcome.total_pixels.to_string()),
+        ("wrong_ratio", wrong_ratio.to_string()),
+        ("value_threshold", options.float_value_threshold.to_string()),
+        ("error_threshold", options.error_threshold.to_string()),
+        ("expected_image", image_paths[0].clone()),
+        ("actual_image", image_paths[1].clone()),
+    ]
+}
+
+// Save the image as a PNG (using the png encoder directly) with the given compression, with the given key-value pairs written as text chunks.
+// The values are written as iTXt (UTF-8) chunks if they aren't plain ASCII (e.g. non-ASCII image paths), and as tEXt chunks otherwise.
+pub fn save_png(path: &str, img: &DynamicImage, entries: &[(&str, String)], compression: png::Compression) -> anyhow::Result<()> {
+    let file = File::create(path).context(format!("Failed to write {}", path))?;
+    write_png(BufWriter::new(file), path, img, entries, compression)
+}
+
+// Encode the image as a PNG into the writer like `save_png`, where the name of the destination is only used in the errors.
+pub fn write_png(writer: impl Write, name: &str, img: &DynamicImage, entries: &[(&str, String)], compression: png::Compression) -> anyhow::Result<()> {
+    // The error image is saved with 8 bits per channel.
+    let (color, data) = match img {
+        DynamicImage::ImageLuma8(img) => (png::ColorType::Grayscale, img.as_raw().clone()),
+        DynamicImage::ImageLumaA8(img) => (png::ColorType::GrayscaleAlpha, img.as_raw().clone()),
+        DynamicImage::ImageRgb8(img) => (png::ColorType::Rgb, img.as_raw().clone()),
+        img => (png::ColorType::Rgba, img.to_rgba8().into_raw()),
+    };
+
+    let mut encoder = png::Encoder::new(writer, img.width(), img.height());
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression);
+    for (key, value) in entries {
+        match value.is_ascii() {
+            true => encoder.add_text_chunk(key.to_string(), value.clone()),
+            false => encoder.add_itxt_chunk(key.to_string(), value.clone()),
+        }.map_err(std::io::Error::other).context(format!("Failed to write {}", name))?;
+    }
+    // The encoding errors are I/O errors, so they are reported like any image that couldn't be written.
+    let mut writer = encoder.write_header().map_err(std::io::Error::other).context(format!("Failed to write {}", name))?;
+    writer.write_image_data(&data).map_err(std::io::Error::other).context(format!("Failed to write {}", name))?;
+    writer.finish().map_err(std::io::Error::other).context(format!("Failed to write {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use imgcmp::ImagePair;
+
+    #[test]
+    fn metadata_round_trips_through_the_text_chunks() {
+        let first = image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 0, 255]));
+        let mut second = first.clone();
+        second.put_pixel(1, 2, image::Rgba([255, 0, 0, 255]));
+        let options = ComparisonOptions::default();
+        let (outcome, error_img) = imgcmp::compare(&ImagePair::new(DynamicImage::ImageRgba8(first), DynamicImage::ImageRgba8(second)), &options).unwrap();
+        let paths = ["expected/plain.png".to_string(), "actual/café.png".to_string()];
+        let entries = comparison_entries([&paths[0], &paths[1]], &outcome, &options);
+
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, "memory", &error_img.unwrap(), &entries, png::Compression::Fast).unwrap();
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        let info = reader.info();
+
+        // The ASCII values are tEXt chunks, and the non-ASCII path is an iTXt chunk.
+        let mut read: Vec<(String, String)> = info.uncompressed_latin1_text.iter().map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+            .chain(info.utf8_text.iter().map(|chunk| (chunk.keyword.clone(), chunk.get_text().unwrap())))
+            .collect();
+        read.sort();
+        let mut expected: Vec<(String, String)> = entries.iter().map(|(key, value)| (key.to_string(), value.clone())).collect();
+        expected.sort();
+        assert_eq!(read, expected);
+        assert_eq!(info.utf8_text.len(), 1);
+        assert_eq!(info.utf8_text[0].keyword, "actual_image");
+        assert!(read.contains(&("wrong_pixels".to_string(), "1".to_string())));
+    }
+}
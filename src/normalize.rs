@@ -0,0 +1,88 @@
+// Normalization of the brightness and contrast of the second image to those of the first one,
+// so a uniform difference (e.g. from the calibration of the monitors the screenshots were taken on) isn't counted as a difference.
+
+use crate::{ImagePair, luma};
+
+// The linear transform applied to the color channels of the second image (in [0-255] for 8-bit images, and as-is for float images).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    pub gain: f32,
+    pub offset: f32,
+}
+
+// The standard deviation of the luminance below which an image is considered uniform.
+const MIN_DEVIATION: f64 = 1e-6;
+
+// Return the mean and standard deviation of the luminance of the pixels.
+fn luminance_stats(pixels: impl Iterator<Item = [f32; 3]>) -> (f64, f64) {
+    let (mut sum, mut sum_squares, mut count) = (0f64, 0f64, 0f64);
+    for [r, g, b] in pixels {
+        let value = luma(r, g, b) as f64;
+        sum += value;
+        sum_squares += value * value;
+        count += 1f64;
+    }
+    if count == 0f64 {
+        return (0f64, 0f64);
+    }
+    let mean = sum / count;
+    (mean, (sum_squares / count - mean * mean).max(0f64).sqrt())
+}
+
+// Return the transform that gives the luminance of the second image the mean and standard deviation of the luminance of the first one.
+// Since the luminance is a weighted average of the channels, applying it to each channel transforms the luminance the same way.
+// If either image is (almost) uniform, only the mean is matched, since scaling the second image to a uniform first one would flatten it.
+fn normalization(stats: [(f64, f64); 2]) -> Normalization {
+    let [(mean1, deviation1), (mean2, deviation2)] = stats;
+    let gain = if deviation1 > MIN_DEVIATION && deviation2 > MIN_DEVIATION { deviation1 / deviation2 } else { 1f64 };
+    Normalization { gain: gain as f32, offset: (mean1 - gain * mean2) as f32 }
+}
+
+// Return the images with the brightness and contrast of the second one normalized to those of the first one (see `normalization`),
+// along with the applied transform. The channels of 8-bit images are clamped to [0-255], and the alpha channel is kept.
+pub fn normalize(images: &ImagePair) -> (ImagePair, Normalization) {
+    match images {
+        ImagePair::Integer(img1, img2) => {
+            let rgb = |pixel: &image::Rgba<u8>| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+            let transform = normalization([img1, img2].map(|image| luminance_stats(image.pixels().map(rgb))));
+            let mut normalized = img2.clone();
+            for pixel in normalized.pixels_mut() {
+                for channel in 0..3 {
+                    pixel[channel] = (pixel[channel] as f32 * transform.gain + transform.offset).round().clamp(0f32, 255f32) as u8;
+                }
+            }
+            (ImagePair::Integer(img1.clone(), normalized), transform)
+        },
+        ImagePair::Float(img1, img2) => {
+            let rgb = |pixel: &image::Rgba<f32>| [pixel[0], pixel[1], pixel[2]];
+            let transform = normalization([img1, img2].map(|image| luminance_stats(image.pixels().map(rgb))));
+            let mut normalized = img2.clone();
+            for pixel in normalized.pixels_mut() {
+                for channel in 0..3 {
+                    pixel[channel] = pixel[channel] * transform.gain + transform.offset;
+                }
+            }
+            (ImagePair::Float(img1.clone(), normalized), transform)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageBuffer, Rgba};
+
+    use crate::{ComparisonOptions, ImagePair, compare};
+
+    #[test]
+    fn uniform_image_still_differs_from_a_textured_one() {
+        let uniform = ImageBuffer::from_pixel(4, 4, Rgba([128u8, 128, 128, 255]));
+        let textured = ImageBuffer::from_fn(4, 4, |x, y| if (x + y) % 2 == 0 { Rgba([0u8, 0, 0, 255]) } else { Rgba([255u8, 255, 255, 255]) });
+        let images = ImagePair::new(DynamicImage::ImageRgba8(uniform), DynamicImage::ImageRgba8(textured));
+        let options = ComparisonOptions { normalize: true, ..ComparisonOptions::default() };
+        let outcome = compare(&images, &options).unwrap().0;
+        let normalization = outcome.normalization.unwrap();
+        assert_eq!(normalization.gain, 1f32);
+        assert!(!outcome.matched);
+        assert_eq!(outcome.wrong_pixels, 16);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{Status, batch::Comparison};
+
+// The format of an output manifest, which is inferred from the extension of its path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    // Return the format named by the extension of the path (json or csv), or an error if the extension doesn't name any.
+    pub fn from_path(path: &str) -> anyhow::Result<Self> {
+        match Path::new(path).extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).as_deref() {
+            Some("json") => Ok(Format::Json),
+            Some("csv") => Ok(Format::Csv),
+            _ => Err(anyhow::Error::msg(format!("Can't infer the format of the output manifest {} (expected a .json or .csv extension)", path))),
+        }
+    }
+}
+
+// A row of the output manifest: the outcome of a pair in a batch run.
+// The number of different pixels is only known if the images could be compared and have the same size.
+#[derive(Serialize)]
+struct Row<'a> {
+    expected: &'a str,
+    actual: &'a str,
+    status: &'static str,
+    wrong_pixels: Option<u32>,
+    output: Option<&'a str>,
+    error: Option<String>,
+}
+
+fn row(comparison: &Comparison) -> Row<'_> {
+    Row {
+        expected: &comparison.image_paths[0],
+        actual: &comparison.image_paths[1],
+        status: Status::from(comparison).name(),
+        wrong_pixels: comparison.result.as_ref().ok().filter(|outcome| !comparison.updated && !outcome.is_size_mismatch()).map(|outcome| outcome.wrong_pixels),
+        output: comparison.output.as_deref(),
+        error: comparison.result.as_ref().err().filter(|_| !comparison.updated).map(|err| format!("{err:#}")),
+    }
+}
+
+// Quote a CSV field if it contains a separator, a quote or a line break (doubling its quotes).
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// Write the output manifest of a batch run, with one row per compared pair (including those that couldn't be compared),
+// as a JSON array of objects or as a CSV file with a header, where the unknown fields are null or empty.
+pub fn write(path: &str, format: Format, comparisons: &[Comparison]) -> anyhow::Result<()> {
+    let rows: Vec<Row> = comparisons.iter().map(row).collect();
+    let text = match format {
+        Format::Json => serde_json::to_string_pretty(&rows)? + "\n",
+        Format::Csv => {
+            let mut text = String::from("expected,actual,status,wrong_pixels,output,error\n");
+            for row in &rows {
+                let fields = [
+                    csv_field(row.expected),
+                    csv_field(row.actual),
+                    row.status.to_string(),
+                    row.wrong_pixels.map(|count| count.to_string()).unwrap_or_default(),
+                    row.output.map(csv_field).unwrap_or_default(),
+                    row.error.as_deref().map(csv_field).unwrap_or_default(),
+                ];
+                text += &(fields.join(",") + "\n");
+            }
+            text
+        },
+    };
+    fs::write(path, text).context(format!("Failed to write {}", path))
+}
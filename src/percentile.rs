@@ -0,0 +1,47 @@
+// Collects the per-pixel errors of a comparison to find a percentile of them.
+pub enum ErrorDistribution {
+    Histogram(Vec<u64>, u64),   // The counts of the 8-bit errors (the multiples of 1/255), for which 256 bins are exact, and their total.
+    Values(Vec<f32>),           // The errors themselves (for float images, FLIP or edge errors), which take 4 bytes per pixel.
+}
+
+impl ErrorDistribution {
+    // Create an empty distribution, which is a histogram if the errors are all multiples of 1/255 (as with 8-bit images).
+    pub fn new(eight_bit: bool) -> Self {
+        if eight_bit { ErrorDistribution::Histogram(vec![0; 256], 0) } else { ErrorDistribution::Values(Vec::new()) }
+    }
+
+    // Add the error of a pixel.
+    pub fn add(&mut self, error: f32) {
+        match self {
+            ErrorDistribution::Histogram(counts, total) => {
+                counts[(error * 255f32).round().clamp(0f32, 255f32) as usize] += 1;
+                *total += 1;
+            },
+            ErrorDistribution::Values(values) => values.push(error),
+        }
+    }
+
+    // Return the P-th percentile [0-100] of the errors using the nearest rank: the smallest error that is at least as large as P% of the errors
+    // (the smallest error for P = 0). Return None if there are no errors.
+    pub fn percentile(self, percentile: f32) -> Option<f32> {
+        let count = match &self {
+            ErrorDistribution::Histogram(_, total) => *total,
+            ErrorDistribution::Values(values) => values.len() as u64,
+        };
+        if count == 0 {
+            return None;
+        }
+        // The (0-based) rank of the percentile among the sorted errors.
+        let rank = ((percentile.clamp(0f32, 100f32) as f64 / 100f64 * count as f64).ceil() as u64).clamp(1, count) - 1;
+        match self {
+            ErrorDistribution::Histogram(counts, _) => {
+                let mut seen = 0;
+                counts.iter().position(|&bin| { seen += bin; seen > rank }).map(|level| level as f32 / 255f32)
+            },
+            ErrorDistribution::Values(mut values) => {
+                let (_, value, _) = values.select_nth_unstable_by(rank as usize, f32::total_cmp);
+                Some(*value)
+            },
+        }
+    }
+}
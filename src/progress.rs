@@ -0,0 +1,44 @@
+use std::io::Write;
+
+// The number of characters in the bar (excluding the label and the percentage).
+const BAR_WIDTH: usize = 30;
+
+// A progress bar drawn in place on stderr (so stdout only has the results), which does nothing if it is disabled.
+pub struct ProgressBar {
+    enabled: bool,
+    drawn: Option<u32>, // The percentage shown by the bar on screen (None if it isn't shown).
+}
+
+impl ProgressBar {
+    pub fn new(enabled: bool) -> ProgressBar {
+        ProgressBar { enabled, drawn: None }
+    }
+
+    // Draw the bar with the given label and completed fraction [0-1], unless the shown percentage wouldn't change.
+    // Once complete, the bar is erased, so whatever is printed next starts on a clean line.
+    pub fn draw(&mut self, label: &str, fraction: f32) {
+        if !self.enabled {
+            return;
+        }
+        if fraction >= 1f32 {
+            self.clear();
+            return;
+        }
+        let percentage = (fraction.max(0f32) * 100f32) as u32;
+        if self.drawn == Some(percentage) {
+            return;
+        }
+        let filled = BAR_WIDTH * percentage as usize / 100;
+        let mut stderr = std::io::stderr().lock();
+        write!(stderr, "\r{} [{}{}] {:>3}%", label, "#".repeat(filled), ".".repeat(BAR_WIDTH - filled), percentage).expect("Failed to write progress");
+        stderr.flush().expect("Failed to write progress");
+        self.drawn = Some(percentage);
+    }
+
+    // Erase the bar if it is shown.
+    pub fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            write!(std::io::stderr(), "\r\x1b[2K").expect("Failed to write progress");
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// A multi-resolution comparison over a Gaussian pyramid of the images, where each level is the previous one blurred and downscaled by half.
+// The coarse levels are cheap to compare and catch the large structural changes, while the finer ones catch the details,
+// so the levels are compared from the coarsest one and the comparison stops at the first level that fails.
+// The options given in pixels (the error threshold, the ignored border, the tiles and the tolerance map) are scaled to each level.
+
+use image::imageops::{self, FilterType};
+
+use crate::{ComparisonOptions, ImagePair, Threshold, tiles::TileLayout};
+
+// The smallest dimension of a level, below which the pyramid stops even if it has fewer levels than requested.
+pub const MIN_LEVEL_DIM: u32 = 8;
+
+// The result of comparing a level of the pyramid.
+#[derive(Debug, Clone, Copy)]
+pub struct PyramidLevel {
+    pub level: u32,             // The index of the level (0 is the full resolution, and each level halves the previous one).
+    pub size: (u32, u32),       // The size of the images at this level.
+    pub wrong_pixels: u32,      // The number of different pixels at this level.
+    pub total_pixels: u32,      // The number of compared pixels at this level.
+    pub matched: bool,          // True if the level is within its error threshold (see `level_threshold`).
+}
+
+// The levels compared by a pyramid comparison, from the coarsest one.
+#[derive(Debug, Clone)]
+pub struct PyramidOutcome {
+    pub levels: Vec<PyramidLevel>, // The compared levels, which stop at the first one that failed.
+    pub mismatch_level: Option<u32>, // The level that first detected a mismatch (None if all the levels matched).
+}
+
+// Halve the images (rounding down) with a Gaussian filter, which blurs them before they are downsampled.
+fn downscale(images: &ImagePair) -> ImagePair {
+    let (width, height) = images.dimensions()[0];
+    match images {
+        ImagePair::Integer(img1, img2) => ImagePair::Integer(
+            imageops::resize(img1, width / 2, height / 2, FilterType::Gaussian),
+            imageops::resize(img2, width / 2, height / 2, FilterType::Gaussian),
+        ),
+        ImagePair::Float(img1, img2) => ImagePair::Float(
+            imageops::resize(img1, width / 2, height / 2, FilterType::Gaussian),
+            imageops::resize(img2, width / 2, height / 2, FilterType::Gaussian),
+        ),
+    }
+}
+
+// Return the levels of the pyramid of the images (of the same size) from the full resolution, with up to the given number of levels,
+// stopping before a level would be smaller than `MIN_LEVEL_DIM` in either dimension.
+pub fn build(images: &ImagePair, levels: u32) -> Vec<ImagePair> {
+    let mut pyramid = vec![images.clone()];
+    while (pyramid.len() as u32) < levels {
+        let (width, height) = pyramid[pyramid.len() - 1].dimensions()[0];
+        if width / 2 < MIN_LEVEL_DIM || height / 2 < MIN_LEVEL_DIM {
+            break;
+        }
+        let level = downscale(&pyramid[pyramid.len() - 1]);
+        pyramid.push(level);
+    }
+    pyramid
+}
+
+// Return the error threshold of a level: the range of different pixels allowed at the full resolution scaled by the area of the level,
+// where the lower bound is rounded down and the upper one up (so a level of a threshold that allows some pixels allows at least one).
+pub fn level_threshold(threshold: &Threshold, full_size: (u32, u32), level_size: (u32, u32)) -> Threshold {
+    let (min, max) = threshold.get_actual_range(full_size);
+    let scale = (level_size.0 as f64 * level_size.1 as f64) / (full_size.0 as f64 * full_size.1 as f64).max(1f64);
+    let max = if max == u32::MAX { None } else { Some(Box::new(Threshold::Absolute((max as f64 * scale).ceil() as u32))) };
+    Threshold::Range(Some(Box::new(Threshold::Absolute((min as f64 * scale).floor() as u32))), max)
+}
+
+// Scale the absolute bounds of a threshold by the given area ratio (rounding them up), keeping its ratios, which already adapt to the size.
+fn scale_absolute(threshold: &Threshold, scale: f64) -> Threshold {
+    match threshold {
+        Threshold::Absolute(value) => Threshold::Absolute((*value as f64 * scale).ceil() as u32),
+        Threshold::Ratio(ratio) => Threshold::Ratio(*ratio),
+        Threshold::Range(min, max) => Threshold::Range(
+            min.as_ref().map(|min| Box::new(scale_absolute(min, scale))),
+            max.as_ref().map(|max| Box::new(scale_absolute(max, scale))),
+        ),
+    }
+}
+
+// Return the options of the given level (of the given size) of the pyramid of images of the full size: the error threshold is scaled
+// by `level_threshold`, the ignored border and the sizes of the tiles are halved at each level (the border is also kept less than
+// half the level), the absolute tile thresholds are scaled by the area, and the tolerance map is downscaled (keeping its values).
+// Only the full resolution keeps the mask of the wrong pixels, whose size is that of the images.
+pub fn level_options(options: &ComparisonOptions, full_size: (u32, u32), level: u32, level_size: (u32, u32)) -> ComparisonOptions {
+    let halve = |value: u32| (value >> level).max(1);
+    let area_scale = (level_size.0 as f64 * level_size.1 as f64) / (full_size.0 as f64 * full_size.1 as f64).max(1f64);
+    ComparisonOptions {
+        error_threshold: level_threshold(&options.error_threshold, full_size, level_size),
+        ignore_border: (options.ignore_border >> level).min(level_size.0.min(level_size.1).saturating_sub(1) / 2),
+        tile_size: options.tile_size.map(|(width, height)| (halve(width), halve(height))),
+        tile: options.tile.as_ref().map(|(layout, tile_error)| {
+            let layout = match *layout {
+                TileLayout::Size(width, height) => TileLayout::Size(halve(width), halve(height)),
+                grid => grid,
+            };
+            (layout, tile_error.as_ref().map(|tile_error| scale_absolute(tile_error, area_scale)))
+        }),
+        tolerance_map: options.tolerance_map.as_ref().map(|map| imageops::resize(map, level_size.0, level_size.1, FilterType::Nearest)),
+        diff_mask: options.diff_mask && level == 0,
+        pyramid: None,
+        ..options.clone()
+    }
+}
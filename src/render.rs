@@ -0,0 +1,145 @@
+// The visual style of the error image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStyle {
+    Difference, // Each channel shows the (remapped) difference between the images.
+    Overlay,    // The different pixels are drawn in a highlight color over a faded copy of the first image.
+    Raw,        // Each channel shows the difference between the images as is (amplified by the gain), without the remapping to [128-255].
+    ColorblindSafe, // The different pixels are colored by their largest channel error using the Viridis colormap
+                    // (and the edges by the Okabe-Ito palette), so they are readable with common color vision deficiencies.
+}
+
+impl TryFrom<&str> for DiffStyle {
+    type Error = anyhow::Error;
+    // Try to parse a string into a diff style.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "difference" => Ok(DiffStyle::Difference),
+            "overlay" => Ok(DiffStyle::Overlay),
+            "raw" => Ok(DiffStyle::Raw),
+            "colorblind-safe" => Ok(DiffStyle::ColorblindSafe),
+            _ => Err(anyhow::Error::msg(format!("Invalid diff style \"{}\" (expected difference, overlay, raw or colorblind-safe)", value))),
+        }
+    }
+}
+
+// The color of the matching pixels in the difference error image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorBackground {
+    Black,      // The matching pixels are black.
+    Original,   // The matching pixels are drawn from the first image at half intensity.
+    White,      // The matching pixels are white.
+}
+
+impl TryFrom<&str> for ErrorBackground {
+    type Error = anyhow::Error;
+    // Try to parse a string into an error background.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "black" => Ok(ErrorBackground::Black),
+            "original" => Ok(ErrorBackground::Original),
+            "white" => Ok(ErrorBackground::White),
+            _ => Err(anyhow::Error::msg(format!("Invalid error background \"{}\" (expected black, original or white)", value))),
+        }
+    }
+}
+
+// Try to parse a color written as "R,G,B" where each component is in [0-255].
+pub fn parse_color(value: &str) -> anyhow::Result<image::Rgb<u8>> {
+    let components = value.split(',').map(|component| component.trim().parse::<u8>()).collect::<Result<Vec<u8>, _>>()?;
+    match components[..] {
+        [r, g, b] => Ok(image::Rgb([r, g, b])),
+        _ => Err(anyhow::Error::msg(format!("Invalid color \"{}\" (expected R,G,B)", value))),
+    }
+}
+
+// Try to parse a color written in hex as "#RRGGBB" or "#RRGGBBAA" (the alpha is 255 if omitted).
+pub fn parse_hex_color(value: &str) -> anyhow::Result<image::Rgba<u8>> {
+    let invalid = || anyhow::Error::msg(format!("Invalid color \"{}\" (expected #RRGGBB or #RRGGBBAA)", value));
+    let hex = value.strip_prefix('#').ok_or_else(invalid)?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let component = |index: usize| u8::from_str_radix(&hex[2 * index..2 * index + 2], 16).map_err(|_| invalid());
+    let alpha = if hex.len() == 8 { component(3)? } else { 255 };
+    Ok(image::Rgba([component(0)?, component(1)?, component(2)?, alpha]))
+}
+
+// Render the overlay error image: the pixels marked in the row-major mask are drawn in the highlight color,
+// and the rest are drawn from the given image at 50% opacity over a white background.
+pub fn render_overlay(image: &image::RgbaImage, mask: &[bool], highlight: image::Rgb<u8>) -> image::RgbImage {
+    let width = image.width();
+    image::RgbImage::from_fn(width, image.height(), |x, y| {
+        if mask[(y * width + x) as usize] {
+            highlight
+        } else {
+            let pixel = image.get_pixel(x, y);
+            image::Rgb([pixel[0], pixel[1], pixel[2]].map(|v| 128 + (v >> 1)))
+        }
+    })
+}
+
+// Return the color of a (row-major) pixel in the error image of an edge comparison: the edges of the first image that are farther
+// than the allowed distance from the second image are red, those of the second image that are far from the first image are green,
+// the pixels that are both are yellow, the other edges are gray, and the rest is black.
+// If the colors must be colorblind-safe, the Okabe-Ito vermillion, blue and yellow are used instead of red, green and yellow.
+pub fn edge_color(edges: &crate::edges::EdgeComparison, index: usize, max_distance: f32, colorblind_safe: bool) -> [u8; 3] {
+    use crate::colormap::{OKABE_ITO_BLUE, OKABE_ITO_VERMILLION, OKABE_ITO_YELLOW};
+    let far = edges.distances.each_ref().map(|distances| distances[index] > max_distance);
+    match (far, edges.edges[0][index] || edges.edges[1][index]) {
+        ([true, true], _) => if colorblind_safe { OKABE_ITO_YELLOW } else { [255, 255, 0] },
+        ([true, false], _) => if colorblind_safe { OKABE_ITO_VERMILLION } else { [255, 0, 0] },
+        ([false, true], _) => if colorblind_safe { OKABE_ITO_BLUE } else { [0, 255, 0] },
+        (_, true) => [96; 3],
+        (_, false) => [0; 3],
+    }
+}
+
+// The characters used by the text preview, from the lowest to the highest error.
+const PREVIEW_RAMP: &[u8] = b" .:-=+*#%@";
+
+// Render a text preview of the error image that is at most the given number of columns wide.
+// Each character covers a block of pixels (twice as tall as it is wide, since terminal cells are tall),
+// and is picked by the average of the brightest channel over the block, so denser characters mark higher errors.
+pub fn render_preview(image: &image::DynamicImage, columns: u32) -> String {
+    let image = image.to_rgb8();
+    let (width, height) = image.dimensions();
+    let block_width = width.div_ceil(columns.max(1)).max(1);
+    let block_height = block_width * 2;
+    let mut preview = String::new();
+    for block_y in (0..height).step_by(block_height as usize) {
+        for block_x in (0..width).step_by(block_width as usize) {
+            let (mut sum, mut count) = (0u64, 0u64);
+            for y in block_y..(block_y + block_height).min(height) {
+                for x in block_x..(block_x + block_width).min(width) {
+                    sum += *image.get_pixel(x, y).0.iter().max().unwrap() as u64;
+                    count += 1;
+                }
+            }
+            let level = (sum * (PREVIEW_RAMP.len() as u64 - 1) + count * 127) / (count * 255);
+            preview.push(PREVIEW_RAMP[level as usize] as char);
+        }
+        preview.push('\n');
+    }
+    preview
+}
+
+// The number of characters of the longest bar of the text histogram.
+const HISTOGRAM_WIDTH: usize = 40;
+
+// Render a text histogram of the per-pixel errors from an error histogram (see `ComparisonOutcome::error_histogram`),
+// whose bins are merged into the given number of bins (which must divide 256). Each line shows the range of errors [0-1] of a bin,
+// a bar whose length is log-scaled (so the few large errors aren't dwarfed by the matching pixels) and the number of pixels.
+// The bin that contains the value threshold is marked.
+pub fn render_histogram(histogram: &[u64], bins: usize, value_threshold: u8) -> String {
+    let merged = crate::stats::merge_bins(histogram, bins);
+    let levels = histogram.len() / bins;
+    let largest = merged.iter().copied().max().unwrap_or(0);
+    let mut text = String::new();
+    for (index, &count) in merged.iter().enumerate() {
+        let length = if count == 0 { 0 } else { ((count as f64).ln_1p() / (largest as f64).ln_1p() * HISTOGRAM_WIDTH as f64).ceil() as usize };
+        let (first, last) = (index * levels, (index + 1) * levels - 1);
+        let marker = if (first..=last).contains(&(value_threshold as usize)) { "  <- threshold" } else { "" };
+        text += &format!("    {:.3}-{:.3} |{:<width$} {}{}\n", first as f32 / 255f32, last as f32 / 255f32, "#".repeat(length), count, marker, width = HISTOGRAM_WIDTH);
+    }
+    text
+}
@@ -0,0 +1,156 @@
+// A comparison of two images that arrive in bands of rows (e.g. while they are decoded), so neither image is held whole.
+// It gives the same outcome as `compare` for the options it supports, but it doesn't draw an error image.
+
+use crate::{BoundingBox, ComparisonOptions, ComparisonOutcome, ImagePair, Metric, PixelError, RGB_CHANNELS, compare_float_pixels, compare_pixels};
+use crate::explain::WorstPixels;
+use crate::percentile::ErrorDistribution;
+use crate::stats::{self, ChannelAccumulator, HISTOGRAM_BINS};
+use crate::tiles::TileGrid;
+
+// Accumulates the statistics of a comparison from the bands of rows of two images of the same size.
+pub struct RowComparison<'a> {
+    options: &'a ComparisonOptions,
+    size: (u32, u32),
+    region: BoundingBox,                    // The region of the image that is compared (everything except the ignored border).
+    allowed_wrong_pixels: (u32, u32),       // The (inclusive) range of wrong pixels allowed by the error threshold.
+    next_row: u32,                          // The first row of the next band.
+    wrong_pixels: u32,
+    bbox: Option<BoundingBox>,
+    max_error: Option<(u32, u32, f32, [f32; 3])>, // The position, error and channel errors of the pixel with the largest error.
+    tile_grid: Option<TileGrid>,
+    tile_counts: Option<TileGrid>,          // The number of wrong pixels per tile of the tile size.
+    distribution: Option<ErrorDistribution>, // The errors of the compared pixels (only needed by the percentile).
+    channel_sums: ChannelAccumulator,
+    error_histogram: Option<Vec<u64>>,
+    worst_pixels: Option<WorstPixels>,
+}
+
+impl<'a> RowComparison<'a> {
+    // Return true if the options can be applied without holding the images whole: the pixel metric,
+    // without alignment, shift tolerance or clusters (which need the neighbors of the pixels), nor sampling (which picks pixels anywhere),
+    // nor the mask of the wrong pixels (which is as large as the images), nor the perceptual hashes, the z-score threshold or the pyramid (which need the whole images).
+    pub fn supports(options: &ComparisonOptions) -> bool {
+        options.metric == Metric::Pixel && options.align.is_none() && !options.normalize && options.shift_tolerance == 0 && !options.analyze_clusters() && options.sample.is_none()
+            && !options.diff_mask && options.perceptual_hash.is_none() && options.zscore_threshold.is_none() && options.pyramid.is_none()
+    }
+
+    // Start comparing two images of the given size, or return an error if the options can't be applied to them.
+    pub fn new(size: (u32, u32), options: &'a ComparisonOptions) -> anyhow::Result<Self> {
+        if !Self::supports(options) {
+            return Err(anyhow::Error::msg("The options can't be applied to images compared row by row"));
+        }
+        crate::check_tolerance_map(size, options)?;
+        let region = crate::compared_region(size, options)?;
+        Ok(RowComparison {
+            options,
+            size,
+            region,
+            allowed_wrong_pixels: options.error_threshold.get_actual_range(region.size()),
+            next_row: 0,
+            wrong_pixels: 0,
+            bbox: None,
+            max_error: None,
+            tile_grid: options.tile.as_ref().map(|(layout, _)| TileGrid::with_layout(size, *layout)).transpose()?,
+            tile_counts: options.tile_size.map(|tile_size| TileGrid::new(size, tile_size)),
+            distribution: None,
+            channel_sums: ChannelAccumulator::default(),
+            error_histogram: options.error_histogram.then(|| vec![0u64; HISTOGRAM_BINS]),
+            worst_pixels: options.explain.map(WorstPixels::new),
+        })
+    }
+
+    // Return the number of rows that were compared so far.
+    pub fn compared_rows(&self) -> u32 {
+        self.next_row
+    }
+
+    // Return true if the comparison can stop early (with early exit) since there are more wrong pixels than the error threshold allows.
+    // Like with `compare`, the statistics of the outcome are then incomplete.
+    pub fn is_decided(&self) -> bool {
+        self.options.early_exit && self.options.tile.is_none() && self.options.tile_size.is_none() && self.options.percentile.is_none() && !self.options.error_histogram && !self.options.suggest_thresholds
+            && self.options.explain.is_none() && self.wrong_pixels > self.allowed_wrong_pixels.1
+    }
+
+    // Compare the next band of rows of the two images, which must be as wide as the images.
+    pub fn add_rows(&mut self, rows: &ImagePair) -> anyhow::Result<()> {
+        let (width, height) = rows.dimensions()[0];
+        if rows.dimensions()[1] != (width, height) || width != self.size.0 || self.next_row + height > self.size.1 {
+            return Err(anyhow::Error::msg(format!("The rows {}..{} don't fit in the images ({}x{})", self.next_row, self.next_row + height, self.size.0, self.size.1)));
+        }
+        let channels = self.options.channels.as_deref().unwrap_or(&RGB_CHANNELS);
+        if self.options.percentile.is_some() && self.distribution.is_none() {
+            self.distribution = Some(ErrorDistribution::new(matches!(rows, ImagePair::Integer(..))));
+        }
+        for row in 0..height {
+            let y = self.next_row + row;
+            for x in 0..width {
+                if !self.region.contains(x, y) || self.options.alpha_threshold.is_some_and(|alpha_threshold| rows.is_transparent(x, row, alpha_threshold)) {
+                    continue;
+                }
+                let error = rows.pixel_error(x, row, channels);
+                self.channel_sums.add(rows.channel_errors(x, row));
+                if let Some(distribution) = &mut self.distribution { distribution.add(error); }
+                if let Some(histogram) = &mut self.error_histogram { histogram[stats::histogram_bin(error)] += 1; }
+                if let Some(worst_pixels) = &mut self.worst_pixels { worst_pixels.add(x, y, None, error, || rows.pixel_values(x, row)); }
+                // The pixels are visited column by column in `compare`, so a tie goes to the leftmost pixel.
+                let is_max_error = match self.max_error {
+                    Some((max_x, _, max, _)) => error > max || (error == max && x < max_x),
+                    None => error > 0f32,
+                };
+                if is_max_error {
+                    self.max_error = Some((x, y, error, rows.channel_errors(x, row)));
+                }
+
+                let thresholds = self.options.value_thresholds_at(x, y);
+                let (is_pixel_different, _) = match rows {
+                    ImagePair::Integer(img1, img2) => compare_pixels(img1.get_pixel(x, row), img2.get_pixel(x, row), channels, thresholds.0, self.options.luma_weight),
+                    ImagePair::Float(img1, img2) => compare_float_pixels(img1.get_pixel(x, row), img2.get_pixel(x, row), channels, thresholds.1, self.options.luma_weight),
+                };
+                if is_pixel_different {
+                    self.wrong_pixels += 1;
+                    if let Some(tile_grid) = &mut self.tile_grid { tile_grid.add(x, y); }
+                    if let Some(tile_counts) = &mut self.tile_counts { tile_counts.add(x, y); }
+                    match &mut self.bbox {
+                        Some(bbox) => bbox.include(x, y),
+                        None => self.bbox = Some(BoundingBox::new(x, y)),
+                    }
+                }
+            }
+        }
+        self.next_row += height;
+        Ok(())
+    }
+
+    // Return the outcome of the comparison of the rows compared so far.
+    pub fn finish(self) -> ComparisonOutcome {
+        let (min_wrong_pixels, max_wrong_pixels) = self.allowed_wrong_pixels;
+        let failed_tiles = crate::failed_tiles(self.tile_grid.as_ref(), self.options, &self.region);
+        let percentile_error = self.options.percentile.zip(self.distribution).and_then(|((percentile, _), distribution)| distribution.percentile(percentile));
+        let mismatch = self.wrong_pixels < min_wrong_pixels || self.wrong_pixels > max_wrong_pixels || crate::exceeds_tile_limit(&failed_tiles, self.options)
+            || crate::exceeds_percentile_limit(percentile_error, self.options);
+        let region_size = self.region.size();
+        let (mae, rmse) = self.channel_sums.errors();
+        let outcome = ComparisonOutcome {
+            matched: !mismatch,
+            wrong_pixels: self.wrong_pixels,
+            total_pixels: region_size.0 * region_size.1,
+            allowed_wrong_pixels: self.allowed_wrong_pixels,
+            bbox: self.bbox,
+            failed_tiles,
+            tiles: self.tile_counts.as_ref().map(|tile_counts| tile_counts.tiles().collect()),
+            max_error_pixel: self.max_error.map(|(x, y, _, channel_errors)| PixelError { x, y, channel_errors }),
+            percentile_error,
+            channel_stats: Some(self.channel_sums.finish()),
+            error_histogram: self.error_histogram,
+            worst_pixels: self.worst_pixels.map(WorstPixels::finish),
+            mae,
+            rmse,
+            ..ComparisonOutcome::size_mismatch([self.size, self.size])
+        };
+        ComparisonOutcome {
+            suggestions: stats::suggest_thresholds(&outcome, self.max_error.map_or(0f32, |(_, _, max, _)| max), self.options),
+            sweep: stats::sweep(&outcome, self.options),
+            ..outcome
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// An approximate comparison of two images that only compares the pixels at random positions,
+// for smoke tests on images that are too large to compare whole in time.
+
+use crate::{BoundingBox, ComparisonOptions, ComparisonOutcome, ImagePair, Metric, PixelError, RGB_CHANNELS, compare_float_pixels, compare_pixels};
+use crate::percentile::ErrorDistribution;
+use crate::stats::{self, ChannelAccumulator, HISTOGRAM_BINS};
+
+// The number of pixel positions to sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleSize {
+    Count(u32), // A number of positions.
+    Rate(f32),  // A fraction (0-1] of the pixels of the compared region (rounded up).
+}
+
+// The number of random pixel positions to compare and the seed of their generator (the same seed samples the same positions).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub size: SampleSize,
+    pub seed: u64,
+}
+
+// The PCG32 generator (XSH-RR output on a 64-bit LCG state), see https://www.pcg-random.org.
+struct Pcg32 {
+    state: u64,
+    increment: u64, // The (odd) increment of the LCG, which selects one of its streams.
+}
+
+impl Pcg32 {
+    // The stream of the generator (the one used by the examples of the reference implementation).
+    const STREAM: u64 = 54;
+
+    // Seed the generator like the reference implementation (pcg32_srandom_r).
+    fn new(seed: u64) -> Self {
+        let mut rng = Pcg32 { state: 0, increment: (Self::STREAM << 1) | 1 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state.wrapping_mul(6364136223846793005).wrapping_add(self.increment);
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+
+    // Return a random number in [0, bound).
+    fn below(&mut self, bound: u32) -> u32 {
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+}
+
+// Return the range of wrong pixels allowed by the error threshold, scaled from the pixels of the region to the samples.
+fn sampled_range(range: (u32, u32), region_pixels: u64, samples: u32) -> (u32, u32) {
+    let scale = |value: u32| if value == u32::MAX { u32::MAX } else { (value as u64 * samples as u64 / region_pixels) as u32 };
+    (scale(range.0), scale(range.1))
+}
+
+// Compare the pixels at the sampled positions (drawn with replacement) of the compared region of two images of the same size.
+// The mismatch is decided on the ratio of wrong samples, so the error threshold is scaled to the number of samples,
+// and the pixel counts of the outcome are those of the samples. Return None if there are at least as many samples as pixels,
+// in which case the images should be compared whole, or an error if the options need the whole images.
+pub fn compare_sampled(images: &ImagePair, options: &ComparisonOptions, sampling: &Sampling) -> anyhow::Result<Option<ComparisonOutcome>> {
+    if options.metric != Metric::Pixel || options.analyze_clusters() || options.tile.is_some() || options.tile_size.is_some() || options.explain.is_some() {
+        return Err(anyhow::Error::msg("The sampling can't be combined with the FLIP or edges metrics, clusters, tiles or the worst pixels"));
+    }
+    let size = images.dimensions()[0];
+    let region = crate::compared_region(size, options)?;
+    let region_size = region.size();
+    let region_pixels = region_size.0 as u64 * region_size.1 as u64;
+    let count = match sampling.size {
+        SampleSize::Count(count) => count as u64,
+        SampleSize::Rate(rate) => (rate as f64 * region_pixels as f64).ceil() as u64,
+    };
+    if count >= region_pixels {
+        return Ok(None);
+    }
+    let count = count.max(1) as u32;
+
+    let channels = options.channels.as_deref().unwrap_or(&RGB_CHANNELS);
+    let allowed_wrong_pixels = sampled_range(options.error_threshold.get_actual_range(region_size), region_pixels, count);
+    let mut distribution = options.percentile.map(|_| ErrorDistribution::new(matches!(images, ImagePair::Integer(..))));
+    let mut rng = Pcg32::new(sampling.seed);
+    let mut wrong_pixels = 0;
+    let mut bbox: Option<BoundingBox> = None;
+    let mut max_error: Option<(u32, u32, f32)> = None;
+    let mut channel_sums = ChannelAccumulator::default();
+    let mut error_histogram = options.error_histogram.then(|| vec![0u64; HISTOGRAM_BINS]);
+    for _ in 0..count {
+        let (x, y) = (region.min.0 + rng.below(region_size.0), region.min.1 + rng.below(region_size.1));
+        if options.alpha_threshold.is_some_and(|alpha_threshold| images.is_transparent(x, y, alpha_threshold)) {
+            continue;
+        }
+        let error = images.pixel_error(x, y, channels);
+        channel_sums.add(images.channel_errors(x, y));
+        if let Some(distribution) = &mut distribution { distribution.add(error); }
+        if let Some(histogram) = &mut error_histogram { histogram[stats::histogram_bin(error)] += 1; }
+        if error > max_error.map_or(0f32, |(_, _, max)| max) {
+            max_error = Some((x, y, error));
+        }
+
+        let thresholds = options.value_thresholds_at(x, y);
+        let (mut is_pixel_different, _) = match images {
+            ImagePair::Integer(img1, img2) => compare_pixels(img1.get_pixel(x, y), img2.get_pixel(x, y), channels, thresholds.0, options.luma_weight),
+            ImagePair::Float(img1, img2) => compare_float_pixels(img1.get_pixel(x, y), img2.get_pixel(x, y), channels, thresholds.1, options.luma_weight),
+        };
+        if is_pixel_different && options.shift_tolerance > 0
+            && (images.has_nearby_match(x, y, false, thresholds, options) || images.has_nearby_match(x, y, true, thresholds, options)) {
+            is_pixel_different = false;
+        }
+        if is_pixel_different {
+            wrong_pixels += 1;
+            match &mut bbox {
+                Some(bbox) => bbox.include(x, y),
+                None => bbox = Some(BoundingBox::new(x, y)),
+            }
+        }
+    }
+
+    let percentile_error = options.percentile.zip(distribution).and_then(|((percentile, _), distribution)| distribution.percentile(percentile));
+    let mismatch = wrong_pixels < allowed_wrong_pixels.0 || wrong_pixels > allowed_wrong_pixels.1 || crate::exceeds_percentile_limit(percentile_error, options);
+    let (mae, rmse) = channel_sums.errors();
+    let outcome = ComparisonOutcome {
+        matched: !mismatch,
+        wrong_pixels,
+        total_pixels: count,
+        allowed_wrong_pixels,
+        bbox,
+        max_error_pixel: max_error.map(|(x, y, _)| PixelError { x, y, channel_errors: images.channel_errors(x, y) }),
+        percentile_error,
+        channel_stats: Some(channel_sums.finish()),
+        error_histogram,
+        mae,
+        rmse,
+        sampled: true,
+        ..ComparisonOutcome::size_mismatch([size, size])
+    };
+    Ok(Some(ComparisonOutcome {
+        suggestions: stats::suggest_thresholds(&outcome, max_error.map_or(0f32, |(_, _, max)| max), options),
+        sweep: stats::sweep(&outcome, options),
+        ..outcome
+    }))
+}
@@ -0,0 +1,80 @@
+use std::io::{BufRead, Write};
+
+use serde::Deserialize;
+
+use imgcmp::Threshold;
+
+use crate::{Options, STDIN_PATH, Verbosity, batch::{Comparison, Pair}, json, run_with_timeout};
+
+// A comparison request of the server mode, written as a JSON object on a single line, which may also have an "id" (of any type)
+// that is copied into the response. The options given on the command line apply to every request, and the optional fields override them like in a manifest.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    expected: String,
+    actual: String,
+    threshold: Option<f32>,
+    error: Option<String>,
+    output: Option<String>,
+}
+
+impl Request {
+    // Convert the request into a pair of images to compare.
+    fn into_pair(self) -> anyhow::Result<Pair> {
+        if self.expected == STDIN_PATH || self.actual == STDIN_PATH {
+            return Err(anyhow::Error::msg("The images can't be read from stdin in server mode, since it carries the requests"));
+        }
+        Ok(Pair {
+            image_paths: [self.expected, self.actual],
+            threshold: self.threshold,
+            error: self.error.as_deref().map(Threshold::try_from).transpose()?,
+            output: self.output,
+        })
+    }
+}
+
+// Compare the pair of images of a request like any pair of a run.
+fn compare(pair: Pair, options: &Options) -> Comparison {
+    let comparison_options = pair.comparison_options(&options.comparison);
+    let output = pair.output.clone();
+    let result = run_with_timeout([&pair.image_paths[0], &pair.image_paths[1]], output.as_ref(), &comparison_options, options, &mut |_| {})
+        .map(|(outcome, _)| outcome);
+    let output = output.filter(|_| !(options.output_on_mismatch_only && matches!(&result, Ok(outcome) if outcome.matched)));
+    Comparison { image_paths: pair.image_paths, output, result, updated: false }
+}
+
+// Read comparison requests from stdin (one JSON object per line, see `Request`) until it is closed,
+// and write the response to each of them on its own line of stdout as soon as it is compared (see `json::response_line`).
+// A request that can't be read or compared gets an error response, and the server goes on with the next one.
+// Return false if stdin couldn't be read. The server stops early if stdout is closed.
+pub fn serve(options: &Options) -> bool {
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                if options.verbosity > Verbosity::SILENT {
+                    writeln!(std::io::stderr(), "Error Failed to read stdin: {err}").expect("Failed to write Error");
+                }
+                return false;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        // The id is taken out first, so even the error response to an invalid request has it.
+        let mut value = serde_json::from_str::<serde_json::Value>(&line);
+        let id = value.as_mut().ok().and_then(|value| value.as_object_mut()).and_then(|object| object.remove("id"));
+        let pair = value.map_err(anyhow::Error::new)
+            .and_then(|value| serde_json::from_value::<Request>(value).map_err(anyhow::Error::new))
+            .and_then(Request::into_pair);
+        let response = match pair {
+            Ok(pair) => json::response_line(id.as_ref(), Ok(&compare(pair, options))),
+            Err(err) => json::response_line(id.as_ref(), Err(&err.context("Invalid request"))),
+        };
+        if writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+    true
+}
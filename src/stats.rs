@@ -0,0 +1,199 @@
+use crate::{ComparisonOptions, ComparisonOutcome, Metric, Threshold};
+
+// The statistics of the absolute differences of a channel over the compared pixels
+// (in [0-255] for 8-bit images, and in linear float space for float images).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChannelStats {
+    pub mean: f32,          // The mean absolute difference.
+    pub max: f32,           // The largest absolute difference.
+    pub differing: u32,     // The number of pixels whose channel differs at all (regardless of the value threshold).
+}
+
+// Accumulates the statistics of the RGB channels as the pixels are compared.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChannelAccumulator {
+    sums: [f64; 3],
+    squares: [f64; 3],  // The sums of the squared differences.
+    max: [f32; 3],
+    differing: [u32; 3],
+    count: u64,
+}
+
+impl ChannelAccumulator {
+    // Add the absolute differences of the RGB channels of a pixel.
+    pub(crate) fn add(&mut self, channel_errors: [f32; 3]) {
+        for (channel, &error) in channel_errors.iter().enumerate() {
+            self.sums[channel] += error as f64;
+            self.squares[channel] += error as f64 * error as f64;
+            self.max[channel] = self.max[channel].max(error);
+            if error > 0f32 {
+                self.differing[channel] += 1;
+            }
+        }
+        self.count += 1;
+    }
+
+    // Return the statistics of the R, G and B channels of the pixels added so far.
+    pub(crate) fn finish(&self) -> [ChannelStats; 3] {
+        [0, 1, 2].map(|channel| ChannelStats {
+            mean: if self.count == 0 { 0f32 } else { (self.sums[channel] / self.count as f64) as f32 },
+            max: self.max[channel],
+            differing: self.differing[channel],
+        })
+    }
+
+    // Return the mean absolute error and the root mean squared error over the RGB channels of the pixels added so far.
+    pub(crate) fn errors(&self) -> (f64, f64) {
+        if self.count == 0 {
+            return (0f64, 0f64);
+        }
+        let samples = 3f64 * self.count as f64;
+        (self.sums.iter().sum::<f64>() / samples, (self.squares.iter().sum::<f64>() / samples).sqrt())
+    }
+}
+
+// The number of bins of the exact histogram of the per-pixel errors, one per 8-bit level.
+pub const HISTOGRAM_BINS: usize = 256;
+
+// Return the bin of the error histogram of a per-pixel error [0-1] (the larger errors of float images go to the last bin).
+pub(crate) fn histogram_bin(error: f32) -> usize {
+    (error * 255f32).round().clamp(0f32, 255f32) as usize
+}
+
+// Add the bins of the second error histogram to those of the first one.
+pub(crate) fn add_histogram(histogram: &mut [u64], other: &[u64]) {
+    histogram.iter_mut().zip(other).for_each(|(count, other)| *count += other);
+}
+
+// Merge the consecutive bins of an error histogram into the given number of bins, which must divide its number of bins.
+pub fn merge_bins(histogram: &[u64], bins: usize) -> Vec<u64> {
+    histogram.chunks(histogram.len() / bins).map(|chunk| chunk.iter().sum()).collect()
+}
+
+// The thresholds under which a comparison would have passed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Suggestions {
+    pub value_threshold: Option<f32>, // The smallest value threshold [0-1] under which every pixel matches (None with the FLIP or edges metrics or a tolerance map).
+    pub error_pixels: u32,      // The smallest absolute error threshold under which the different pixels are allowed with the current settings.
+    pub error_percentage: f32,  // The same error threshold as a percentage of the compared pixels.
+}
+
+impl Suggestions {
+    // Merge the suggestions of two comparisons (e.g. frames) into those under which both would pass.
+    pub(crate) fn merge(self, other: Suggestions) -> Suggestions {
+        Suggestions {
+            value_threshold: self.value_threshold.zip(other.value_threshold).map(|(a, b)| a.max(b)),
+            error_pixels: self.error_pixels.max(other.error_pixels),
+            error_percentage: self.error_percentage.max(other.error_percentage),
+        }
+    }
+}
+
+// Round the value up to 4 decimals, then raise it by 0.0001 until it is accepted (which makes up for the rounding of the floats).
+fn round_up(value: f32, accepts: impl Fn(f32) -> bool) -> f32 {
+    let mut value = (value * 10000f32).ceil() / 10000f32;
+    while !accepts(value) {
+        value += 0.0001f32;
+    }
+    value
+}
+
+// Return the suggested thresholds of a comparison given the largest per-pixel error [0-1] of its compared pixels,
+// or None if they weren't requested or the images couldn't be compared.
+pub(crate) fn suggest_thresholds(outcome: &ComparisonOutcome, max_error: f32, options: &ComparisonOptions) -> Option<Suggestions> {
+    if !options.suggest_thresholds || outcome.is_size_mismatch() {
+        return None;
+    }
+    // A pixel is different if its error exceeds the threshold, which is truncated to an 8-bit level for 8-bit images.
+    let value_threshold = (options.metric == Metric::Pixel && options.tolerance_map.is_none()).then(|| {
+        let level = (max_error * 255f32).round() as u32;
+        round_up(max_error, |threshold| threshold >= max_error && (threshold * 255f32) as u32 >= level)
+    });
+    let (wrong_pixels, total_pixels) = (outcome.wrong_pixels, outcome.total_pixels);
+    let error_percentage = round_up(wrong_pixels as f32 * 100f32 / total_pixels.max(1) as f32,
+        |percentage| Threshold::Ratio(percentage / 100f32).accepts(wrong_pixels, (total_pixels, 1)));
+    Some(Suggestions { value_threshold, error_pixels: wrong_pixels, error_percentage })
+}
+
+// The outcome of a comparison under another value threshold, computed from its error histogram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepPoint {
+    pub value_threshold: f32,   // The value threshold [0-1].
+    pub wrong_pixels: u32,      // The number of pixels whose largest channel difference exceeds the threshold.
+    pub matched: bool,          // True if the number of wrong pixels is allowed by the error threshold.
+}
+
+// Return the outcomes of a comparison under each value threshold of the sweep given by the options, or None if no sweep was requested.
+// A pixel is wrong if its largest channel difference exceeds the 8-bit level of the threshold, which is exact for 8-bit images
+// compared with the pixel metric (without a luma weight, a tolerance map or a shift tolerance), and only the error threshold decides the match.
+pub(crate) fn sweep(outcome: &ComparisonOutcome, options: &ComparisonOptions) -> Option<Vec<SweepPoint>> {
+    let thresholds = options.sweep.as_deref()?;
+    if outcome.is_size_mismatch() {
+        return None;
+    }
+    if let Some(animation) = &outcome.animation {
+        // The images match under a threshold if they have the same number of frames and every frame matches under it.
+        let same_frame_count = animation.frame_counts[0] == animation.frame_counts[1];
+        return animation.frames.iter().map(|frame| frame.sweep.as_deref()).collect::<Option<Vec<&[SweepPoint]>>>().map(|sweeps| {
+            thresholds.iter().enumerate().map(|(index, &value_threshold)| SweepPoint {
+                value_threshold,
+                wrong_pixels: crate::saturating_sum(sweeps.iter().map(|sweep| sweep[index].wrong_pixels)),
+                matched: same_frame_count && sweeps.iter().all(|sweep| sweep[index].matched),
+            }).collect()
+        });
+    }
+    let histogram = outcome.error_histogram.as_deref()?;
+    Some(thresholds.iter().map(|&value_threshold| {
+        let level = (value_threshold * 255f32) as u8 as usize;
+        let wrong_pixels = u32::try_from(histogram[level + 1..].iter().sum::<u64>()).unwrap_or(u32::MAX);
+        SweepPoint { value_threshold, wrong_pixels, matched: options.error_threshold.accepts(wrong_pixels, (outcome.total_pixels, 1)) }
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageBuffer, Rgba};
+
+    use crate::{ComparisonOptions, ImagePair, compare};
+
+    use super::ChannelStats;
+
+    // Return the channel statistics of the comparison of two images.
+    fn channel_stats(images: ImagePair) -> [ChannelStats; 3] {
+        compare(&images, &ComparisonOptions::default()).unwrap().0.channel_stats.unwrap()
+    }
+
+    #[test]
+    fn channel_stats_of_8_bit_images() {
+        let first = ImageBuffer::from_pixel(2, 2, Rgba([0u8, 0, 0, 255]));
+        let second = ImageBuffer::from_vec(2, 2, vec![
+            10, 0, 0, 255,   20, 5, 0, 255,
+            0, 0, 0, 255,    30, 0, 255, 0,
+        ]).unwrap();
+        let stats = channel_stats(ImagePair::new(DynamicImage::ImageRgba8(first), DynamicImage::ImageRgba8(second)));
+        assert_eq!(stats, [
+            ChannelStats { mean: 15f32, max: 30f32, differing: 3 },
+            ChannelStats { mean: 1.25f32, max: 5f32, differing: 1 },
+            ChannelStats { mean: 63.75f32, max: 255f32, differing: 1 },
+        ]);
+    }
+
+    #[test]
+    fn channel_stats_of_float_images() {
+        let first = ImageBuffer::from_pixel(2, 1, Rgba([0.5f32, 0.5, 0.5, 1.0]));
+        let second = ImageBuffer::from_vec(2, 1, vec![0.75f32, 0.5, 0.0, 1.0,   0.5, 0.5, 2.5, 1.0]).unwrap();
+        let stats = channel_stats(ImagePair::new(DynamicImage::ImageRgba32F(first), DynamicImage::ImageRgba32F(second)));
+        assert_eq!(stats, [
+            ChannelStats { mean: 0.125f32, max: 0.25f32, differing: 1 },
+            ChannelStats { mean: 0f32, max: 0f32, differing: 0 },
+            ChannelStats { mean: 1.25f32, max: 2f32, differing: 2 },
+        ]);
+    }
+
+    #[test]
+    fn channel_stats_of_identical_images() {
+        let image = ImageBuffer::from_pixel(3, 3, Rgba([12u8, 34, 56, 255]));
+        let stats = channel_stats(ImagePair::new(DynamicImage::ImageRgba8(image.clone()), DynamicImage::ImageRgba8(image)));
+        assert_eq!(stats, [ChannelStats::default(); 3]);
+    }
+}
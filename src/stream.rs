@@ -0,0 +1,135 @@
+use std::{fs::File, io::BufReader};
+
+use anyhow::Context;
+use image::{DynamicImage, ImageFormat, imageops, io::Reader};
+use tiff::decoder::Decoder;
+
+use imgcmp::{ComparisonOptions, ComparisonOutcome, ImagePair, rows::RowComparison};
+
+use crate::image_from_samples;
+
+// A page of a TIFF image that is decoded one band at a time, where a band is a strip or a row of tiles.
+struct BandReader {
+    decoder: Decoder<BufReader<File>>,
+    name: String,
+    size: (u32, u32),
+    color: tiff::ColorType,
+    chunk_size: (u32, u32),     // The size of the strips or tiles (those on the right and bottom edges may be smaller).
+    chunks_across: u32,         // The number of chunks in a band.
+    next_band: u32,
+}
+
+impl BandReader {
+    // Open the page with the given index of a TIFF image.
+    // Return None if the image isn't a TIFF or its page can't be read, which the full decoding then reports.
+    fn open(path: &str, page: usize) -> Option<Self> {
+        if Reader::open(path).ok()?.with_guessed_format().ok()?.format() != Some(ImageFormat::Tiff) {
+            return None;
+        }
+        let mut decoder = Decoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+        if page > 0 {
+            decoder.seek_to_image(page).ok()?;
+        }
+        let size = decoder.dimensions().ok()?;
+        let color = decoder.colortype().ok()?;
+        let chunk_size = decoder.chunk_dimensions();
+        if size.0 == 0 || size.1 == 0 || chunk_size.0 == 0 || chunk_size.1 == 0 {
+            return None;
+        }
+        let chunks_across = size.0.div_ceil(chunk_size.0.min(size.0));
+        Some(BandReader { decoder, name: path.to_string(), size, color, chunk_size, chunks_across, next_band: 0 })
+    }
+
+    // Return true if the pixels are compared as floats (see `ImagePair::new`).
+    fn is_float(&self) -> bool {
+        matches!(self.color, tiff::ColorType::RGB(32) | tiff::ColorType::RGBA(32))
+    }
+
+    // Decode the next band as an RGBA image (with float channels if requested).
+    fn read_band(&mut self, float: bool) -> anyhow::Result<DynamicImage> {
+        // The decoding errors are I/O errors, like those of any image that can't be decoded.
+        let decode_error = || format!("Failed to decode {}", self.name);
+        let convert = |img: DynamicImage| if float { DynamicImage::ImageRgba32F(img.into_rgba32f()) } else { DynamicImage::ImageRgba8(img.into_rgba8()) };
+        let first = self.next_band * self.chunks_across;
+        let mut band: Option<DynamicImage> = None;
+        for column in 0..self.chunks_across {
+            let index = first + column;
+            let chunk_size = self.decoder.chunk_data_dimensions(index);
+            let samples = self.decoder.read_chunk(index).map_err(std::io::Error::other).context(decode_error())?;
+            let chunk = convert(image_from_samples(self.color, chunk_size, samples)
+                .map_err(|err| std::io::Error::other(format!("{err:#}"))).context(decode_error())?);
+            // A strip is a band by itself, while the tiles of a row are pasted side by side.
+            if self.chunks_across == 1 {
+                band = Some(chunk);
+                continue;
+            }
+            let band = band.get_or_insert_with(|| convert(DynamicImage::new_rgba8(self.size.0, chunk_size.1)));
+            let x = (column * self.chunk_size.0) as i64;
+            match (band, &chunk) {
+                (DynamicImage::ImageRgba8(band), DynamicImage::ImageRgba8(chunk)) => imageops::replace(band, chunk, x, 0),
+                (DynamicImage::ImageRgba32F(band), DynamicImage::ImageRgba32F(chunk)) => imageops::replace(band, chunk, x, 0),
+                _ => unreachable!("The band and its chunks have the same type"),
+            }
+        }
+        self.next_band += 1;
+        band.ok_or_else(|| anyhow::Error::msg(format!("{} has no pixels", self.name)))
+    }
+}
+
+// A band that was decoded but not yet compared whole.
+struct PendingBand {
+    band: DynamicImage,
+    rows: u32,          // The height of the band (the band itself is taken once its rows are all compared at once).
+    compared_rows: u32,
+}
+
+impl PendingBand {
+    fn new(band: DynamicImage) -> Self {
+        PendingBand { rows: band.height(), band, compared_rows: 0 }
+    }
+
+    fn remaining_rows(&self) -> u32 {
+        self.rows - self.compared_rows
+    }
+
+    // Take the given number of rows after those already compared.
+    fn take_rows(&mut self, count: u32) -> DynamicImage {
+        let rows = if self.compared_rows == 0 && count == self.rows {
+            std::mem::replace(&mut self.band, DynamicImage::new_rgba8(0, 0))
+        } else {
+            self.band.crop_imm(0, self.compared_rows, self.band.width(), count)
+        };
+        self.compared_rows += count;
+        rows
+    }
+}
+
+// Compare the pages with the given index of two TIFF images of the same size band by band, as they are decoded,
+// so only a band of each image is held in memory. The progress is the fraction [0-1] of the compared rows.
+// Return None if the images can't be compared that way (if they aren't TIFF files, or they have different sizes),
+// in which case they should be decoded whole.
+pub fn compare_tiff_bands(image_paths: [&String; 2], page: usize, options: &ComparisonOptions, progress: &mut dyn FnMut(f32)) -> Option<anyhow::Result<ComparisonOutcome>> {
+    let mut readers = [BandReader::open(image_paths[0], page)?, BandReader::open(image_paths[1], page)?];
+    if readers[0].size != readers[1].size {
+        return None;
+    }
+    let (size, float) = (readers[0].size, readers[0].is_float() || readers[1].is_float());
+    Some((|| {
+        let mut comparison = RowComparison::new(size, options)?;
+        let mut pending: [Option<PendingBand>; 2] = [None, None];
+        // The bands of the two images may have different heights, so the rows that both images have are compared at each step.
+        while comparison.compared_rows() < size.1 && !comparison.is_decided() {
+            for (reader, pending) in readers.iter_mut().zip(pending.iter_mut()) {
+                if pending.as_ref().is_none_or(|pending| pending.remaining_rows() == 0) {
+                    *pending = Some(PendingBand::new(reader.read_band(float)?));
+                }
+            }
+            let [Some(pending1), Some(pending2)] = &mut pending else { unreachable!("Both images have a pending band") };
+            let count = pending1.remaining_rows().min(pending2.remaining_rows());
+            comparison.add_rows(&ImagePair::new(pending1.take_rows(count), pending2.take_rows(count)))?;
+            progress(comparison.compared_rows() as f32 / size.1 as f32);
+        }
+        progress(1f32);
+        Ok(comparison.finish())
+    })())
+}
@@ -0,0 +1,112 @@
+use crate::BoundingBox;
+
+// A tile of the image grid along with the number of different pixels inside it.
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub index: (u32, u32),  // The column and row of the tile in the grid.
+    pub bbox: BoundingBox,  // The pixels covered by the tile (tiles on the right and bottom edges may be smaller).
+    pub wrong_pixels: u32,  // The number of different pixels inside the tile.
+}
+
+impl Tile {
+    // Return the size of the tile in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        self.bbox.size()
+    }
+}
+
+// How the image is divided into tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileLayout {
+    Size(u32, u32),     // Tiles of this width and height (the tiles on the right and bottom edges may be smaller).
+    Grid(u32, u32),     // This number of columns and rows, whose sizes differ by at most one pixel.
+}
+
+// Divides the image into a grid of tiles and accumulates the number of different pixels per tile.
+pub struct TileGrid {
+    column_starts: Vec<u32>,    // The first x of each column of tiles, followed by the image width.
+    row_starts: Vec<u32>,       // The first y of each row of tiles, followed by the image height.
+    counts: Vec<u32>,   // The number of different pixels per tile in row-major order.
+}
+
+// Return the start of each of the given number of parts of the given length (whose lengths differ by at most one), followed by the length.
+fn even_starts(length: u32, parts: u32) -> Vec<u32> {
+    (0..=parts).map(|part| (part as u64 * length as u64 / parts as u64) as u32).collect()
+}
+
+impl TileGrid {
+    // Create a grid of tiles with the given size covering an image of the given size.
+    pub fn new(image_size: (u32, u32), tile_size: (u32, u32)) -> Self {
+        let starts = |length: u32, tile_length: u32| (0..length).step_by(tile_length as usize).chain([length]).collect::<Vec<_>>();
+        TileGrid::from_starts(starts(image_size.0, tile_size.0), starts(image_size.1, tile_size.1))
+    }
+
+    // Create a grid of tiles with the given layout covering an image of the given size,
+    // or return an error if the image has fewer pixels than the grid has columns or rows.
+    pub fn with_layout(image_size: (u32, u32), layout: TileLayout) -> anyhow::Result<Self> {
+        match layout {
+            TileLayout::Size(width, height) => Ok(TileGrid::new(image_size, (width, height))),
+            TileLayout::Grid(columns, rows) if columns <= image_size.0 && rows <= image_size.1 => {
+                Ok(TileGrid::from_starts(even_starts(image_size.0, columns), even_starts(image_size.1, rows)))
+            },
+            TileLayout::Grid(columns, rows) => Err(anyhow::Error::msg(format!(
+                "The grid of {}x{} tiles is finer than the image ({}x{})", columns, rows, image_size.0, image_size.1))),
+        }
+    }
+
+    fn from_starts(column_starts: Vec<u32>, row_starts: Vec<u32>) -> Self {
+        let count = (column_starts.len() - 1) * (row_starts.len() - 1);
+        TileGrid { column_starts, row_starts, counts: vec![0; count] }
+    }
+
+    // Count a different pixel in the tile containing it.
+    pub fn add(&mut self, x: u32, y: u32) {
+        let index = self.tile_index(x, y);
+        self.counts[index] += 1;
+    }
+
+    // Uncount a different pixel (that was counted) from the tile containing it.
+    pub fn remove(&mut self, x: u32, y: u32) {
+        let index = self.tile_index(x, y);
+        self.counts[index] -= 1;
+    }
+
+    // Return the row-major index of the tile containing the pixel (x, y).
+    fn tile_index(&self, x: u32, y: u32) -> usize {
+        let column = self.column_starts.partition_point(|&start| start <= x) - 1;
+        let row = self.row_starts.partition_point(|&start| start <= y) - 1;
+        row * (self.column_starts.len() - 1) + column
+    }
+
+    // Return all the tiles in row-major order.
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        let columns = self.column_starts.len() - 1;
+        self.counts.iter().enumerate().map(move |(index, &wrong_pixels)| {
+            let (column, row) = (index % columns, index / columns);
+            let min = (self.column_starts[column], self.row_starts[row]);
+            let max = (self.column_starts[column + 1] - 1, self.row_starts[row + 1] - 1);
+            Tile { index: (column as u32, row as u32), bbox: BoundingBox { min, max }, wrong_pixels }
+        })
+    }
+}
+
+// Return the tiles of the frames of animated images (which share their grid) with the different pixels summed over the frames,
+// or None if some frame has no tiles.
+pub(crate) fn sum_frame_tiles<'a>(frames: impl Iterator<Item = Option<&'a [Tile]>>) -> Option<Vec<Tile>> {
+    frames.map(|tiles| tiles.map(<[Tile]>::to_vec)).reduce(|sum, tiles| {
+        let (mut sum, tiles) = (sum?, tiles?);
+        sum.iter_mut().zip(&tiles).for_each(|(tile, other)| tile.wrong_pixels = tile.wrong_pixels.saturating_add(other.wrong_pixels));
+        Some(sum)
+    })?
+}
+
+// Draw the lines between the tiles of the given size on the given image.
+pub fn draw_grid(image: &mut image::RgbImage, tile_size: (u32, u32), color: image::Rgb<u8>) {
+    let (width, height) = image.dimensions();
+    for x in (tile_size.0..width).step_by(tile_size.0 as usize) {
+        for y in 0..height { image.put_pixel(x, y, color); }
+    }
+    for y in (tile_size.1..height).step_by(tile_size.1 as usize) {
+        for x in 0..width { image.put_pixel(x, y, color); }
+    }
+}
@@ -0,0 +1,250 @@
+use std::io::Cursor;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{ComparisonOptions, ImagePair, Metric, Threshold, clusters::Connectivity, parse_channel, render::{DiffStyle, ErrorBackground}, tiles::TileLayout};
+
+// The comparison options as received from JavaScript. The fields mirror `ComparisonOptions`,
+// except that thresholds, connectivity, styles and channels are written as they would be on the command line.
+#[derive(Deserialize)]
+#[serde(default)]
+struct JsOptions {
+    metric: String,             // "pixel", "flip", "edges", "gradient" or "fft".
+    edge_distance: f32,
+    value_threshold: f32,       // A threshold [0-1] on the maximum allowed per-channel error.
+    luma_weight: f32,
+    error_threshold: String,    // The number (or percentage) of pixels allowed to be different.
+    percentile: Option<f32>,    // The percentile [0-100] of the per-pixel errors that must not exceed percentile_error (both are required together).
+    percentile_error: Option<f32>,
+    draw_bbox: bool,
+    connectivity: String,       // "4" or "8".
+    find_clusters: bool,
+    min_cluster_size: Option<u32>,
+    max_cluster_size: Option<u32>,
+    diff_style: String,         // "difference", "overlay", "raw" or "colorblind-safe".
+    highlight_color: [u8; 3],
+    error_background: String,   // "black", "original" or "white".
+    tile: Option<u32>,
+    tile_error: Option<String>,
+    channels: Option<Vec<String>>,
+    shift_tolerance: u32,
+    ignore_border: u32,
+    alpha_threshold: Option<u8>, // The alpha [0-255] below which the pixels of both images always match.
+    align: Option<u32>,
+    normalize: bool,
+    error_image: bool,          // If true, the error image is returned as PNG bytes.
+}
+
+impl Default for JsOptions {
+    fn default() -> Self {
+        JsOptions {
+            metric: "pixel".to_string(),
+            edge_distance: 1f32,
+            value_threshold: 0f32,
+            luma_weight: 0f32,
+            error_threshold: "0".to_string(),
+            percentile: None,
+            percentile_error: None,
+            draw_bbox: false,
+            connectivity: "8".to_string(),
+            find_clusters: false,
+            min_cluster_size: None,
+            max_cluster_size: None,
+            diff_style: "difference".to_string(),
+            highlight_color: [255, 0, 0],
+            error_background: "black".to_string(),
+            tile: None,
+            tile_error: None,
+            channels: None,
+            shift_tolerance: 0,
+            ignore_border: 0,
+            alpha_threshold: None,
+            align: None,
+            normalize: false,
+            error_image: false,
+        }
+    }
+}
+
+impl TryFrom<&JsOptions> for ComparisonOptions {
+    type Error = anyhow::Error;
+    // Try to convert the JavaScript options into comparison options.
+    fn try_from(options: &JsOptions) -> Result<Self, Self::Error> {
+        let tile = match (options.tile, &options.tile_error) {
+            (Some(size), Some(error)) if size > 0 => Some((TileLayout::Size(size, size), Some(Threshold::try_from(error.as_str())?))),
+            (Some(_), _) => return Err(anyhow::Error::msg("tile must be positive and requires tile_error")),
+            (None, _) => None,
+        };
+        let percentile = match (options.percentile, options.percentile_error) {
+            (Some(percentile), Some(limit)) if (0f32..=100f32).contains(&percentile) => Some((percentile, limit)),
+            (None, None) => None,
+            _ => return Err(anyhow::Error::msg("percentile must be in [0-100] and requires percentile_error")),
+        };
+        let channels = match &options.channels {
+            Some(channels) => Some(channels.iter().map(|channel| parse_channel(channel)).collect::<anyhow::Result<Vec<usize>>>()?),
+            None => None,
+        };
+        Ok(ComparisonOptions {
+            metric: Metric::try_from(options.metric.as_str())?,
+            edge_distance: options.edge_distance,
+            value_threshold: (options.value_threshold * 255f32) as u8,
+            float_value_threshold: options.value_threshold,
+            luma_weight: options.luma_weight,
+            tolerance_map: None,
+            zscore_threshold: None,
+            error_threshold: Threshold::try_from(options.error_threshold.as_str())?,
+            percentile,
+            error_histogram: false,
+            suggest_thresholds: false,
+            sweep: None,
+            explain: None,
+            perceptual_hash: None,
+            diff_mask: false,
+            sample: None,
+            draw_bbox: options.draw_bbox,
+            diff_only: false,
+            clamp_output: None,
+            diff_gain: 1f32,
+            tile_size: None,
+            draw_tile_grid: false,
+            connectivity: Connectivity::try_from(options.connectivity.as_str())?,
+            find_clusters: options.find_clusters,
+            min_cluster_size: options.min_cluster_size,
+            max_cluster_size: options.max_cluster_size,
+            diff_style: DiffStyle::try_from(options.diff_style.as_str())?,
+            extra_diff_styles: Vec::new(),
+            highlight_color: image::Rgb(options.highlight_color),
+            error_background: ErrorBackground::try_from(options.error_background.as_str())?,
+            tile,
+            channels,
+            shift_tolerance: options.shift_tolerance,
+            ignore_border: options.ignore_border,
+            alpha_threshold: options.alpha_threshold,
+            align: options.align,
+            normalize: options.normalize,
+            pyramid: None,
+            early_exit: false,
+        })
+    }
+}
+
+// A box written as [min_x, min_y, max_x, max_y] (both corners are inclusive).
+type JsBox = [u32; 4];
+
+// A cluster of different pixels as returned to JavaScript.
+#[derive(Serialize)]
+struct JsCluster {
+    bbox: JsBox,
+    pixel_count: u32,
+    max_error: f32,
+}
+
+// The pixel with the largest error as returned to JavaScript.
+#[derive(Serialize)]
+struct JsPixelError {
+    x: u32,
+    y: u32,
+    r_diff: f32,
+    g_diff: f32,
+    b_diff: f32,
+}
+
+// The mean and maximum FLIP error as returned to JavaScript.
+#[derive(Serialize)]
+struct JsFlipStats {
+    mean: f32,
+    max: f32,
+}
+
+// The mean and maximum error of the frequency bins as returned to JavaScript.
+#[derive(Serialize)]
+struct JsFftStats {
+    mean: f32,
+    max: f32,
+}
+
+// The Hausdorff distance between the edges as returned to JavaScript.
+#[derive(Serialize)]
+struct JsEdgeStats {
+    hausdorff: f32,
+    edge_pixels: [u32; 2],
+}
+
+// The comparison outcome as returned to JavaScript.
+#[derive(Serialize)]
+struct JsOutcome {
+    matched: bool,
+    size_mismatch: bool,
+    sizes: [(u32, u32); 2],
+    wrong_pixels: u32,
+    total_pixels: u32,
+    wrong_percentage: f32,
+    bbox: Option<JsBox>,
+    clusters: Option<Vec<JsCluster>>,
+    max_error_pixel: Option<JsPixelError>,
+    alignment_offset: Option<(i32, i32)>,
+    flip: Option<JsFlipStats>,
+    fft: Option<JsFftStats>,
+    edges: Option<JsEdgeStats>,
+    percentile_error: Option<f32>,
+    #[serde(with = "serde_bytes")]
+    error_image: Option<Vec<u8>>, // The PNG-encoded error image (if requested).
+}
+
+// Compare two encoded images (in any format supported by the image crate) using the options given as JSON,
+// and return the outcome as a JavaScript object.
+fn compare_encoded_images(img1_bytes: &[u8], img2_bytes: &[u8], options_json: &str) -> anyhow::Result<JsOutcome> {
+    let js_options: JsOptions = if options_json.trim().is_empty() { JsOptions::default() } else { serde_json::from_str(options_json)? };
+    let options = ComparisonOptions::try_from(&js_options)?;
+
+    let img1 = image::load_from_memory(img1_bytes).map_err(|err| anyhow::Error::new(err).context("Failed to decode the first image"))?;
+    let img2 = image::load_from_memory(img2_bytes).map_err(|err| anyhow::Error::new(err).context("Failed to decode the second image"))?;
+    let (outcome, error_img) = crate::compare(&ImagePair::new(img1, img2), &options)?;
+
+    let error_image = match error_img {
+        Some(error_img) if js_options.error_image => {
+            let mut bytes = Vec::new();
+            error_img.write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+            Some(bytes)
+        },
+        _ => None,
+    };
+
+    let to_js_box = |bbox: &crate::BoundingBox| [bbox.min.0, bbox.min.1, bbox.max.0, bbox.max.1];
+    Ok(JsOutcome {
+        matched: outcome.matched,
+        size_mismatch: outcome.is_size_mismatch(),
+        sizes: outcome.sizes,
+        wrong_pixels: outcome.wrong_pixels,
+        total_pixels: outcome.total_pixels,
+        wrong_percentage: outcome.wrong_percentage(),
+        bbox: outcome.bbox.as_ref().map(to_js_box),
+        clusters: outcome.clusters.as_ref().map(|clusters| clusters.iter().map(|cluster| JsCluster {
+            bbox: to_js_box(&cluster.bbox),
+            pixel_count: cluster.pixel_count,
+            max_error: cluster.max_error,
+        }).collect()),
+        max_error_pixel: outcome.max_error_pixel.map(|pixel| JsPixelError {
+            x: pixel.x,
+            y: pixel.y,
+            r_diff: pixel.channel_errors[0],
+            g_diff: pixel.channel_errors[1],
+            b_diff: pixel.channel_errors[2],
+        }),
+        alignment_offset: outcome.alignment_offset,
+        flip: outcome.flip.map(|flip| JsFlipStats { mean: flip.mean, max: flip.max }),
+        fft: outcome.fft.map(|fft| JsFftStats { mean: fft.mean, max: fft.max }),
+        edges: outcome.edges.map(|edges| JsEdgeStats { hausdorff: edges.hausdorff, edge_pixels: edges.edge_pixels }),
+        percentile_error: outcome.percentile_error,
+        error_image,
+    })
+}
+
+// Compare two encoded images using the options given as JSON (which may be empty to use the defaults).
+// Comparison errors (e.g. invalid options or undecodable images) are thrown as JavaScript errors.
+#[wasm_bindgen]
+pub fn compare_images(img1_bytes: &[u8], img2_bytes: &[u8], options_json: &str) -> Result<JsValue, JsError> {
+    let outcome = compare_encoded_images(img1_bytes, img2_bytes, options_json).map_err(|err| JsError::new(&format!("{err:#}")))?;
+    serde_wasm_bindgen::to_value(&outcome).map_err(|err| JsError::new(&err.to_string()))
+}
@@ -0,0 +1,99 @@
+use std::{fs, path::{Path, PathBuf}, sync::atomic::{AtomicBool, AtomicU8, Ordering}, thread, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+// The interval at which the watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How long the files must stay unchanged after a modification before they are compared.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+// Set once Ctrl-C is pressed, which ends the watch after the current comparison.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// The exit code of the most recent comparison, which the process exits with if Ctrl-C is pressed again during a comparison.
+static LAST_EXIT_CODE: AtomicU8 = AtomicU8::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // Only async-signal-safe functions can be called here, so the process exits right away.
+        unsafe { libc::_exit(LAST_EXIT_CODE.load(Ordering::SeqCst) as libc::c_int) };
+    }
+}
+
+// Handle Ctrl-C (SIGINT) by ending the watch, instead of killing the process, so it exits with the code of the most recent comparison.
+// Elsewhere than on Unix, Ctrl-C still kills the process.
+pub fn handle_interrupts() {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+// Record the exit code of the most recent comparison.
+pub fn set_exit_code(code: u8) {
+    LAST_EXIT_CODE.store(code, Ordering::SeqCst);
+}
+
+// The size and modification time of a file (None if it can't be read, e.g. while it is being replaced).
+type Stamp = Option<(u64, SystemTime)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+// Watches files for modifications by polling their sizes and modification times.
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    stamps: Vec<Stamp>,
+}
+
+impl Watcher {
+    // Start watching the given files in their current state.
+    pub fn new(paths: &[&str]) -> Self {
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        let stamps = paths.iter().map(|path| stamp(path)).collect();
+        Watcher { paths, stamps }
+    }
+
+    fn current_stamps(&self) -> Vec<Stamp> {
+        self.paths.iter().map(|path| stamp(path)).collect()
+    }
+
+    // Wait until any of the files is modified, then until all of them have stopped changing,
+    // since renderers often write their files incrementally. Return false if Ctrl-C was pressed while waiting.
+    pub fn wait_for_change(&mut self) -> bool {
+        let mut stamps = loop {
+            if INTERRUPTED.load(Ordering::SeqCst) { return false; }
+            thread::sleep(POLL_INTERVAL);
+            let stamps = self.current_stamps();
+            if stamps != self.stamps { break stamps; }
+        };
+        // The files are considered written once their sizes and modification times haven't changed for the settle time.
+        let mut unchanged = Duration::ZERO;
+        while unchanged < SETTLE_TIME {
+            if INTERRUPTED.load(Ordering::SeqCst) { return false; }
+            thread::sleep(POLL_INTERVAL);
+            let current = self.current_stamps();
+            if current == stamps {
+                unchanged += POLL_INTERVAL;
+            } else {
+                (stamps, unchanged) = (current, Duration::ZERO);
+            }
+        }
+        self.stamps = stamps;
+        true
+    }
+}
+
+// Return the current time of day as HH:MM:SS, in local time on Unix and in UTC elsewhere.
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    #[cfg(unix)]
+    {
+        let time = seconds as libc::time_t;
+        let mut local = unsafe { std::mem::zeroed::<libc::tm>() };
+        if !unsafe { libc::localtime_r(&time, &mut local) }.is_null() {
+            return format!("{:02}:{:02}:{:02}", local.tm_hour, local.tm_min, local.tm_sec);
+        }
+    }
+    format!("{:02}:{:02}:{:02}", seconds / 3600 % 24, seconds / 60 % 60, seconds % 60)
+}
@@ -0,0 +1,35 @@
+// An adaptive value threshold computed from the statistics of the per-pixel errors,
+// so the global noise of the images (e.g. from lossy compression or dithering) isn't counted as a difference.
+
+use crate::{BoundingBox, ImagePair};
+
+// The mean and standard deviation of the per-pixel errors, and the value threshold derived from them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZScoreThreshold {
+    pub z: f32,                 // The number of standard deviations above the mean at which a pixel becomes different.
+    pub mean: f32,              // The mean of the per-pixel errors (the largest channel error, in [0-1] for 8-bit images).
+    pub deviation: f32,         // The standard deviation of the per-pixel errors.
+    pub threshold: f32,         // The value threshold mean + z * deviation.
+}
+
+// Return the threshold at z standard deviations above the mean of the errors of the pixels in the region (over the given channels),
+// skipping the pixels that are transparent in both images if there is an alpha threshold (see `ImagePair::is_transparent`).
+pub fn zscore_threshold(images: &ImagePair, region: &BoundingBox, channels: &[usize], alpha_threshold: Option<u8>, z: f32) -> ZScoreThreshold {
+    let (mut sum, mut sum_squares, mut count) = (0f64, 0f64, 0f64);
+    for y in region.min.1..=region.max.1 {
+        for x in region.min.0..=region.max.0 {
+            if alpha_threshold.is_some_and(|alpha_threshold| images.is_transparent(x, y, alpha_threshold)) {
+                continue;
+            }
+            let error = images.pixel_error(x, y, channels) as f64;
+            sum += error;
+            sum_squares += error * error;
+            count += 1f64;
+        }
+    }
+    let (mean, deviation) = if count == 0f64 { (0f64, 0f64) } else {
+        let mean = sum / count;
+        (mean, (sum_squares / count - mean * mean).max(0f64).sqrt())
+    };
+    ZScoreThreshold { z, mean: mean as f32, deviation: deviation as f32, threshold: (mean + z as f64 * deviation) as f32 }
+}
@@ -0,0 +1,37 @@
+// Helpers shared by the integration tests, which run the binary on small images written to a temporary directory.
+// Each test file uses only some of them.
+#![allow(dead_code)]
+
+use std::{path::{Path, PathBuf}, process::{Command, Output}};
+
+use image::{Rgba, RgbaImage};
+
+// Return a fresh temporary directory for the given test, so the tests can run in parallel.
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create the test directory");
+    dir
+}
+
+// Write an image of the given size filled with the given color (with the pixels of `changed` set to `changed_color`) as a PNG,
+// and return its path.
+pub fn write_image(dir: &Path, name: &str, size: (u32, u32), color: [u8; 4], changed: &[(u32, u32)], changed_color: [u8; 4]) -> String {
+    let mut image = RgbaImage::from_pixel(size.0, size.1, Rgba(color));
+    for &(x, y) in changed {
+        image.put_pixel(x, y, Rgba(changed_color));
+    }
+    let path = dir.join(name);
+    image.save(&path).expect("Failed to write a test image");
+    path.to_string_lossy().into_owned()
+}
+
+// Run the binary with the given arguments.
+pub fn imgcmp(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_imgcmp-rs")).args(args).output().expect("Failed to run imgcmp-rs")
+}
+
+// Run the binary with the given arguments and return its exit code.
+pub fn exit_code(args: &[&str]) -> i32 {
+    imgcmp(args).status.code().expect("imgcmp-rs was terminated by a signal")
+}
@@ -0,0 +1,92 @@
+// The exit codes of the binary, which scripts rely on to tell bad input from a regression.
+
+mod common;
+
+use common::{exit_code, test_dir, write_image};
+
+const GRAY: [u8; 4] = [128, 128, 128, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+#[test]
+fn matching_images_exit_with_0() {
+    let dir = test_dir("exit_match");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let second = write_image(&dir, "second.png", (8, 8), GRAY, &[], GRAY);
+    assert_eq!(exit_code(&[&first, &second, "--no-fast-path"]), 0);
+}
+
+#[test]
+fn different_pixels_exit_with_1() {
+    let dir = test_dir("exit_mismatch");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let second = write_image(&dir, "second.png", (8, 8), GRAY, &[(3, 4)], WHITE);
+    assert_eq!(exit_code(&[&first, &second]), 1);
+}
+
+#[test]
+fn different_sizes_exit_with_2() {
+    let dir = test_dir("exit_size_mismatch");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let second = write_image(&dir, "second.png", (8, 9), GRAY, &[], GRAY);
+    assert_eq!(exit_code(&[&first, &second]), 2);
+}
+
+#[test]
+fn unreadable_images_exit_with_3() {
+    let dir = test_dir("exit_io_error");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let missing = dir.join("missing.png").to_string_lossy().into_owned();
+    let corrupt = dir.join("corrupt.png");
+    std::fs::write(&corrupt, b"not a png").unwrap();
+    assert_eq!(exit_code(&[&first, &missing]), 3);
+    assert_eq!(exit_code(&[&first, &corrupt.to_string_lossy()]), 3);
+}
+
+#[test]
+fn invalid_arguments_exit_with_4() {
+    let dir = test_dir("exit_invalid_arguments");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let second = write_image(&dir, "second.png", (8, 8), GRAY, &[], GRAY);
+    assert_eq!(exit_code(&[&first, &second, "--threshold", "abc"]), 4);
+    // The border is valid syntax, but it can't be applied to images this small (which are decoded since their bytes are the same).
+    assert_eq!(exit_code(&[&first, &second, "--ignore-border", "4", "--no-fast-path"]), 4);
+}
+
+#[test]
+fn missing_baseline_exits_with_5() {
+    let dir = test_dir("exit_missing_baseline");
+    let missing = dir.join("baseline.png").to_string_lossy().into_owned();
+    let second = write_image(&dir, "second.png", (8, 8), GRAY, &[], GRAY);
+    assert_eq!(exit_code(&[&missing, &second, "--missing-baseline", "fail"]), 5);
+    // Without --missing-baseline fail, a missing baseline is an unreadable image.
+    assert_eq!(exit_code(&[&missing, &second]), 3);
+}
+
+#[test]
+fn custom_exit_codes_replace_the_failures() {
+    let dir = test_dir("exit_custom");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let different = write_image(&dir, "different.png", (8, 8), GRAY, &[(0, 0)], WHITE);
+    let larger = write_image(&dir, "larger.png", (9, 8), GRAY, &[], GRAY);
+    let missing = dir.join("missing.png").to_string_lossy().into_owned();
+    assert_eq!(exit_code(&[&first, &different, "--exit-code-on-mismatch", "10"]), 10);
+    assert_eq!(exit_code(&[&first, &larger, "--exit-code-on-mismatch", "10"]), 10);
+    assert_eq!(exit_code(&[&first, &missing, "--exit-code-on-error", "20"]), 20);
+    assert_eq!(exit_code(&[&first, &different, "--ignore-border", "4", "--exit-code-on-error", "20"]), 20);
+    // A match still exits with 0, and the other failures keep their codes.
+    assert_eq!(exit_code(&[&first, &first, "--exit-code-on-mismatch", "10", "--exit-code-on-error", "20"]), 0);
+    assert_eq!(exit_code(&[&first, &missing, "--exit-code-on-mismatch", "10"]), 3);
+    assert_eq!(exit_code(&[&first, &different, "--exit-code-on-error", "20"]), 1);
+}
+
+#[test]
+fn zero_exit_codes_require_allow_zero_exit() {
+    let dir = test_dir("exit_zero");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let different = write_image(&dir, "different.png", (8, 8), GRAY, &[(0, 0)], WHITE);
+    let missing = dir.join("missing.png").to_string_lossy().into_owned();
+    assert_eq!(exit_code(&[&first, &different, "--exit-code-on-mismatch", "0"]), 4);
+    assert_eq!(exit_code(&[&first, &missing, "--exit-code-on-error", "0"]), 4);
+    assert_eq!(exit_code(&[&first, &different, "--exit-code-on-mismatch", "0", "--allow-zero-exit"]), 0);
+    assert_eq!(exit_code(&[&first, &missing, "--exit-code-on-error", "0", "--allow-zero-exit"]), 0);
+}
@@ -0,0 +1,27 @@
+// Stopping a batch run at the first failure with --fail-fast.
+
+mod common;
+
+use common::{imgcmp, test_dir, write_image};
+
+const GRAY: [u8; 4] = [128, 128, 128, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+// Run a batch of the given number of pairs, of which only the first one differs, with --fail-fast and return its output.
+fn fail_fast_output(name: &str, pair_count: usize) -> String {
+    let dir = test_dir(name);
+    let expected = write_image(&dir, "expected.png", (8, 8), GRAY, &[], GRAY);
+    let different = write_image(&dir, "different.png", (8, 8), GRAY, &[(0, 0)], WHITE);
+    let pairs: Vec<String> = (0..pair_count).map(|index| format!("{}\t{}\n", expected, if index == 0 { &different } else { &expected })).collect();
+    let pairs_file = dir.join("pairs.txt");
+    std::fs::write(&pairs_file, pairs.concat()).unwrap();
+    let output = imgcmp(&["--pairs-file", &pairs_file.to_string_lossy(), "--fail-fast"]);
+    assert_eq!(output.status.code(), Some(1));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn fail_fast_counts_the_skipped_pairs() {
+    assert!(fail_fast_output("fail_fast_one", 2).contains("skipping 1 pair."));
+    assert!(fail_fast_output("fail_fast_many", 3).contains("skipping 2 pairs."));
+}
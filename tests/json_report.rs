@@ -0,0 +1,22 @@
+// The path of the JSON report, which can't be stdout.
+
+mod common;
+
+use std::process::Command;
+
+use common::{test_dir, write_image};
+
+const GRAY: [u8; 4] = [128, 128, 128, 255];
+
+#[test]
+fn json_report_to_stdout_is_rejected() {
+    let dir = test_dir("json_report_stdout");
+    let first = write_image(&dir, "first.png", (8, 8), GRAY, &[], GRAY);
+    let second = write_image(&dir, "second.png", (8, 8), GRAY, &[], GRAY);
+    // The binary runs in the test directory, so a report written to a file named - would show up there.
+    let output = Command::new(env!("CARGO_BIN_EXE_imgcmp-rs")).args([&first, &second, "--json", "-"]).current_dir(&dir)
+        .output().expect("Failed to run imgcmp-rs");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--json -"));
+    assert!(!dir.join("-").exists());
+}
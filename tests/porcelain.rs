@@ -0,0 +1,29 @@
+// The --porcelain line, whose tab-separated format is a contract with the scripts that parse it.
+
+mod common;
+
+use common::{imgcmp, test_dir, write_image};
+
+const GRAY: [u8; 4] = [128, 128, 128, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+// Return the standard output of the binary run with --porcelain and the given arguments.
+fn porcelain(args: &[&str]) -> String {
+    let output = imgcmp(&[args, &["--porcelain"]].concat());
+    String::from_utf8(output.stdout).expect("The porcelain line isn't UTF-8")
+}
+
+#[test]
+fn porcelain_prints_a_single_tab_separated_line() {
+    let dir = test_dir("porcelain");
+    let first = write_image(&dir, "first.png", (4, 4), GRAY, &[], GRAY);
+    let different = write_image(&dir, "different.png", (4, 4), GRAY, &[(0, 0), (1, 0), (2, 0)], WHITE);
+    let larger = write_image(&dir, "larger.png", (4, 5), GRAY, &[], GRAY);
+    let missing = dir.join("missing.png").to_string_lossy().into_owned();
+    let output = dir.join("diff.png").to_string_lossy().into_owned();
+
+    assert_eq!(porcelain(&[&first, &first, "--no-fast-path"]), "match\t0\t16\t0.0000\t-\n");
+    assert_eq!(porcelain(&[&first, &different, "-o", &output]), format!("mismatch\t3\t16\t18.7500\t{}\n", output));
+    assert_eq!(porcelain(&[&first, &larger]), "size-mismatch\t-\t-\t-\t-\n");
+    assert_eq!(porcelain(&[&first, &missing]), "error\t-\t-\t-\t-\n");
+}
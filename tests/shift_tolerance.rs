@@ -0,0 +1,43 @@
+// The shift tolerance on a large image, where almost every pixel differs and must find its match in the neighborhood search.
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use imgcmp::{ComparisonOptions, ImagePair, compare};
+
+// The size of the images, large enough that a slow neighborhood search shows.
+const SIZE: u32 = 1024;
+
+// Return a gradient and its copy shifted right by one pixel (whose first column is repeated).
+fn shifted_gradients() -> ImagePair {
+    let gradient = RgbaImage::from_fn(SIZE, SIZE, |x, y| Rgba([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 255]));
+    let shifted = RgbaImage::from_fn(SIZE, SIZE, |x, y| *gradient.get_pixel(x.saturating_sub(1), y));
+    ImagePair::new(DynamicImage::ImageRgba8(gradient), DynamicImage::ImageRgba8(shifted))
+}
+
+#[test]
+fn shifted_gradient_matches_with_a_shift_tolerance() {
+    let images = shifted_gradients();
+    let (outcome, _) = compare(&images, &ComparisonOptions { shift_tolerance: 1, ..ComparisonOptions::default() }).unwrap();
+    assert!(outcome.matched);
+    assert_eq!(outcome.wrong_pixels, 0);
+}
+
+#[test]
+fn shifted_gradient_mismatches_without_a_shift_tolerance() {
+    let images = shifted_gradients();
+    let (outcome, _) = compare(&images, &ComparisonOptions::default()).unwrap();
+    assert!(!outcome.matched);
+    // Every pixel but those of the repeated first column differs.
+    assert_eq!(outcome.wrong_pixels, (SIZE - 1) * SIZE);
+}
+
+#[test]
+fn huge_shift_tolerance_searches_the_whole_image() {
+    // A pixel that moved to the opposite corner is still found, and the radius doesn't overflow the neighborhood bounds.
+    let first = RgbaImage::from_fn(8, 8, |x, y| if (x, y) == (0, 0) { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 0, 255]) });
+    let second = RgbaImage::from_fn(8, 8, |x, y| if (x, y) == (7, 7) { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 0, 255]) });
+    let images = ImagePair::new(DynamicImage::ImageRgba8(first), DynamicImage::ImageRgba8(second));
+    let (outcome, _) = compare(&images, &ComparisonOptions { shift_tolerance: u32::MAX, ..ComparisonOptions::default() }).unwrap();
+    assert!(outcome.matched);
+    assert_eq!(outcome.wrong_pixels, 0);
+}
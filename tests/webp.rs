@@ -0,0 +1,67 @@
+// The round trip of a known image through WebP, whose lossy compression artifacts must be detected (and tolerated by a loose threshold).
+
+mod common;
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{ColorType, Rgba, RgbaImage, codecs::webp::{WebPEncoder, WebPQuality}};
+
+use common::{exit_code, test_dir};
+
+// Return a 64x64 image with gradients and a checkerboard, whose sharp edges lossy compression can't keep exactly.
+fn known_image() -> RgbaImage {
+    RgbaImage::from_fn(64, 64, |x, y| {
+        let checker = if (x / 4 + y / 4) % 2 == 0 { 64 } else { 0 };
+        Rgba([(x * 4) as u8, (y * 4) as u8, 128 + checker, 255])
+    })
+}
+
+// Encode the image as a WebP with the given quality.
+fn write_webp(path: &Path, image: &RgbaImage, quality: WebPQuality) {
+    let file = File::create(path).expect("Failed to create the WebP image");
+    WebPEncoder::new_with_quality(BufWriter::new(file), quality)
+        .encode(image.as_raw(), image.width(), image.height(), ColorType::Rgba8)
+        .expect("Failed to encode the WebP image");
+}
+
+#[test]
+fn lossy_webp_artifacts_are_detected() {
+    let dir = test_dir("webp_lossy");
+    let image = known_image();
+    let master = dir.join("master.png");
+    image.save(&master).unwrap();
+    let lossy = dir.join("lossy.webp");
+    write_webp(&lossy, &image, WebPQuality::lossy(50));
+    let [master, lossy] = [master, lossy].map(|path| path.to_string_lossy().into_owned());
+
+    // Any difference fails, while a threshold above the artifacts tolerates them.
+    assert_eq!(exit_code(&[&master, &lossy]), 1);
+    assert_eq!(exit_code(&[&master, &lossy, "-t", "0.5"]), 0);
+}
+
+#[test]
+fn lossless_webp_matches_exactly() {
+    let dir = test_dir("webp_lossless");
+    let image = known_image();
+    let master = dir.join("master.png");
+    image.save(&master).unwrap();
+    let lossless = dir.join("lossless.webp");
+    write_webp(&lossless, &image, WebPQuality::lossless());
+    let [master, lossless] = [master, lossless].map(|path| path.to_string_lossy().into_owned());
+    assert_eq!(exit_code(&[&master, &lossless]), 0);
+}
+
+#[test]
+fn error_image_is_saved_as_webp() {
+    let dir = test_dir("webp_output");
+    let image = known_image();
+    let master = dir.join("master.png");
+    image.save(&master).unwrap();
+    let lossy = dir.join("lossy.webp");
+    write_webp(&lossy, &image, WebPQuality::lossy(50));
+    let output = dir.join("diff.webp");
+    let [master, lossy, output_path] = [&master, &lossy, &output].map(|path| path.to_string_lossy().into_owned());
+    assert_eq!(exit_code(&[&master, &lossy, "-o", &output_path]), 1);
+    let error_image = image::open(&output).expect("The WebP error image can't be decoded");
+    assert_eq!((error_image.width(), error_image.height()), (64, 64));
+}